@@ -2,6 +2,19 @@ const loginLink = document.getElementById("login-link");
 const logoutLink = document.getElementById("logout-link");
 const protectImg = document.getElementById("protected-img");
 
+// The auth service names the cookie __Host-csrf_token in its host-only mode.
+function getCsrfToken() {
+    for (const name of ["__Host-csrf_token=", "csrf_token="]) {
+        const cookie = document.cookie
+            .split("; ")
+            .find(row => row.startsWith(name));
+        if (cookie) {
+            return cookie.substring(name.length);
+        }
+    }
+    return "";
+}
+
 logoutLink.addEventListener("click", (e) => {
     e.preventDefault();
 
@@ -10,6 +23,11 @@ logoutLink.addEventListener("click", (e) => {
     fetch(url, {
         method: 'POST',
         credentials: 'include', // This will include cookies in the request
+        headers: {
+            // The auth service's CSRF cookie is visible here because cookies are not
+            // scoped by port.
+            'X-CSRF-Token': getCsrfToken(),
+        },
     }).then(response => {
         if (response.ok) {
             loginLink.style.display = "block";
@@ -47,7 +47,8 @@ async fn root() -> impl IntoResponse {
 }
 
 async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
+    // The auth service names the cookie __Host-jwt in its host-only mode.
+    let jwt_cookie = match jar.get("__Host-jwt").or_else(|| jar.get("jwt")) {
         Some(cookie) => cookie,
         None => {
             return StatusCode::UNAUTHORIZED.into_response();
@@ -1,6 +1,7 @@
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
+const resetPasswordSection = document.getElementById("reset-password-section");
 
 const signupLink = document.getElementById("signup-link");
 const twoFALoginLink = document.getElementById("2fa-login-link");
@@ -32,6 +33,29 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// The server sets a CSRF cookie readable by scripts; echoing it in a header
+// proves the request was made by this page and not by another site. With
+// AUTH_COOKIE_HOST_PREFIX it is named __Host-csrf_token.
+function getCsrfToken() {
+    for (const name of ["__Host-csrf_token=", "csrf_token="]) {
+        const cookie = document.cookie
+            .split("; ")
+            .find(row => row.startsWith(name));
+        if (cookie) {
+            return cookie.substring(name.length);
+        }
+    }
+    return "";
+}
+
+function jsonHeaders() {
+    return {
+        'Content-Type': 'application/json',
+        'X-CSRF-Token': getCsrfToken(),
+    };
+}
+
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -42,11 +66,9 @@ loginButton.addEventListener("click", (e) => {
     const email = loginForm.email.value;
     const password = loginForm.password.value;
 
-    fetch('/login', {
+    fetchWithProofOfWork('/login', {
         method: 'POST',
-        headers: {
-            'Content-Type': 'application/json',
-        },
+        headers: jsonHeaders(),
         body: JSON.stringify({ email, password }),
     }).then(response => {
         if (response.status === 206) {
@@ -92,11 +114,9 @@ signupButton.addEventListener("click", (e) => {
     const password = signupForm.password.value;
     const requires2FA = signupForm.twoFA.checked;
 
-    fetch('/signup', {
+    fetchWithProofOfWork('/signup', {
         method: 'POST',
-        headers: {
-            'Content-Type': 'application/json',
-        },
+        headers: jsonHeaders(),
         body: JSON.stringify({ email, password, requires2FA }),
     }).then(response => {
         if (response.ok) {
@@ -132,18 +152,18 @@ TwoFAButton.addEventListener("click", (e) => {
     const email = TwoFAForm.email.value;
     const loginAttemptId = TwoFAForm.login_attempt_id.value;
     const TwoFACode = TwoFAForm.email_code.value;
+    const trustDevice = TwoFAForm.trustDevice.checked;
 
     fetch('/verify-2fa', {
         method: 'POST',
-        headers: {
-            'Content-Type': 'application/json',
-        },
-        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
+        headers: jsonHeaders(),
+        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode, trustDevice }),
     }).then(response => {
         if (response.ok) {
             TwoFAForm.email.value = "";
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
+            TwoFAForm.trustDevice.checked = false;
             TwoFAErrAlter.style.display = "none";
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
@@ -161,4 +181,88 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+const reportLoginSection = document.getElementById("report-login-section");
+const reportLoginForm = document.getElementById("report-login-form");
+const reportLoginButton = document.getElementById("report-login-form-submit");
+const reportLoginErrAlter = document.getElementById("report-login-err-alert");
+
+const resetPasswordForm = document.getElementById("reset-password-form");
+const resetPasswordButton = document.getElementById("reset-password-form-submit");
+const resetPasswordErrAlter = document.getElementById("reset-password-err-alert");
+
+// The "this wasn't me" link in new sign-in emails redirects here with a report
+// token. Nothing is reported until the user confirms.
+const reportToken = new URLSearchParams(window.location.search).get("report_token");
+if (reportToken !== null) {
+    reportLoginForm.token.value = reportToken;
+    history.replaceState(null, "", window.location.pathname);
+
+    loginSection.style.display = "none";
+    twoFASection.style.display = "none";
+    signupSection.style.display = "none";
+    reportLoginSection.style.display = "block";
+}
+
+reportLoginButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const token = reportLoginForm.token.value;
+
+    fetch('/report-login', {
+        method: 'POST',
+        headers: jsonHeaders(),
+        body: JSON.stringify({ token }),
+    }).then(response => {
+        if (response.ok) {
+            reportLoginForm.token.value = "";
+            reportLoginErrAlter.style.display = "none";
+            resetPasswordForm.token.value = token;
+            reportLoginSection.style.display = "none";
+            resetPasswordSection.style.display = "block";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    reportLoginErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    reportLoginErrAlter.style.display = "block";
+                } else {
+                    reportLoginErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+resetPasswordButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const token = resetPasswordForm.token.value;
+    const password = resetPasswordForm.password.value;
+
+    fetch('/reset-password', {
+        method: 'POST',
+        headers: jsonHeaders(),
+        body: JSON.stringify({ token, password }),
+    }).then(response => {
+        if (response.ok) {
+            resetPasswordForm.token.value = "";
+            resetPasswordForm.password.value = "";
+            resetPasswordErrAlter.style.display = "none";
+            alert("Your password has been reset. You can now log in.");
+            loginSection.style.display = "block";
+            resetPasswordSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    resetPasswordErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    resetPasswordErrAlter.style.display = "block";
+                } else {
+                    resetPasswordErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
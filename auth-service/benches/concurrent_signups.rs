@@ -0,0 +1,176 @@
+//! Signup throughput through the signup handler, with the stores the service
+//! uses on Postgres. Each iteration signs up the same number of users one
+//! after another and all at once. If a store made requests wait for each
+//! other, both would take about as long.
+//!
+//! Needs the Postgres instance from `DATABASE_URL`, like the API tests:
+//!
+//! ```sh
+//! cargo bench --bench concurrent_signups
+//! ```
+
+use std::{str::FromStr, sync::Arc};
+
+use argon2::Params;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    Connection, Executor, PgConnection, PgPool,
+};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+use auth_service::{
+    app_state::AppState,
+    domain::{Device, PasswordHasherType, PasswordPolicy},
+    get_postgres_pool,
+    routes::{signup, SignupRequest},
+    services::{
+        argon2_password_hasher::Argon2PasswordHasher,
+        data_stores::{
+            HashmapTwoFACodeStore, HashsetBannedTokenStore, PostgresAuditLogStore,
+            PostgresLoginHistoryStore, PostgresTrustedDeviceStore, PostgresUserStore,
+        },
+        mock_email_client::MockEmailClient,
+        password_pepper::PasswordPepper,
+    },
+    utils::constants::{
+        DATABASE_URL, DEFAULT_ARGON2_MEMORY_COST_KIB, DEFAULT_ARGON2_PARALLELISM,
+        DEFAULT_ARGON2_TIME_COST,
+    },
+};
+
+const SIGNUPS: usize = 16;
+
+fn concurrent_signups(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("Failed to build Tokio runtime");
+    let db_name = Uuid::new_v4().to_string();
+    let pg_pool = runtime.block_on(configure_postgresql(&db_name));
+    let app_state = app_state(&pg_pool);
+
+    let mut group = c.benchmark_group("concurrent_signups");
+    group.throughput(Throughput::Elements(SIGNUPS as u64));
+    group.sample_size(10);
+
+    group.bench_function("one_at_a_time", |b| {
+        b.to_async(&runtime).iter(|| {
+            let app_state = app_state.clone();
+            async move {
+                for _ in 0..SIGNUPS {
+                    sign_up(app_state.clone()).await;
+                }
+            }
+        })
+    });
+
+    group.bench_function("concurrent", |b| {
+        b.to_async(&runtime).iter(|| {
+            let app_state = app_state.clone();
+            async move {
+                let handles: Vec<_> = (0..SIGNUPS)
+                    .map(|_| tokio::spawn(sign_up(app_state.clone())))
+                    .collect();
+
+                for handle in handles {
+                    handle.await.expect("Signup task panicked");
+                }
+            }
+        })
+    });
+
+    group.finish();
+
+    runtime.block_on(async {
+        pg_pool.close().await;
+        delete_database(&db_name).await;
+    });
+}
+
+async fn sign_up(app_state: AppState) {
+    let request = SignupRequest {
+        email: Secret::new(format!("{}@example.com", Uuid::new_v4())),
+        password: Secret::new("password123".to_owned()),
+        requires_2fa: false,
+    };
+    let device = Device::new("127.0.0.1".to_owned(), "criterion".to_owned());
+
+    let response = signup(State(app_state), device, Json(request))
+        .await
+        .into_response();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+fn app_state(pg_pool: &PgPool) -> AppState {
+    AppState::new(
+        Arc::new(PostgresUserStore::new(pg_pool.clone(), password_hasher())),
+        Arc::new(HashsetBannedTokenStore::default()),
+        Arc::new(HashmapTwoFACodeStore::default()),
+        Arc::new(PostgresLoginHistoryStore::new(pg_pool.clone())),
+        Arc::new(PostgresAuditLogStore::new(pg_pool.clone())),
+        Arc::new(PostgresTrustedDeviceStore::new(pg_pool.clone())),
+        Arc::new(MockEmailClient),
+        Arc::new(PasswordPolicy::default()),
+    )
+}
+
+fn password_hasher() -> PasswordHasherType {
+    let password_hash_params = Params::new(
+        DEFAULT_ARGON2_MEMORY_COST_KIB,
+        DEFAULT_ARGON2_TIME_COST,
+        DEFAULT_ARGON2_PARALLELISM,
+        None,
+    )
+    .unwrap();
+
+    Arc::new(Argon2PasswordHasher::new(
+        password_hash_params,
+        PasswordPepper::default(),
+    ))
+}
+
+async fn configure_postgresql(db_name: &str) -> PgPool {
+    let connection = PgPoolOptions::new()
+        .connect(DATABASE_URL.expose_secret())
+        .await
+        .expect("Failed to create Postgres connection pool.");
+
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, db_name).as_str())
+        .await
+        .expect("Failed to create database.");
+
+    let pg_pool = get_postgres_pool(&Secret::new(format!(
+        "{}/{}",
+        DATABASE_URL.expose_secret(),
+        db_name
+    )))
+    .await
+    .expect("Failed to create Postgres connection pool!");
+
+    sqlx::migrate!()
+        .run(&pg_pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    pg_pool
+}
+
+async fn delete_database(db_name: &str) {
+    let connection_options = PgConnectOptions::from_str(DATABASE_URL.expose_secret())
+        .expect("Failed to parse PostgreSQL connection string");
+
+    let mut connection = PgConnection::connect_with(&connection_options)
+        .await
+        .expect("Failed to connect to Postgres");
+
+    connection
+        .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, db_name).as_str())
+        .await
+        .expect("Failed to drop the database.");
+}
+
+criterion_group!(benches, concurrent_signups);
+criterion_main!(benches);
\ No newline at end of file
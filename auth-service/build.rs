@@ -2,4 +2,5 @@
 fn main() {
     // trigger recompilation when a new migration is added
     println!("cargo:rerun-if-changed=migrations");
-}
+    println!("cargo:rerun-if-changed=migrations_sqlite");
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS login_history(
+   id BIGSERIAL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   ip_address TEXT NOT NULL,
+   user_agent TEXT NOT NULL,
+   logged_in_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   session_token TEXT,
+   report_token_hash TEXT NOT NULL UNIQUE,
+   reported_at TIMESTAMPTZ,
+   password_reset_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS login_history_email_idx ON login_history(email);
\ No newline at end of file
@@ -0,0 +1,30 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS audit_events(
+   sequence BIGINT PRIMARY KEY,
+   event_type TEXT NOT NULL,
+   email TEXT,
+   ip_address TEXT,
+   details TEXT,
+   occurred_at TIMESTAMPTZ NOT NULL,
+   previous_hash TEXT NOT NULL,
+   hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_email_idx ON audit_events(email);
+CREATE INDEX IF NOT EXISTS audit_events_event_type_idx ON audit_events(event_type);
+CREATE INDEX IF NOT EXISTS audit_events_occurred_at_idx ON audit_events(occurred_at);
+
+-- The hash chain detects changes, this keeps the application from making them.
+CREATE OR REPLACE FUNCTION reject_audit_event_changes() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+   BEFORE UPDATE OR DELETE ON audit_events
+   FOR EACH ROW EXECUTE FUNCTION reject_audit_event_changes();
+
+CREATE TRIGGER audit_events_no_truncate
+   BEFORE TRUNCATE ON audit_events
+   FOR EACH STATEMENT EXECUTE FUNCTION reject_audit_event_changes();
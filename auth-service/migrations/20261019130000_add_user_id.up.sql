@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Existing users get a random id. The email stays the primary key, so the
+-- tables that reference users keep working.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid();
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_id_idx ON users(id);
\ No newline at end of file
@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_email_lower_idx;
+
+ALTER TABLE login_history
+   DROP CONSTRAINT login_history_email_fkey,
+   ADD CONSTRAINT login_history_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
+
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_email_fkey,
+   ADD CONSTRAINT trusted_devices_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
\ No newline at end of file
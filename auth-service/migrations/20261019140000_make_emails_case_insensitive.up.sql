@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- Emails that only differ in case belong to one user. Duplicates have to be
+-- resolved first, `cargo run --bin report_duplicate_emails` lists them.
+DO $$
+BEGIN
+   IF EXISTS (SELECT 1 FROM users GROUP BY lower(email) HAVING count(*) > 1) THEN
+      RAISE EXCEPTION 'users has emails that only differ in case, run report_duplicate_emails to list them';
+   END IF;
+END;
+$$;
+
+-- Lets the domains below be lowercased without breaking the references.
+ALTER TABLE login_history
+   DROP CONSTRAINT login_history_email_fkey,
+   ADD CONSTRAINT login_history_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_email_fkey,
+   ADD CONSTRAINT trusted_devices_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- Domains are stored in lowercase, local parts as the user typed them.
+UPDATE users
+SET email = left(email, -strpos(reverse(email), '@')) || lower(right(email, strpos(reverse(email), '@')))
+WHERE email <> left(email, -strpos(reverse(email), '@')) || lower(right(email, strpos(reverse(email), '@')));
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users(lower(email));
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_created_at_idx;
+
+ALTER TABLE users
+   DROP COLUMN IF EXISTS email_verified,
+   DROP COLUMN IF EXISTS display_name,
+   DROP COLUMN IF EXISTS created_at;
\ No newline at end of file
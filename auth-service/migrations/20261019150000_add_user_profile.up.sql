@@ -0,0 +1,8 @@
+-- Add up migration script here
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   ADD COLUMN IF NOT EXISTS display_name TEXT,
+   ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Users are listed in the order they signed up.
+CREATE INDEX IF NOT EXISTS users_created_at_idx ON users(created_at, id);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS two_fa_codes;
+DROP TABLE IF EXISTS banned_tokens;
\ No newline at end of file
@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   token_hash TEXT PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens(expires_at);
+
+-- Keyed hashes of pending 2FA logins. The sequence behind id orders the
+-- attempts of a user.
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   id BIGSERIAL PRIMARY KEY,
+   attempt_hash TEXT NOT NULL UNIQUE,
+   email_hash TEXT NOT NULL,
+   code_hash TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_hash_idx ON two_fa_codes(email_hash);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes(expires_at);
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+-- The tokens cannot be recovered, so their sessions can no longer be revoked.
+UPDATE login_history SET session_token_hash = NULL;
+
+ALTER TABLE login_history RENAME COLUMN session_token_hash TO session_token;
\ No newline at end of file
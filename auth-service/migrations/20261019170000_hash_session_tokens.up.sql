@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Only a hash of each session token is kept, the one banned tokens are stored by.
+UPDATE login_history
+   SET session_token = encode(sha256(convert_to(session_token, 'UTF8')), 'hex')
+   WHERE session_token IS NOT NULL;
+
+ALTER TABLE login_history RENAME COLUMN session_token TO session_token_hash;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS audit_events_email_lower_idx;
+
+CREATE INDEX IF NOT EXISTS audit_events_email_idx ON audit_events(email);
\ No newline at end of file
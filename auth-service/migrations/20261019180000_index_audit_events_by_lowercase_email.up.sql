@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Audit events are filtered by email regardless of case.
+DROP INDEX IF EXISTS audit_events_email_idx;
+
+CREATE INDEX IF NOT EXISTS audit_events_email_lower_idx ON audit_events(lower(email));
\ No newline at end of file
@@ -0,0 +1,31 @@
+-- Add down migration script here
+ALTER TABLE login_history ADD COLUMN IF NOT EXISTS email TEXT;
+ALTER TABLE trusted_devices ADD COLUMN IF NOT EXISTS email TEXT;
+
+UPDATE login_history AS l SET email = u.email FROM users AS u WHERE u.id = l.user_id;
+UPDATE trusted_devices AS d SET email = u.email FROM users AS u WHERE u.id = d.user_id;
+
+ALTER TABLE login_history
+   DROP CONSTRAINT login_history_user_id_fkey,
+   DROP COLUMN user_id,
+   ALTER COLUMN email SET NOT NULL;
+
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_user_id_fkey,
+   DROP COLUMN user_id,
+   ALTER COLUMN email SET NOT NULL;
+
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD CONSTRAINT users_pkey PRIMARY KEY (email);
+CREATE UNIQUE INDEX IF NOT EXISTS users_id_idx ON users(id);
+
+ALTER TABLE login_history
+   ADD CONSTRAINT login_history_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE trusted_devices
+   ADD CONSTRAINT trusted_devices_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE INDEX IF NOT EXISTS login_history_email_idx ON login_history(email);
+CREATE INDEX IF NOT EXISTS trusted_devices_email_idx ON trusted_devices(email);
\ No newline at end of file
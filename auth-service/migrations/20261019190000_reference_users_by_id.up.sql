@@ -0,0 +1,35 @@
+-- Add up migration script here
+-- The login history and trusted devices reference users by id, so the email
+-- no longer has to be the primary key. The audit log keeps only the email: it
+-- never referenced users, outlives them and cannot be updated.
+ALTER TABLE login_history ADD COLUMN IF NOT EXISTS user_id UUID;
+ALTER TABLE trusted_devices ADD COLUMN IF NOT EXISTS user_id UUID;
+
+UPDATE login_history AS l SET user_id = u.id FROM users AS u WHERE u.email = l.email;
+UPDATE trusted_devices AS d SET user_id = u.id FROM users AS u WHERE u.email = d.email;
+
+ALTER TABLE login_history
+   DROP CONSTRAINT login_history_email_fkey,
+   DROP COLUMN email,
+   ALTER COLUMN user_id SET NOT NULL;
+
+ALTER TABLE trusted_devices
+   DROP CONSTRAINT trusted_devices_email_fkey,
+   DROP COLUMN email,
+   ALTER COLUMN user_id SET NOT NULL;
+
+-- The unique index on lower(email) still keeps emails unique.
+ALTER TABLE users
+   DROP CONSTRAINT users_pkey,
+   ADD CONSTRAINT users_pkey PRIMARY KEY USING INDEX users_id_idx;
+
+ALTER TABLE login_history
+   ADD CONSTRAINT login_history_user_id_fkey
+      FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+ALTER TABLE trusted_devices
+   ADD CONSTRAINT trusted_devices_user_id_fkey
+      FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS login_history_user_id_idx ON login_history(user_id);
+CREATE INDEX IF NOT EXISTS trusted_devices_user_id_idx ON trusted_devices(user_id);
\ No newline at end of file
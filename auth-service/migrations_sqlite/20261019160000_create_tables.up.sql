@@ -0,0 +1,38 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS users(
+   id BLOB NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL,
+   -- What users are looked up by. SQLite lowercases only ASCII, so the
+   -- service stores the lowercased email itself.
+   normalized_email TEXT NOT NULL UNIQUE,
+   password_hash TEXT NOT NULL,
+   password_pepper_version INTEGER,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   display_name TEXT,
+   -- Searched instead of display_name, for the same reason.
+   normalized_display_name TEXT,
+   created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS users_created_at_idx ON users(created_at, id);
+
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   token_hash TEXT PRIMARY KEY,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens(expires_at);
+
+-- id aliases the rowid, so the newest pending login of a user has the
+-- highest one.
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   id INTEGER PRIMARY KEY,
+   attempt_hash TEXT NOT NULL UNIQUE,
+   email_hash TEXT NOT NULL,
+   code_hash TEXT NOT NULL,
+   expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS two_fa_codes_email_hash_idx ON two_fa_codes(email_hash);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes(expires_at);
\ No newline at end of file
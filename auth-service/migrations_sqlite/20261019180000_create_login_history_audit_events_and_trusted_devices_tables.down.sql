@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS trusted_devices;
+DROP TABLE IF EXISTS audit_events;
+DROP TABLE IF EXISTS login_history;
\ No newline at end of file
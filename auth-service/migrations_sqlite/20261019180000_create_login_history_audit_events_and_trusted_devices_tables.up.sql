@@ -0,0 +1,57 @@
+-- Add up migration script here
+-- The login history only compares its times, so they are unix seconds. Times
+-- that are read back are RFC 3339 text in UTC, which sorts by time.
+CREATE TABLE IF NOT EXISTS login_history(
+   id INTEGER PRIMARY KEY,
+   user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   ip_address TEXT NOT NULL,
+   user_agent TEXT NOT NULL,
+   logged_in_at INTEGER NOT NULL,
+   session_token_hash TEXT,
+   report_token_hash TEXT NOT NULL UNIQUE,
+   reported_at INTEGER,
+   password_reset_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS login_history_user_id_idx ON login_history(user_id);
+
+CREATE TABLE IF NOT EXISTS audit_events(
+   sequence INTEGER PRIMARY KEY,
+   event_type TEXT NOT NULL,
+   email TEXT,
+   -- Lowercased by the application, which covers non-ASCII letters too.
+   normalized_email TEXT,
+   ip_address TEXT,
+   details TEXT,
+   occurred_at TEXT NOT NULL,
+   previous_hash TEXT NOT NULL,
+   hash TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_normalized_email_idx ON audit_events(normalized_email);
+CREATE INDEX IF NOT EXISTS audit_events_event_type_idx ON audit_events(event_type);
+CREATE INDEX IF NOT EXISTS audit_events_occurred_at_idx ON audit_events(occurred_at);
+
+-- The hash chain detects changes, these keep the application from making them.
+CREATE TRIGGER IF NOT EXISTS audit_events_no_update
+   BEFORE UPDATE ON audit_events
+BEGIN
+   SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_delete
+   BEFORE DELETE ON audit_events
+BEGIN
+   SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TABLE IF NOT EXISTS trusted_devices(
+   id BLOB PRIMARY KEY,
+   user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   ip_address TEXT NOT NULL,
+   user_agent TEXT NOT NULL,
+   created_at TEXT NOT NULL,
+   expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS trusted_devices_user_id_idx ON trusted_devices(user_id);
\ No newline at end of file
@@ -1,34 +1,78 @@
+use secrecy::Secret;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuditLogStore, BannedTokenStore, EmailClient, LoginHistoryStore, PasswordPolicy,
+        TrustedDeviceStore, TwoFACodeStore, UserStore,
+    },
+    utils::proof_of_work::ProofOfWork,
+};
 
 // Using a type alias to improve readability!
-pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+// These stores synchronize internally, so requests use them without waiting
+// for each other.
+pub type UserStoreType = Arc<dyn UserStore + Send + Sync>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + Send + Sync>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + Send + Sync>;
+pub type LoginHistoryStoreType = Arc<dyn LoginHistoryStore + Send + Sync>;
+pub type AuditLogStoreType = Arc<dyn AuditLogStore + Send + Sync>;
+pub type TrustedDeviceStoreType = Arc<dyn TrustedDeviceStore + Send + Sync>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type PasswordPolicyType = Arc<PasswordPolicy>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub login_history_store: LoginHistoryStoreType,
+    pub audit_log_store: AuditLogStoreType,
+    pub trusted_device_store: TrustedDeviceStoreType,
     pub email_client: EmailClientType,
+    pub password_policy: PasswordPolicyType,
+    pub admin_api_token: Option<Secret<String>>,
+    pub proof_of_work: ProofOfWork,
+    pub signup_hide_existing_users: bool,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
+        login_history_store: LoginHistoryStoreType,
+        audit_log_store: AuditLogStoreType,
+        trusted_device_store: TrustedDeviceStoreType,
         email_client: EmailClientType,
+        password_policy: PasswordPolicyType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
+            login_history_store,
+            audit_log_store,
+            trusted_device_store,
             email_client,
+            password_policy,
+            admin_api_token: None,
+            proof_of_work: ProofOfWork::default(),
+            signup_hide_existing_users: false,
         }
     }
+
+    /// Bearer token for the `/admin` routes. Without one they reject every request.
+    pub fn with_admin_api_token(mut self, admin_api_token: Secret<String>) -> Self {
+        self.admin_api_token = Some(admin_api_token);
+        self
+    }
+
+    /// Answers signups with a registered email like any other and emails the
+    /// owner instead, so the response does not tell who has an account.
+    pub fn with_signup_hide_existing_users(mut self, signup_hide_existing_users: bool) -> Self {
+        self.signup_hide_existing_users = signup_hide_existing_users;
+        self
+    }
 }
\ No newline at end of file
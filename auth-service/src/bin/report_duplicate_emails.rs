@@ -0,0 +1,63 @@
+//! Lists users in `DATABASE_URL` whose emails only differ in case.
+//!
+//! Usage: `report_duplicate_emails`
+//!
+//! Emails are unique regardless of case, and the migration that enforces it
+//! stops while there are duplicates. Each group shows the ids and emails of the
+//! accounts, one of which has to be removed or renamed before migrating. Exits
+//! with 1 if there are duplicates.
+
+use std::process::ExitCode;
+
+use auth_service::{get_postgres_pool, utils::constants::DATABASE_URL};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    color_eyre::install().expect("Failed to install color_eyre");
+
+    if std::env::args().len() > 1 {
+        eprintln!("Usage: report_duplicate_emails");
+        return ExitCode::from(2);
+    }
+
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool!");
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, email, lower(email) AS "normalized_email!"
+        FROM users
+        WHERE lower(email) IN (
+            SELECT lower(email)
+            FROM users
+            GROUP BY lower(email)
+            HAVING count(*) > 1
+        )
+        ORDER BY lower(email), email
+        "#
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .expect("Failed to read users");
+
+    if rows.is_empty() {
+        println!("No duplicate emails");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut groups = 0;
+    let mut previous: Option<&str> = None;
+    for row in &rows {
+        if previous != Some(row.normalized_email.as_str()) {
+            println!("{}", row.normalized_email);
+            previous = Some(&row.normalized_email);
+            groups += 1;
+        }
+        println!("  {} {}", row.id, row.email);
+    }
+
+    eprintln!("Emails used by more than one user: {}", groups);
+
+    ExitCode::FAILURE
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+//! Checks the hash chain of the audit log in `DATABASE_URL`.
+//!
+//! Usage: `verify_audit_log [<sequence> <hash>]`
+//!
+//! The output ends with the sequence and hash of the last record. Passing them
+//! to a later run also detects records that were removed from the end of the log
+//! or rewritten together with everything after them.
+
+use std::process::ExitCode;
+
+use auth_service::{
+    domain::{AuditChainVerifier, AuditEventFilter, AuditLogStore},
+    get_postgres_pool,
+    services::data_stores::PostgresAuditLogStore,
+    utils::constants::DATABASE_URL,
+};
+use secrecy::ExposeSecret;
+
+#[cfg(feature = "sqlite")]
+use auth_service::{get_sqlite_pool, services::data_stores::SqliteAuditLogStore};
+
+const BATCH_SIZE: i64 = 1000;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    color_eyre::install().expect("Failed to install color_eyre");
+
+    let checkpoint = match parse_checkpoint(std::env::args().skip(1).collect()) {
+        Ok(checkpoint) => checkpoint,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            return ExitCode::from(2);
+        }
+    };
+
+    let audit_log_store = match DATABASE_URL.expose_secret().starts_with("sqlite:") {
+        true => open_sqlite_audit_log_store().await,
+        false => open_postgres_audit_log_store().await,
+    };
+
+    let mut verifier = AuditChainVerifier::default();
+
+    loop {
+        let filter = AuditEventFilter {
+            after_sequence: verifier.verified_records(),
+            limit: BATCH_SIZE,
+            ..Default::default()
+        };
+        let records = audit_log_store
+            .get_events(&filter)
+            .await
+            .expect("Failed to read audit events");
+
+        if records.is_empty() {
+            break;
+        }
+
+        for record in &records {
+            if let Err(e) = verifier.verify(record) {
+                eprintln!("Audit log was tampered with: {}", e);
+                return ExitCode::FAILURE;
+            }
+
+            if let Some((sequence, hash)) = &checkpoint {
+                if record.sequence == *sequence && record.hash != *hash {
+                    eprintln!(
+                        "Audit log was tampered with: record {} does not match the checkpoint",
+                        sequence
+                    );
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    if let Some((sequence, _)) = checkpoint {
+        if verifier.verified_records() < sequence {
+            eprintln!(
+                "Audit log was tampered with: it ends at record {}, before the checkpoint",
+                verifier.verified_records()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+
+    println!(
+        "Audit log is intact, checkpoint: {} {}",
+        verifier.verified_records(),
+        verifier.head_hash()
+    );
+
+    ExitCode::SUCCESS
+}
+
+fn parse_checkpoint(args: Vec<String>) -> Result<Option<(i64, String)>, &'static str> {
+    const USAGE: &str = "Usage: verify_audit_log [<sequence> <hash>]";
+
+    match args.as_slice() {
+        [] => Ok(None),
+        [sequence, hash] => {
+            let sequence = sequence.parse().map_err(|_| USAGE)?;
+            Ok(Some((sequence, hash.to_owned())))
+        }
+        _ => Err(USAGE),
+    }
+}
+
+async fn open_postgres_audit_log_store() -> Box<dyn AuditLogStore> {
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool!");
+
+    Box::new(PostgresAuditLogStore::new(pg_pool))
+}
+
+#[cfg(feature = "sqlite")]
+async fn open_sqlite_audit_log_store() -> Box<dyn AuditLogStore> {
+    let sqlite_pool = get_sqlite_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to open SQLite database!");
+
+    Box::new(SqliteAuditLogStore::new(sqlite_pool))
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn open_sqlite_audit_log_store() -> Box<dyn AuditLogStore> {
+    panic!(
+        "DATABASE_URL is a SQLite URL, but verify_audit_log was built without the sqlite feature"
+    );
+}
\ No newline at end of file
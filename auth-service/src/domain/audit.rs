@@ -0,0 +1,361 @@
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use color_eyre::eyre::{eyre, Result};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::{Device, Email};
+
+/// `previous_hash` of the first record in the log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditEventType {
+    #[serde(rename = "signup")]
+    Signup,
+    #[serde(rename = "login")]
+    Login,
+    #[serde(rename = "login_failed")]
+    LoginFailed,
+    #[serde(rename = "2fa_code_sent")]
+    TwoFACodeSent,
+    #[serde(rename = "2fa_verified")]
+    TwoFAVerified,
+    #[serde(rename = "2fa_failed")]
+    TwoFAFailed,
+    #[serde(rename = "logout")]
+    Logout,
+    #[serde(rename = "token_banned")]
+    TokenBanned,
+    #[serde(rename = "login_reported")]
+    LoginReported,
+    #[serde(rename = "password_reset")]
+    PasswordReset,
+    #[serde(rename = "trusted_device_added")]
+    TrustedDeviceAdded,
+    #[serde(rename = "trusted_device_revoked")]
+    TrustedDeviceRevoked,
+    #[serde(rename = "password_changed")]
+    PasswordChanged,
+}
+
+impl AuditEventType {
+    const ALL: [AuditEventType; 13] = [
+        Self::Signup,
+        Self::Login,
+        Self::LoginFailed,
+        Self::TwoFACodeSent,
+        Self::TwoFAVerified,
+        Self::TwoFAFailed,
+        Self::Logout,
+        Self::TokenBanned,
+        Self::LoginReported,
+        Self::PasswordReset,
+        Self::TrustedDeviceAdded,
+        Self::TrustedDeviceRevoked,
+        Self::PasswordChanged,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Signup => "signup",
+            Self::Login => "login",
+            Self::LoginFailed => "login_failed",
+            Self::TwoFACodeSent => "2fa_code_sent",
+            Self::TwoFAVerified => "2fa_verified",
+            Self::TwoFAFailed => "2fa_failed",
+            Self::Logout => "logout",
+            Self::TokenBanned => "token_banned",
+            Self::LoginReported => "login_reported",
+            Self::PasswordReset => "password_reset",
+            Self::TrustedDeviceAdded => "trusted_device_added",
+            Self::TrustedDeviceRevoked => "trusted_device_revoked",
+            Self::PasswordChanged => "password_changed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| eyre!("Unknown audit event type: {:?}", s))
+    }
+}
+
+/// Something that happened to an account, before it is added to the log.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub event_type: AuditEventType,
+    pub email: Option<String>,
+    pub ip_address: Option<String>,
+    pub details: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(event_type: AuditEventType) -> Self {
+        Self {
+            event_type,
+            email: None,
+            ip_address: None,
+            details: None,
+        }
+    }
+
+    pub fn email(mut self, email: &Email) -> Self {
+        self.email = Some(email.as_ref().expose_secret().to_owned());
+        self
+    }
+
+    pub fn device(mut self, device: &Device) -> Self {
+        self.ip_address = Some(device.ip_address.clone());
+        self
+    }
+
+    pub fn details(mut self, details: &str) -> Self {
+        self.details = Some(details.to_owned());
+        self
+    }
+}
+
+/// An event in the audit log. Each record includes the hash of the one before
+/// it, so changing, removing or reordering records breaks the chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub sequence: i64,
+    pub event_type: AuditEventType,
+    pub email: Option<String>,
+    pub ip_address: Option<String>,
+    pub details: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub previous_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Chains `event` onto the record with `previous_sequence` and `previous_hash`,
+    /// or onto `0` and [`GENESIS_HASH`] if the log is empty.
+    pub fn append(event: AuditEvent, previous_sequence: i64, previous_hash: String) -> Self {
+        let mut record = Self {
+            sequence: previous_sequence + 1,
+            event_type: event.event_type,
+            email: event.email,
+            ip_address: event.ip_address,
+            details: event.details,
+            // PostgreSQL keeps microseconds, anything finer would change the hash.
+            occurred_at: Utc::now().trunc_subsecs(6),
+            previous_hash,
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+        record
+    }
+
+    pub fn compute_hash(&self) -> String {
+        // A JSON array keeps the field boundaries unambiguous.
+        let content = serde_json::json!([
+            self.sequence,
+            self.event_type.as_str(),
+            self.email,
+            self.ip_address,
+            self.details,
+            self.occurred_at
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.previous_hash,
+        ]);
+
+        format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
+    }
+}
+
+/// Which records to return from the audit log, oldest first.
+#[derive(Debug, Clone)]
+pub struct AuditEventFilter {
+    /// Matches regardless of case, as emails do.
+    pub email: Option<String>,
+    pub event_type: Option<AuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub after_sequence: i64,
+    pub limit: i64,
+}
+
+impl Default for AuditEventFilter {
+    fn default() -> Self {
+        Self {
+            email: None,
+            event_type: None,
+            from: None,
+            to: None,
+            after_sequence: 0,
+            limit: 100,
+        }
+    }
+}
+
+impl AuditEventFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        // An unset condition matches every record.
+        let check = |condition: Option<bool>| condition.unwrap_or(true);
+
+        record.sequence > self.after_sequence
+            && check(self.email.as_ref().map(|email| {
+                record
+                    .email
+                    .as_ref()
+                    .is_some_and(|record_email| record_email.to_lowercase() == email.to_lowercase())
+            }))
+            && check(
+                self.event_type
+                    .map(|event_type| record.event_type == event_type),
+            )
+            && check(self.from.map(|from| record.occurred_at >= from))
+            && check(self.to.map(|to| record.occurred_at < to))
+    }
+}
+
+/// Checks the records of the whole log, fed in sequence order.
+///
+/// The chain cannot tell whether records were cut off at the end. Compare
+/// [`AuditChainVerifier::head_hash`] with a value kept elsewhere for that.
+#[derive(Debug)]
+pub struct AuditChainVerifier {
+    sequence: i64,
+    hash: String,
+}
+
+impl Default for AuditChainVerifier {
+    fn default() -> Self {
+        Self {
+            sequence: 0,
+            hash: GENESIS_HASH.to_owned(),
+        }
+    }
+}
+
+impl AuditChainVerifier {
+    pub fn verify(&mut self, record: &AuditRecord) -> Result<(), AuditChainError> {
+        if record.sequence != self.sequence + 1 {
+            return Err(AuditChainError::MissingRecord {
+                expected: self.sequence + 1,
+                found: record.sequence,
+            });
+        }
+        if record.previous_hash != self.hash {
+            return Err(AuditChainError::BrokenLink(record.sequence));
+        }
+        if record.compute_hash() != record.hash {
+            return Err(AuditChainError::ModifiedRecord(record.sequence));
+        }
+
+        self.sequence = record.sequence;
+        self.hash.clone_from(&record.hash);
+
+        Ok(())
+    }
+
+    pub fn verified_records(&self) -> i64 {
+        self.sequence
+    }
+
+    pub fn head_hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum AuditChainError {
+    #[error("record {expected} is missing, found record {found} instead")]
+    MissingRecord { expected: i64, found: i64 },
+    #[error("record {0} does not link to the record before it")]
+    BrokenLink(i64),
+    #[error("record {0} was modified")]
+    ModifiedRecord(i64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(length: usize) -> Vec<AuditRecord> {
+        let mut records: Vec<AuditRecord> = Vec::new();
+
+        for _ in 0..length {
+            let (sequence, hash) = records
+                .last()
+                .map(|record| (record.sequence, record.hash.clone()))
+                .unwrap_or((0, GENESIS_HASH.to_owned()));
+            let event = AuditEvent::new(AuditEventType::Login)
+                .details("test")
+                .device(&Device::new("127.0.0.1".to_owned(), String::new()));
+
+            records.push(AuditRecord::append(event, sequence, hash));
+        }
+
+        records
+    }
+
+    fn verify(records: &[AuditRecord]) -> Result<(), AuditChainError> {
+        let mut verifier = AuditChainVerifier::default();
+        records
+            .iter()
+            .try_for_each(|record| verifier.verify(record))
+    }
+
+    #[test]
+    fn test_event_type_round_trip() {
+        for event_type in AuditEventType::ALL {
+            assert_eq!(
+                AuditEventType::parse(event_type.as_str()).unwrap(),
+                event_type
+            );
+            assert_eq!(
+                serde_json::to_value(event_type).unwrap(),
+                serde_json::json!(event_type.as_str())
+            );
+        }
+
+        assert!(AuditEventType::parse("unknown").is_err());
+    }
+
+    #[test]
+    fn test_verify_intact_chain() {
+        let records = chain(3);
+        let mut verifier = AuditChainVerifier::default();
+
+        for record in &records {
+            verifier.verify(record).unwrap();
+        }
+
+        assert_eq!(verifier.verified_records(), 3);
+        assert_eq!(verifier.head_hash(), records[2].hash);
+    }
+
+    #[test]
+    fn test_verify_detects_modified_record() {
+        let mut records = chain(3);
+        records[1].ip_address = Some("10.0.0.1".to_owned());
+
+        assert_eq!(verify(&records), Err(AuditChainError::ModifiedRecord(2)));
+
+        // Recomputing the hash of the changed record breaks the next link instead
+        records[1].hash = records[1].compute_hash();
+
+        assert_eq!(verify(&records), Err(AuditChainError::BrokenLink(3)));
+    }
+
+    #[test]
+    fn test_verify_detects_removed_record() {
+        let mut records = chain(3);
+        records.remove(1);
+
+        assert_eq!(
+            verify(&records),
+            Err(AuditChainError::MissingRecord {
+                expected: 2,
+                found: 3
+            })
+        );
+    }
+}
\ No newline at end of file
@@ -1,16 +1,36 @@
+use std::hash::{Hash, Hasher};
+
 use color_eyre::eyre::{eyre, Report, Result};
 use rand::Rng;
 use secrecy::{ExposeSecret, Secret};
 use thiserror::Error;
+use uuid::Uuid;
 
-use super::{Email, Password, User};
+use super::{
+    AuditEvent, AuditEventFilter, AuditRecord, Device, Email, Login, Password, PasswordHasherType,
+    ReportToken, ReportedLogin, TrustedDevice, User, UserFilter, UserId, UserUpdate,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    /// Verifies the passwords of this store, so hashes of new users have to
+    /// come from it as well.
+    fn password_hasher(&self) -> &PasswordHasherType;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    /// Applies `update` and returns the updated user.
+    async fn update_user(&self, email: &Email, update: UserUpdate) -> Result<User, UserStoreError>;
+    /// Also removes the login history and trusted devices of the user.
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError>;
+    async fn list_users(&self, filter: &UserFilter) -> Result<Vec<User>, UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -37,9 +57,110 @@ impl PartialEq for UserStoreError {
     }
 }
 
+#[async_trait::async_trait]
+pub trait LoginHistoryStore {
+    async fn add_login(
+        &self,
+        login: Login,
+        report_token: &ReportToken,
+    ) -> Result<(), LoginHistoryStoreError>;
+    async fn has_logins(&self, email: &Email) -> Result<bool, LoginHistoryStoreError>;
+    async fn has_login_from_device(
+        &self,
+        email: &Email,
+        device: &Device,
+    ) -> Result<bool, LoginHistoryStoreError>;
+    async fn report_login(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<ReportedLogin, LoginHistoryStoreError>;
+    async fn requires_password_reset(&self, email: &Email) -> Result<bool, LoginHistoryStoreError>;
+    async fn get_password_reset_email(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<Email, LoginHistoryStoreError>;
+    async fn complete_password_reset(&self, email: &Email) -> Result<(), LoginHistoryStoreError>;
+    /// Forgets the session tokens of the logins of `email` that may still be
+    /// live, except `current_session_token` if given, and returns their hashes
+    /// so that the sessions can be banned.
+    async fn take_session_token_hashes(
+        &self,
+        email: &Email,
+        current_session_token: Option<&Secret<String>>,
+    ) -> Result<Vec<String>, LoginHistoryStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum LoginHistoryStoreError {
+    #[error("Login not found")]
+    LoginNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for LoginHistoryStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LoginNotFound, Self::LoginNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Append-only log of security relevant events.
+#[async_trait::async_trait]
+pub trait AuditLogStore {
+    async fn append(&self, event: AuditEvent) -> Result<AuditRecord, AuditLogStoreError>;
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditLogStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// Browsers that skip 2FA. Expired devices are never returned.
+#[async_trait::async_trait]
+pub trait TrustedDeviceStore {
+    async fn add_device(&self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError>;
+    async fn is_trusted(&self, email: &Email, id: &Uuid) -> Result<bool, TrustedDeviceStoreError>;
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError>;
+    async fn remove_device(&self, email: &Email, id: &Uuid) -> Result<(), TrustedDeviceStoreError>;
+    async fn remove_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TrustedDeviceStoreError {
+    #[error("Trusted device not found")]
+    DeviceNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TrustedDeviceStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::DeviceNotFound, Self::DeviceNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
 #[async_trait::async_trait]
 pub trait BannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
+    async fn add_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
+    /// Bans a token of which only the hex SHA-256 is known, as with the
+    /// sessions in the login history.
+    async fn add_token_hash(&self, token_hash: String) -> Result<(), BannedTokenStoreError>;
     async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
 }
 
@@ -51,23 +172,42 @@ pub enum BannedTokenStoreError {
 
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
+    /// Adds a pending login of `email`. Each login attempt gets a code of its
+    /// own, so logins on several devices at once all work. Beyond
+    /// [`MAX_PENDING_LOGIN_ATTEMPTS`] per user, the oldest attempt is dropped.
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
-    async fn get_code(
+    async fn remove_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    /// Checks that the login attempt is pending for `email` and has `code`.
+    /// Stores only have to be able to check codes, not to give them back.
+    async fn validate_code(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError>;
 }
 
+/// Logins of one user that can wait for their 2FA code at the same time.
+pub const MAX_PENDING_LOGIN_ATTEMPTS: usize = 5;
+
+/// How long a 2FA code can be used after it was sent.
+pub const TWO_FA_CODE_TTL_SECONDS: u64 = 600;
+
 #[derive(Debug, Error)]
 pub enum TwoFACodeStoreError {
     #[error("Login Attempt ID not found")]
     LoginAttemptIdNotFound,
+    #[error("Incorrect 2FA code")]
+    IncorrectCode,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -77,6 +217,7 @@ impl PartialEq for TwoFACodeStoreError {
         matches!(
             (self, other),
             (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                | (Self::IncorrectCode, Self::IncorrectCode)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -91,6 +232,14 @@ impl PartialEq for LoginAttemptId {
     }
 }
 
+impl Eq for LoginAttemptId {}
+
+impl Hash for LoginAttemptId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.expose_secret().hash(state);
+    }
+}
+
 impl LoginAttemptId {
     pub fn parse(id: Secret<String>) -> Result<Self> {
         let id = uuid::Uuid::parse_str(id.expose_secret())
@@ -7,17 +7,16 @@ use std::hash::Hash; // New!
 #[derive(Debug, Clone)] // Updated!
 pub struct Email(Secret<String>); // Updated!
 
-// New!
+// Addresses that only differ in case belong to the same user.
 impl PartialEq for Email {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        self.normalized().expose_secret() == other.normalized().expose_secret()
     }
 }
 
-// New!
 impl Hash for Email {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.expose_secret().hash(state);
+        self.normalized().expose_secret().hash(state);
     }
 }
 
@@ -25,10 +24,17 @@ impl Hash for Email {
 impl Eq for Email {}
 
 impl Email {
-    // Updated!
+    /// Lowercases the domain, which is case-insensitive anyway. The local part
+    /// is kept as typed, to show it the way the user wrote it.
     pub fn parse(s: Secret<String>) -> Result<Email> {
         if validate_email(s.expose_secret()) {
-            Ok(Self(s))
+            let email = s.expose_secret();
+            let (local_part, domain) = email.rsplit_once('@').unwrap_or((email, ""));
+            Ok(Self(Secret::new(format!(
+                "{}@{}",
+                local_part,
+                domain.to_lowercase()
+            ))))
         } else {
             Err(eyre!(format!(
                 "{} is not a valid email.",
@@ -38,6 +44,15 @@ impl Email {
     }
 }
 
+impl Email {
+    /// The address in lowercase, which identifies the user. Mail servers may
+    /// tell local parts apart by case, but hardly any do, and treating them as
+    /// different users would let one register the other's address.
+    pub fn normalized(&self) -> Secret<String> {
+        Secret::new(self.0.expose_secret().to_lowercase())
+    }
+}
+
 // Updated!
 impl AsRef<Secret<String>> for Email {
     fn as_ref(&self) -> &Secret<String> {
@@ -48,10 +63,9 @@ impl AsRef<Secret<String>> for Email {
 #[cfg(test)]
 mod tests {
     use super::Email;
-
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
-    use secrecy::Secret; // New!
+    use secrecy::{ExposeSecret, Secret}; // New!
 
     #[test]
     fn empty_string_is_rejected() {
@@ -69,6 +83,23 @@ mod tests {
         assert!(Email::parse(email).is_err());
     }
 
+    #[test]
+    fn domain_is_lowercased() {
+        let email = Email::parse(Secret::new("Alice@Example.COM".to_string())).unwrap();
+        assert_eq!(email.as_ref().expose_secret(), "Alice@example.com");
+    }
+
+    #[test]
+    fn emails_differing_in_case_are_equal() {
+        let first = Email::parse(Secret::new("Alice@Example.com".to_string())).unwrap();
+        let second = Email::parse(Secret::new("alice@example.com".to_string())).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.normalized().expose_secret(), "alice@example.com");
+
+        let other = Email::parse(Secret::new("bob@example.com".to_string())).unwrap();
+        assert_ne!(first, other);
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmailFixture(pub String);
 
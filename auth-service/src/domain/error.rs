@@ -1,18 +1,34 @@
 use color_eyre::eyre::Report;
 use thiserror::Error;
 
+use super::{PasswordPolicyViolation, Reauthentication};
+
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
     #[error("User already exists")]
     UserAlreadyExists,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Password does not meet the password policy")]
+    PasswordPolicyViolation(Vec<PasswordPolicyViolation>),
     #[error("Incorrect credentials")]
     IncorrectCredentials,
     #[error("Missing token")]
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid CSRF token")]
+    InvalidCsrfToken,
+    #[error("Password reset required")]
+    PasswordResetRequired,
+    #[error("IP address not allowed")]
+    IpAddressNotAllowed,
+    #[error("Trusted device not found")]
+    TrustedDeviceNotFound,
+    #[error("Reauthentication required")]
+    ReauthenticationRequired(Reauthentication),
+    #[error("Proof of work required")]
+    ProofOfWorkRequired,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
\ No newline at end of file
@@ -0,0 +1,187 @@
+use color_eyre::eyre::{eyre, Result};
+use rand::Rng;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Email;
+
+/// The client a login came from, as far as we can tell from the request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Device {
+    pub ip_address: String,
+    pub user_agent: String,
+}
+
+impl Device {
+    pub fn new(ip_address: String, user_agent: String) -> Self {
+        Self {
+            ip_address,
+            user_agent,
+        }
+    }
+
+    /// Short, human readable name of the browser for emails, e.g. "Firefox on Linux".
+    pub fn browser(&self) -> String {
+        let user_agent = self.user_agent.as_str();
+
+        // Order matters: Edge and Opera also claim to be Chrome, and Chrome
+        // also claims to be Safari.
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+        ]
+        .into_iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| name);
+
+        let os = [
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iOS"),
+            ("Windows", "Windows"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .into_iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| name);
+
+        match (browser, os) {
+            (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+            (Some(browser), None) => browser.to_owned(),
+            (None, _) if user_agent.is_empty() => "Unknown browser".to_owned(),
+            (None, _) => user_agent
+                .chars()
+                .take(MAX_UNKNOWN_USER_AGENT_LENGTH)
+                .collect(),
+        }
+    }
+}
+
+const MAX_UNKNOWN_USER_AGENT_LENGTH: usize = 100;
+
+/// How the user proved who they are, as in the `amr` claim of RFC 8176.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthMethod {
+    #[serde(rename = "pwd")]
+    Password,
+    #[serde(rename = "otp")]
+    OneTimePassword,
+}
+
+/// What the user has to do before retrying a request that needs a more recent
+/// login: log in again within `max_age` seconds using all of `methods`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reauthentication {
+    pub max_age: i64,
+    pub methods: Vec<AuthMethod>,
+}
+
+/// A successful login and the session it started.
+pub struct Login {
+    pub email: Email,
+    pub device: Device,
+    pub session_token: Secret<String>,
+}
+
+/// A login the user reported with the "this wasn't me" link. The hash of the
+/// session token is only returned the first time the login is reported.
+pub struct ReportedLogin {
+    pub email: Email,
+    pub session_token_hash: Option<String>,
+}
+
+/// Secret sent in new sign-in emails to report a login. Only its hash is stored.
+#[derive(Debug, Clone)]
+pub struct ReportToken(Secret<String>);
+
+impl ReportToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        let is_valid = token.expose_secret().len() == REPORT_TOKEN_LENGTH
+            && token.expose_secret().chars().all(|c| c.is_ascii_hexdigit());
+
+        if is_valid {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid report token"))
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+}
+
+impl Default for ReportToken {
+    fn default() -> Self {
+        let token = rand::thread_rng()
+            .gen::<[u8; 32]>()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Self(Secret::new(token))
+    }
+}
+
+impl AsRef<Secret<String>> for ReportToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const REPORT_TOKEN_LENGTH: usize = 64;
+
+/// Days after the login during which its report token can report it and then
+/// reset the password. Older sign-in emails are no use to whoever finds them.
+pub const REPORT_TOKEN_MAX_AGE_DAYS: i32 = 7;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(user_agent: &str) -> Device {
+        Device::new("127.0.0.1".to_owned(), user_agent.to_owned())
+    }
+
+    #[test]
+    fn test_browser() {
+        let test_cases = [
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+                "Firefox on Linux",
+            ),
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0",
+                "Edge on Windows",
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+                "Safari on iOS",
+            ),
+            ("curl/8.5.0", "curl/8.5.0"),
+            ("", "Unknown browser"),
+        ];
+
+        for (user_agent, expected) in test_cases {
+            assert_eq!(device(user_agent).browser(), expected);
+        }
+    }
+
+    #[test]
+    fn test_report_token() {
+        let token = ReportToken::default();
+        let parsed = ReportToken::parse(token.as_ref().clone()).unwrap();
+
+        assert_eq!(parsed.hash(), token.hash());
+        assert_ne!(token.hash(), ReportToken::default().hash());
+        assert_ne!(&token.hash(), token.as_ref().expose_secret());
+
+        assert!(ReportToken::parse(Secret::new("not-a-token".to_owned())).is_err());
+    }
+}
\ No newline at end of file
@@ -1,13 +1,25 @@
+pub mod audit;
 pub mod data_stores;
 pub mod email;
 pub mod password;
+pub mod password_hash;
+pub mod password_policy;
+pub mod password_strength;
 pub mod error;
+pub mod login;
+pub mod trusted_device;
 pub mod user;
 pub mod email_client;
 
+pub use audit::*;
 pub use data_stores::*;
 pub use email::*;
 pub use error::*;
+pub use login::*;
+pub use trusted_device::*;
 pub use user::*;
 pub use password::*;
+pub use password_hash::*;
+pub use password_policy::*;
+pub use password_strength::*;
 pub use email_client::*;
\ No newline at end of file
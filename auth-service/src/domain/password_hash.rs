@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+
+use super::Password;
+
+/// A stored password hash. It can only be checked with a [`PasswordHasher`],
+/// never used as a password itself.
+#[derive(Debug, Clone)]
+pub struct PasswordHash {
+    hash: Secret<String>,
+    // Version of the pepper mixed into the password, `None` for no pepper.
+    pepper_version: Option<i32>,
+}
+
+impl PasswordHash {
+    pub fn new(hash: Secret<String>, pepper_version: Option<i32>) -> Self {
+        Self {
+            hash,
+            pepper_version,
+        }
+    }
+
+    pub fn pepper_version(&self) -> Option<i32> {
+        self.pepper_version
+    }
+}
+
+impl PartialEq for PasswordHash {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash.expose_secret() == other.hash.expose_secret()
+            && self.pepper_version == other.pepper_version
+    }
+}
+
+impl AsRef<Secret<String>> for PasswordHash {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.hash
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasswordHasher {
+    async fn hash(&self, password: &Password) -> Result<PasswordHash>;
+
+    /// Whether `password` is the one `password_hash` was made from.
+    async fn verify(&self, password: &Password, password_hash: &PasswordHash) -> Result<bool>;
+
+    /// Takes as long as `verify`, so checking a password of a user that does
+    /// not exist is not any faster.
+    async fn verify_dummy(&self, password: &Password) -> Result<()>;
+
+    /// Whether the hash is weaker than new ones and should be replaced while
+    /// the password is known.
+    fn needs_rehash(&self, password_hash: &PasswordHash) -> bool;
+}
+
+pub type PasswordHasherType = Arc<dyn PasswordHasher + Send + Sync>;
\ No newline at end of file
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::{Report, Result};
+use secrecy::ExposeSecret;
+use thiserror::Error;
+
+use super::{password_strength_score, Email, Password};
+
+#[async_trait::async_trait]
+pub trait BreachedPasswordChecker {
+    async fn is_breached(&self, password: &Password) -> Result<bool>;
+}
+
+pub type BreachedPasswordCheckerType = Arc<dyn BreachedPasswordChecker + Send + Sync>;
+
+/// Rules a new password has to satisfy on signup.
+///
+/// Login only uses `Password::parse`, so tightening the policy never locks out
+/// existing users.
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    min_strength_score: u8,
+    breached_password_checker: Option<BreachedPasswordCheckerType>,
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize, max_length: usize, min_strength_score: u8) -> Self {
+        Self {
+            min_length,
+            max_length,
+            min_strength_score,
+            breached_password_checker: None,
+        }
+    }
+
+    pub fn with_breached_password_checker(mut self, checker: BreachedPasswordCheckerType) -> Self {
+        self.breached_password_checker = Some(checker);
+        self
+    }
+
+    #[tracing::instrument(name = "Checking password policy", skip_all)]
+    pub async fn check(
+        &self,
+        password: &Password,
+        email: &Email,
+    ) -> Result<(), PasswordPolicyError> {
+        let password_str = password.as_ref().expose_secret();
+        let length = password_str.chars().count();
+
+        let mut violations = Vec::new();
+
+        if length < self.min_length {
+            violations.push(PasswordPolicyViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            violations.push(PasswordPolicyViolation::TooLong(self.max_length));
+        }
+
+        let email_inputs = email_inputs(email);
+        let email_inputs: Vec<&str> = email_inputs.iter().map(String::as_str).collect();
+
+        if is_similar_to_email(password_str, &email_inputs) {
+            violations.push(PasswordPolicyViolation::SimilarToEmail);
+        } else if password_strength_score(password_str, &email_inputs) < self.min_strength_score {
+            violations.push(PasswordPolicyViolation::TooWeak);
+        }
+
+        if violations.is_empty() {
+            if let Some(checker) = &self.breached_password_checker {
+                if checker
+                    .is_breached(password)
+                    .await
+                    .map_err(PasswordPolicyError::UnexpectedError)?
+                {
+                    violations.push(PasswordPolicyViolation::Breached);
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(PasswordPolicyError::Violations(violations))
+        }
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(8, 128, 0)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordPolicyError {
+    #[error("Password policy violated")]
+    Violations(Vec<PasswordPolicyViolation>),
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PasswordPolicyViolation {
+    #[error("Password must be at least {0} characters long")]
+    TooShort(usize),
+    #[error("Password must be at most {0} characters long")]
+    TooLong(usize),
+    #[error("Password is too easy to guess")]
+    TooWeak,
+    #[error("Password must not be similar to the email address")]
+    SimilarToEmail,
+    #[error("Password has appeared in a data breach")]
+    Breached,
+}
+
+// The whole address, its local part and the longer pieces of the local part,
+// e.g. "jane.doe+work@example.com" also gives "jane.doe+work", "jane" and "work".
+fn email_inputs(email: &Email) -> Vec<String> {
+    let email = email.as_ref().expose_secret().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default().to_owned();
+
+    let mut inputs: Vec<String> = local_part
+        .split(['.', '-', '_', '+'])
+        .filter(|piece| piece.chars().count() >= MIN_EMAIL_INPUT_LENGTH)
+        .map(str::to_owned)
+        .collect();
+    inputs.push(local_part);
+    inputs.push(email);
+    inputs.dedup();
+    inputs
+}
+
+fn is_similar_to_email(password: &str, email_inputs: &[&str]) -> bool {
+    let password = password.to_lowercase();
+
+    email_inputs.iter().any(|input| {
+        input.chars().count() >= MIN_EMAIL_INPUT_LENGTH
+            && (password.contains(input)
+                || input.contains(password.as_str())
+                || strsim::normalized_levenshtein(&password, input) >= MAX_EMAIL_SIMILARITY)
+    })
+}
+
+const MIN_EMAIL_INPUT_LENGTH: usize = 4;
+const MAX_EMAIL_SIMILARITY: f64 = 0.7;
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    struct StubBreachedPasswordChecker(bool);
+
+    #[async_trait::async_trait]
+    impl BreachedPasswordChecker for StubBreachedPasswordChecker {
+        async fn is_breached(&self, _password: &Password) -> Result<bool> {
+            Ok(self.0)
+        }
+    }
+
+    fn email() -> Email {
+        Email::parse(Secret::new("jane.doe@example.com".to_owned())).unwrap()
+    }
+
+    fn password(s: &str) -> Password {
+        Password::parse(Secret::new(s.to_owned())).unwrap()
+    }
+
+    async fn violations(policy: &PasswordPolicy, s: &str) -> Vec<PasswordPolicyViolation> {
+        match policy.check(&password(s), &email()).await {
+            Ok(()) => vec![],
+            Err(PasswordPolicyError::Violations(violations)) => violations,
+            Err(e) => panic!("Unexpected error: {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_length_limits() {
+        let policy = PasswordPolicy::new(10, 12, 0);
+
+        assert_eq!(
+            violations(&policy, "Xk9#mQ2$v").await,
+            vec![PasswordPolicyViolation::TooShort(10)]
+        );
+        assert_eq!(
+            violations(&policy, "Xk9#mQ2$vL7pZ").await,
+            vec![PasswordPolicyViolation::TooLong(12)]
+        );
+        assert_eq!(violations(&policy, "Xk9#mQ2$vL7p").await, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_strength_score() {
+        let policy = PasswordPolicy::new(8, 128, 3);
+
+        assert_eq!(
+            violations(&policy, "password123").await,
+            vec![PasswordPolicyViolation::TooWeak]
+        );
+        assert_eq!(violations(&policy, "Xk9#mQ2$vL7p").await, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_similar_to_email() {
+        let policy = PasswordPolicy::default();
+
+        for s in [
+            "jane.doe2024",
+            "JaneDoe!",
+            "jame.doe1",
+            "jane.doe@example.com",
+        ] {
+            assert_eq!(
+                violations(&policy, s).await,
+                vec![PasswordPolicyViolation::SimilarToEmail],
+                "Failed for password: {}",
+                s
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_breached_password() {
+        let policy = PasswordPolicy::default()
+            .with_breached_password_checker(Arc::new(StubBreachedPasswordChecker(true)));
+
+        assert_eq!(
+            violations(&policy, "Xk9#mQ2$vL7p").await,
+            vec![PasswordPolicyViolation::Breached]
+        );
+
+        let policy = PasswordPolicy::default()
+            .with_breached_password_checker(Arc::new(StubBreachedPasswordChecker(false)));
+
+        assert_eq!(violations(&policy, "Xk9#mQ2$vL7p").await, vec![]);
+    }
+}
\ No newline at end of file
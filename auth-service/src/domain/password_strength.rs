@@ -0,0 +1,193 @@
+/// Estimates how hard a password is to guess and maps it to a score from 0 (too
+/// guessable) to 4 (very unguessable), using the same thresholds as zxcvbn.
+///
+/// `user_inputs` are words an attacker would try first for this user, such as
+/// parts of their email address.
+pub fn password_strength_score(password: &str, user_inputs: &[&str]) -> u8 {
+    let guesses_log10 = estimate_guesses_log10(password, user_inputs);
+
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+fn estimate_guesses_log10(password: &str, user_inputs: &[&str]) -> f64 {
+    let bruteforce = bruteforce_guesses_log10(password);
+
+    match dictionary_guesses_log10(password, user_inputs) {
+        Some(dictionary) => dictionary.min(bruteforce),
+        None => bruteforce,
+    }
+}
+
+// Common passwords are usually a dictionary word decorated with digits or symbols
+// and some leetspeak. Strip that decoration and look the core up in the dictionary.
+fn dictionary_guesses_log10(password: &str, user_inputs: &[&str]) -> Option<f64> {
+    let is_decoration = |c: char| c.is_ascii_digit() || c.is_ascii_punctuation();
+    let core = password.trim_matches(is_decoration);
+    let decoration_len = password.chars().count() - core.chars().count();
+
+    let normalized: String = core.to_lowercase().chars().map(unleet).collect();
+    if normalized.is_empty() {
+        return None;
+    }
+
+    let rank = user_inputs
+        .iter()
+        .any(|input| input.to_lowercase() == normalized)
+        .then_some(1)
+        .or_else(|| {
+            COMMON_PASSWORDS
+                .split_whitespace()
+                .position(|common| common == normalized)
+                .map(|index| index + 1)
+        })?;
+
+    let mut guesses_log10 = (rank as f64).log10() + decoration_len as f64;
+
+    if core.chars().any(|c| c.is_uppercase()) {
+        guesses_log10 += 2f64.log10();
+    }
+    if core.to_lowercase() != normalized {
+        guesses_log10 += 2f64.log10();
+    }
+
+    Some(guesses_log10)
+}
+
+// Every character costs a full search over the character classes in use, except
+// characters that repeat or continue a sequence ("aaa", "abc", "321"), which an
+// attacker's pattern matcher would guess almost for free.
+fn bruteforce_guesses_log10(password: &str) -> f64 {
+    let cardinality_log10 = (character_cardinality(password) as f64).log10();
+
+    let mut guesses_log10 = 0.0;
+    let mut previous: Option<char> = None;
+
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        guesses_log10 += if predictable {
+            PREDICTABLE_CHARACTER_GUESSES_LOG10
+        } else {
+            cardinality_log10
+        };
+        previous = Some(c);
+    }
+
+    guesses_log10
+}
+
+fn character_cardinality(password: &str) -> u32 {
+    let mut cardinality = 0;
+
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        cardinality += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        cardinality += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        cardinality += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        cardinality += 33;
+    }
+    if !password.is_ascii() {
+        cardinality += 100;
+    }
+
+    cardinality.max(1)
+}
+
+fn unleet(c: char) -> char {
+    match c {
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '3' => 'e',
+        '6' | '9' => 'g',
+        '1' | '!' | '|' => 'i',
+        '0' => 'o',
+        '5' | '$' => 's',
+        '7' | '+' => 't',
+        '2' => 'z',
+        _ => c,
+    }
+}
+
+const PREDICTABLE_CHARACTER_GUESSES_LOG10: f64 = 0.1;
+
+// Most common passwords from public breach corpora, most common first. This only
+// catches the obvious cases; the breached password list is the thorough check.
+const COMMON_PASSWORDS: &str = "\
+    password qwerty abc iloveyou admin welcome monkey login dragon football \
+    baseball letmein master hello freedom whatever qazwsx trustno sunshine princess \
+    starwars shadow superman michael batman passw secret charlie access flower hottie \
+    loveme zaq mustang jennifer jordan hunter buster soccer harley ranger thomas tigger \
+    robert daniel hockey killer george andrew summer ashley pepper jessica cheese \
+    computer internet samsung google apple orange banana chocolate cookie purple maggie \
+    ginger matrix yankees dallas austin thunder taylor london liverpool chelsea arsenal \
+    nicole hannah lovely angel family friends forever changeme default root guest test \
+    user passwort motdepasse contrasena asdf asdfgh zxcvbn qwertyuiop azerty pass love";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_passwords_are_scored_weak() {
+        for password in [
+            "password",
+            "password123",
+            "P@ssw0rd!",
+            "qwerty2024",
+            "letmein",
+        ] {
+            assert!(
+                password_strength_score(password, &[]) <= 1,
+                "Failed for password: {}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn repeats_and_sequences_are_scored_weak() {
+        for password in ["aaaaaaaaaa", "abcdefghij", "1234567890", "9876543210"] {
+            assert_eq!(
+                password_strength_score(password, &[]),
+                0,
+                "Failed for password: {}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn user_inputs_are_treated_as_dictionary_words() {
+        assert!(password_strength_score("ursulamaster99", &[]) >= 3);
+        assert!(password_strength_score("Ursula99", &["ursula"]) <= 1);
+    }
+
+    #[test]
+    fn long_random_passwords_are_scored_strong() {
+        for password in [
+            "correct horse battery staple",
+            "Xk9#mQ2$vL7p",
+            "t7Gh2kQz9wPm",
+        ] {
+            assert_eq!(
+                password_strength_score(password, &[]),
+                4,
+                "Failed for password: {}",
+                password
+            );
+        }
+    }
+}
\ No newline at end of file
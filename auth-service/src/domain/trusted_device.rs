@@ -0,0 +1,60 @@
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use uuid::Uuid;
+
+use super::{Device, Email};
+
+/// How long a browser stays trusted after the user ticked "trust this device".
+pub const TRUSTED_DEVICE_TTL_DAYS: i64 = 30;
+
+/// A browser that may log in to an account without a 2FA code until it expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustedDevice {
+    pub id: Uuid,
+    pub email: Email,
+    pub device: Device,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TrustedDevice {
+    pub fn new(email: Email, device: Device) -> Self {
+        // PostgreSQL keeps microseconds, so a stored device compares equal.
+        let created_at = Utc::now().trunc_subsecs(6);
+
+        Self {
+            id: Uuid::new_v4(),
+            email,
+            device,
+            created_at,
+            expires_at: created_at + Duration::days(TRUSTED_DEVICE_TTL_DAYS),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[test]
+    fn test_new_trusted_device_expires_after_ttl() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let device = Device::new("127.0.0.1".to_owned(), "curl/8.5.0".to_owned());
+
+        let mut trusted_device = TrustedDevice::new(email.clone(), device.clone());
+        assert!(!trusted_device.is_expired());
+        assert_eq!(
+            trusted_device.expires_at - trusted_device.created_at,
+            Duration::days(TRUSTED_DEVICE_TTL_DAYS)
+        );
+        assert_ne!(trusted_device.id, TrustedDevice::new(email, device).id);
+
+        trusted_device.expires_at = Utc::now();
+        assert!(trusted_device.is_expired());
+    }
+}
\ No newline at end of file
@@ -1,18 +1,220 @@
-use super::{Email, Password};
+use std::fmt;
+
+use chrono::{DateTime, SubsecRound, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+use super::{Email, PasswordHash};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
+    pub id: UserId,
     pub email: Email,
-    pub password: Password,
+    pub password_hash: PasswordHash,
     pub requires_2fa: bool,
+    pub email_verified: bool,
+    pub display_name: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    pub fn new(email: Email, password_hash: PasswordHash, requires_2fa: bool) -> Self {
         Self {
+            id: UserId::default(),
             email,
-            password,
+            password_hash,
             requires_2fa,
+            email_verified: false,
+            display_name: None,
+            // Postgres keeps microseconds, so stored users compare equal.
+            created_at: Utc::now().trunc_subsecs(6),
+        }
+    }
+}
+
+/// Changes to a user. Fields left at `None` stay as they are. The password has
+/// an update of its own, since it gets hashed.
+#[derive(Clone, Debug, Default)]
+pub struct UserUpdate {
+    pub requires_2fa: Option<bool>,
+    pub email_verified: Option<bool>,
+    /// `Some(None)` removes the display name.
+    pub display_name: Option<Option<String>>,
+}
+
+impl UserUpdate {
+    pub fn apply(&self, user: &mut User) {
+        if let Some(requires_2fa) = self.requires_2fa {
+            user.requires_2fa = requires_2fa;
+        }
+        if let Some(email_verified) = self.email_verified {
+            user.email_verified = email_verified;
+        }
+        if let Some(display_name) = &self.display_name {
+            user.display_name = display_name.clone();
+        }
+    }
+}
+
+/// Which users to list, ordered by when they signed up.
+#[derive(Clone, Debug)]
+pub struct UserFilter {
+    /// Part of the email or display name, in any case.
+    pub search: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub requires_2fa: Option<bool>,
+    pub email_verified: Option<bool>,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+impl Default for UserFilter {
+    fn default() -> Self {
+        Self {
+            search: None,
+            created_after: None,
+            requires_2fa: None,
+            email_verified: None,
+            offset: 0,
+            limit: 100,
+        }
+    }
+}
+
+impl UserFilter {
+    /// Whether `user` passes the conditions, leaving pagination aside.
+    pub fn matches(&self, user: &User) -> bool {
+        // An unset condition matches every user.
+        let check = |condition: Option<bool>| condition.unwrap_or(true);
+
+        check(self.search.as_ref().map(|search| {
+            let search = search.to_lowercase();
+            user.email.normalized().expose_secret().contains(&search)
+                || user
+                    .display_name
+                    .as_ref()
+                    .is_some_and(|name| name.to_lowercase().contains(&search))
+        })) && check(
+            self.created_after
+                .map(|created_after| user.created_at > created_after),
+        ) && check(
+            self.requires_2fa
+                .map(|requires_2fa| user.requires_2fa == requires_2fa),
+        ) && check(
+            self.email_verified
+                .map(|email_verified| user.email_verified == email_verified),
+        )
+    }
+}
+
+/// Identifies a user for good. Unlike the email it never changes, and it tells
+/// nothing about the user, so it can go into tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn parse(id: &str) -> Result<Self> {
+        Uuid::parse_str(id).map(Self).wrap_err("invalid user id")
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn user() -> User {
+        User::new(
+            Email::parse(Secret::new("Alice@example.com".to_owned())).unwrap(),
+            PasswordHash::new(Secret::new("password-hash".to_owned()), None),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_parse_user_id() {
+        let id = UserId::default();
+        assert_eq!(UserId::parse(&id.to_string()).unwrap(), id);
+        assert!(UserId::parse("test@example.com").is_err());
+    }
+
+    #[test]
+    fn test_user_update() {
+        let mut user = user();
+        user.display_name = Some("Alice".to_owned());
+
+        UserUpdate {
+            requires_2fa: Some(true),
+            ..Default::default()
+        }
+        .apply(&mut user);
+        assert!(user.requires_2fa);
+        assert_eq!(user.display_name.as_deref(), Some("Alice"));
+
+        UserUpdate {
+            display_name: Some(None),
+            ..Default::default()
         }
+        .apply(&mut user);
+        assert!(user.requires_2fa);
+        assert_eq!(user.display_name, None);
+    }
+
+    #[test]
+    fn test_user_filter_matches() {
+        let mut user = user();
+        user.display_name = Some("Alice Liddell".to_owned());
+
+        assert!(UserFilter::default().matches(&user));
+
+        let search = |search: &str| UserFilter {
+            search: Some(search.to_owned()),
+            ..Default::default()
+        };
+        assert!(search("ALICE@").matches(&user));
+        assert!(search("liddell").matches(&user));
+        assert!(!search("bob").matches(&user));
+
+        let created_after = |created_after| UserFilter {
+            created_after: Some(created_after),
+            ..Default::default()
+        };
+        assert!(created_after(user.created_at - chrono::Duration::seconds(1)).matches(&user));
+        assert!(!created_after(user.created_at).matches(&user));
+
+        let flags = UserFilter {
+            requires_2fa: Some(false),
+            email_verified: Some(true),
+            ..Default::default()
+        };
+        assert!(!flags.matches(&user));
+        user.email_verified = true;
+        assert!(flags.matches(&user));
     }
 }
\ No newline at end of file
@@ -1,21 +1,38 @@
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 
 use app_state::AppState;
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    handler::Handler,
+    http::{HeaderValue, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
-use domain::AuthAPIError;
+use domain::{AuthAPIError, Reauthentication};
 use redis::{Client, RedisResult};
-use routes::{login, logout, signup, verify_2fa, verify_token};
+use routes::{
+    change_password, get_audit_events, get_pow_challenge, get_trusted_devices, login, logout,
+    open_login_report, report_login, reset_password, revoke_trusted_device, signup, verify_2fa,
+    verify_token,
+};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
-use utils::tracing::{make_span_with_request_id, on_request, on_response};
+use tower_http::{services::ServeDir, trace::TraceLayer};
+use utils::{
+    admin::require_admin_token,
+    constants::{HSTS_MAX_AGE_SECONDS, UI_CONTENT_SECURITY_POLICY},
+    cors::CorsConfig,
+    csrf::{csrf_protection, require_csrf_token},
+    ip_filter::{filter_ip, IpFilter, RouteGroup},
+    proof_of_work::require_proof_of_work,
+    security_headers::{set_security_headers, SecurityHeaders},
+    step_up::{require_step_up, StepUp, STEP_UP_MAX_AGE_SECONDS},
+    tracing::{make_span_with_request_id, on_request, on_response},
+};
 
 pub mod app_state;
 pub mod domain;
@@ -24,31 +41,103 @@ pub mod services;
 pub mod utils;
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     pub address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        let allowed_origins = [
-            "http://localhost:8000".parse()?,
-            "http://[YOUR_DROPLET_IP]:8000".parse()?,
-        ];
+    pub async fn build(
+        app_state: AppState,
+        address: &str,
+        cors_config: CorsConfig,
+        ip_filter: IpFilter,
+    ) -> Result<Self, Box<dyn Error>> {
+        let api_security_headers = SecurityHeaders::new().hsts(*HSTS_MAX_AGE_SECONDS);
+        // The login page gets a policy that lets it load its own assets, but it
+        // still must not be framed by other sites.
+        let ui_security_headers = api_security_headers
+            .clone()
+            .content_security_policy(HeaderValue::from_str(&UI_CONTENT_SECURITY_POLICY)?)
+            .referrer_policy(HeaderValue::from_static("same-origin"));
+
+        let ui = Router::new()
+            .fallback_service(ServeDir::new("assets"))
+            .layer(middleware::from_fn_with_state(
+                ui_security_headers,
+                set_security_headers,
+            ));
 
-        let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
-            .allow_credentials(true)
-            .allow_origin(allowed_origins);
+        // The layer added last runs first, so the IP filter comes before the token check.
+        let admin = Router::new()
+            .route("/audit-events", get(get_audit_events))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                require_admin_token,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                ip_filter.group(RouteGroup::Admin),
+                filter_ip,
+            ));
 
         let router = Router::new()
-            .nest_service("/", ServeDir::new("assets"))
-            .route("/signup", post(signup))
-            .route("/login", post(login))
+            .nest_service("/", ui)
+            .nest("/admin", admin)
+            .route(
+                "/signup",
+                post(signup).layer(middleware::from_fn_with_state(
+                    app_state.proof_of_work.clone(),
+                    require_proof_of_work,
+                )),
+            )
+            .route(
+                "/login",
+                post(login).layer(middleware::from_fn_with_state(
+                    app_state.proof_of_work.clone(),
+                    require_proof_of_work,
+                )),
+            )
+            .route("/pow-challenge", get(get_pow_challenge))
             .route("/verify-2fa", post(verify_2fa))
             .route("/logout", post(logout))
-            .route("/verify-token", post(verify_token))
+            .route(
+                "/verify-token",
+                post(verify_token).layer(middleware::from_fn_with_state(
+                    ip_filter.group(RouteGroup::Introspection),
+                    filter_ip,
+                )),
+            )
+            .route(
+                "/report-login",
+                get(open_login_report)
+                    .post(report_login.layer(middleware::from_fn(require_csrf_token))),
+            )
+            .route("/reset-password", post(reset_password))
+            .route("/trusted-devices", get(get_trusted_devices))
+            .route("/trusted-devices/:id", delete(revoke_trusted_device))
+            .route(
+                "/change-password",
+                post(change_password).layer(middleware::from_fn_with_state(
+                    (
+                        app_state.clone(),
+                        StepUp::within_seconds(STEP_UP_MAX_AGE_SECONDS).with_second_factor(),
+                    ),
+                    require_step_up,
+                )),
+            )
             .with_state(app_state)
-            .layer(cors)
+            .layer(middleware::from_fn(csrf_protection))
+            .layer(middleware::from_fn_with_state(
+                api_security_headers,
+                set_security_headers,
+            ))
+            .layer(cors_config.layer())
+            .layer(middleware::from_fn_with_state(
+                ip_filter.group(RouteGroup::Default),
+                filter_ip,
+            ))
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
@@ -58,7 +147,10 @@ impl Application {
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Application { server, address })
     }
@@ -72,26 +164,64 @@ impl Application {
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    /// How to log in again when the session is too old or too weak for the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reauthenticate: Option<Reauthentication>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
 
+        let details = match &self {
+            AuthAPIError::PasswordPolicyViolation(violations) => {
+                violations.iter().map(ToString::to_string).collect()
+            }
+            _ => Vec::new(),
+        };
+        let reauthenticate = match &self {
+            AuthAPIError::ReauthenticationRequired(reauthentication) => {
+                Some(reauthentication.clone())
+            }
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::PasswordPolicyViolation(_) => (
+                StatusCode::BAD_REQUEST,
+                "Password does not meet requirements",
+            ),
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::InvalidCsrfToken => (StatusCode::FORBIDDEN, "Invalid CSRF token"),
+            AuthAPIError::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, "Password reset required")
+            }
+            AuthAPIError::IpAddressNotAllowed => (StatusCode::FORBIDDEN, "IP address not allowed"),
+            AuthAPIError::TrustedDeviceNotFound => {
+                (StatusCode::NOT_FOUND, "Trusted device not found")
+            }
+            AuthAPIError::ReauthenticationRequired(_) => {
+                (StatusCode::UNAUTHORIZED, "Reauthentication required")
+            }
+            AuthAPIError::ProofOfWorkRequired => {
+                (StatusCode::PRECONDITION_REQUIRED, "Proof of work required")
+            }
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            details,
+            reauthenticate,
         });
         (status, body).into_response()
     }
@@ -104,6 +234,30 @@ pub async fn get_postgres_pool(url: &Secret<String>) -> Result<PgPool, sqlx::Err
         .await
 }
 
+/// Opens the SQLite database at `url`, creating the file if needed, and brings
+/// its schema up to date.
+#[cfg(feature = "sqlite")]
+pub async fn get_sqlite_pool(url: &Secret<String>) -> Result<sqlx::SqlitePool, sqlx::Error> {
+    use std::{str::FromStr, time::Duration};
+
+    use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+
+    let options = SqliteConnectOptions::from_str(url.expose_secret())?
+        .create_if_missing(true)
+        // Lets readers go on while a request writes.
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(5));
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await?;
+
+    sqlx::migrate!("./migrations_sqlite").run(&pool).await?;
+
+    Ok(pool)
+}
+
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
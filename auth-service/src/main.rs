@@ -1,52 +1,169 @@
+use argon2::Params;
 use reqwest::Client;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{path::PathBuf, sync::Arc};
 
 use auth_service::{
-    app_state::AppState,
-    domain::Email,
+    app_state::{
+        AppState, AuditLogStoreType, BannedTokenStoreType, LoginHistoryStoreType,
+        TrustedDeviceStoreType, TwoFACodeStoreType, UserStoreType,
+    },
+    domain::{Email, PasswordHasherType, PasswordPolicy},
     get_postgres_pool, get_redis_client,
     services::{
-        data_stores::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
+        argon2_password_hasher::Argon2PasswordHasher,
+        data_stores::{
+            PostgresAuditLogStore, PostgresBannedTokenStore, PostgresLoginHistoryStore,
+            PostgresTrustedDeviceStore, PostgresTwoFACodeStore, PostgresUserStore,
+            RedisBannedTokenStore, RedisTwoFACodeStore,
+        },
+        hibp_breached_password_checker::HibpBreachedPasswordChecker,
+        password_pepper::PasswordPepper,
         postmark_email_client::PostmarkEmailClient,
+        redis_connection::RedisConnection,
+        sweeper::spawn_sweeper,
     },
     utils::{
-        constants::{prod, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME},
+        constants::{
+            prod, TokenStore, ADMIN_API_TOKEN, ARGON2_MEMORY_COST_KIB, ARGON2_PARALLELISM,
+            ARGON2_TIME_COST, BREACHED_PASSWORDS_DIR, CORS_ALLOWED_HEADERS, CORS_ALLOWED_METHODS,
+            CORS_ALLOWED_ORIGINS, DATABASE_URL, IP_FILTER_CONFIG, PASSWORD_MAX_LENGTH,
+            PASSWORD_MIN_LENGTH, PASSWORD_MIN_STRENGTH_SCORE, PASSWORD_PEPPERS,
+            POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, SIGNUP_HIDE_EXISTING_USERS, TOKEN_STORE,
+        },
+        cors::CorsConfig,
+        ip_filter::{IpFilter, IpFilterRules},
         tracing::init_tracing,
     },
     Application,
 };
 
+#[cfg(feature = "sqlite")]
+use auth_service::{
+    get_sqlite_pool,
+    services::data_stores::{
+        SqliteAuditLogStore, SqliteBannedTokenStore, SqliteLoginHistoryStore,
+        SqliteTrustedDeviceStore, SqliteTwoFACodeStore, SqliteUserStore,
+    },
+};
+
 #[tokio::main]
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
 
-    let pg_pool = configure_postgresql().await;
-    let redis_connection = Arc::new(RwLock::new(configure_redis()));
-
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-        redis_connection.clone(),
-    )));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+    let password_hasher = configure_password_hasher();
+    let stores = match DATABASE_URL.expose_secret().starts_with("sqlite:") {
+        true => configure_sqlite_stores(password_hasher).await,
+        false => configure_postgres_stores(password_hasher).await,
+    };
 
     let email_client = Arc::new(configure_postmark_email_client());
+    let password_policy = Arc::new(configure_password_policy());
+
+    let mut app_state = AppState::new(
+        stores.user_store,
+        stores.banned_token_store,
+        stores.two_fa_code_store,
+        stores.login_history_store,
+        stores.audit_log_store,
+        stores.trusted_device_store,
+        email_client,
+        password_policy,
+    )
+    .with_signup_hide_existing_users(*SIGNUP_HIDE_EXISTING_USERS);
+
+    match ADMIN_API_TOKEN.as_ref() {
+        Some(admin_api_token) => {
+            app_state = app_state.with_admin_api_token(admin_api_token.to_owned());
+        }
+        None => tracing::warn!("ADMIN_API_TOKEN is not set, the admin routes are disabled"),
+    }
+
+    let app = Application::build(
+        app_state,
+        prod::APP_ADDRESS,
+        configure_cors(),
+        configure_ip_filter(),
+    )
+    .await
+    .expect("Failed to build app");
+
+    app.run().await.expect("Failed to run app");
+}
+
+struct Stores {
+    user_store: UserStoreType,
+    banned_token_store: BannedTokenStoreType,
+    two_fa_code_store: TwoFACodeStoreType,
+    login_history_store: LoginHistoryStoreType,
+    audit_log_store: AuditLogStoreType,
+    trusted_device_store: TrustedDeviceStoreType,
+}
+
+async fn configure_postgres_stores(password_hasher: PasswordHasherType) -> Stores {
+    let pg_pool = configure_postgresql().await;
+
+    let (banned_token_store, two_fa_code_store) = match *TOKEN_STORE {
+        TokenStore::Redis => configure_redis_token_stores(),
+        TokenStore::Postgres => configure_postgres_token_stores(&pg_pool),
+    };
 
-    let app_state = AppState::new(
-        user_store,
+    Stores {
+        user_store: Arc::new(PostgresUserStore::new(pg_pool.clone(), password_hasher)),
         banned_token_store,
         two_fa_code_store,
-        email_client,
+        login_history_store: Arc::new(PostgresLoginHistoryStore::new(pg_pool.clone())),
+        audit_log_store: Arc::new(PostgresAuditLogStore::new(pg_pool.clone())),
+        trusted_device_store: Arc::new(PostgresTrustedDeviceStore::new(pg_pool)),
+    }
+}
+
+fn configure_redis_token_stores() -> (BannedTokenStoreType, TwoFACodeStoreType) {
+    let redis_connection = configure_redis();
+
+    (
+        Arc::new(RedisBannedTokenStore::new(redis_connection.clone())),
+        Arc::new(RedisTwoFACodeStore::new(redis_connection)),
+    )
+}
+
+// Postgres does not expire rows by itself, so sweepers delete them.
+fn configure_postgres_token_stores(pg_pool: &PgPool) -> (BannedTokenStoreType, TwoFACodeStoreType) {
+    let banned_token_store = Arc::new(PostgresBannedTokenStore::new(pg_pool.clone()));
+    let two_fa_code_store = Arc::new(PostgresTwoFACodeStore::new(pg_pool.clone()));
+
+    spawn_sweeper(
+        "banned tokens",
+        banned_token_store.clone(),
+        prod::SWEEP_INTERVAL,
     );
+    spawn_sweeper("2FA codes", two_fa_code_store.clone(), prod::SWEEP_INTERVAL);
+
+    (banned_token_store, two_fa_code_store)
+}
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+// Keeps everything in a single file, so neither Postgres nor Redis is needed.
+#[cfg(feature = "sqlite")]
+async fn configure_sqlite_stores(password_hasher: PasswordHasherType) -> Stores {
+    let sqlite_pool = get_sqlite_pool(&DATABASE_URL)
         .await
-        .expect("Failed to build app");
+        .expect("Failed to open SQLite database!");
 
-    app.run().await.expect("Failed to run app");
+    Stores {
+        user_store: Arc::new(SqliteUserStore::new(sqlite_pool.clone(), password_hasher)),
+        banned_token_store: Arc::new(SqliteBannedTokenStore::new(sqlite_pool.clone())),
+        two_fa_code_store: Arc::new(SqliteTwoFACodeStore::new(sqlite_pool.clone())),
+        login_history_store: Arc::new(SqliteLoginHistoryStore::new(sqlite_pool.clone())),
+        audit_log_store: Arc::new(SqliteAuditLogStore::new(sqlite_pool.clone())),
+        trusted_device_store: Arc::new(SqliteTrustedDeviceStore::new(sqlite_pool)),
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn configure_sqlite_stores(_password_hasher: PasswordHasherType) -> Stores {
+    panic!("DATABASE_URL is a SQLite URL, but auth-service was built without the sqlite feature");
 }
 
 async fn configure_postgresql() -> PgPool {
@@ -62,11 +179,101 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+// Connects in the background. Requests that need Redis fail until it is up.
+fn configure_redis() -> RedisConnection {
+    let client = get_redis_client(REDIS_HOST_NAME.to_owned()).expect("Invalid Redis host name");
+    let redis_connection = RedisConnection::new(client);
+
+    let connection = redis_connection.clone();
+    tokio::spawn(async move {
+        if let Err(e) = connection.get().await {
+            tracing::warn!("Redis is unavailable, retrying on first use: {}", e);
+        }
+    });
+
+    redis_connection
+}
+
+fn configure_cors() -> CorsConfig {
+    CorsConfig::parse(
+        CORS_ALLOWED_ORIGINS
+            .as_deref()
+            .unwrap_or(prod::cors::ALLOWED_ORIGINS),
+        CORS_ALLOWED_METHODS
+            .as_deref()
+            .unwrap_or(prod::cors::ALLOWED_METHODS),
+        CORS_ALLOWED_HEADERS
+            .as_deref()
+            .unwrap_or(prod::cors::ALLOWED_HEADERS),
+    )
+    .expect("Invalid CORS configuration")
+}
+
+// Without a configuration file, no address is blocked and X-Forwarded-For is ignored.
+fn configure_ip_filter() -> IpFilter {
+    let Some(path) = IP_FILTER_CONFIG.as_ref() else {
+        return IpFilter::default();
+    };
+
+    let path = PathBuf::from(path);
+    let ip_filter =
+        IpFilter::new(IpFilterRules::load(&path).expect("Invalid IP filter configuration"));
+
+    ip_filter
+        .reload_on_hangup(path)
+        .expect("Failed to set up IP filter reloading");
+
+    ip_filter
+}
+
+fn configure_password_hasher() -> PasswordHasherType {
+    Arc::new(Argon2PasswordHasher::new(
+        configure_password_hash_params(),
+        configure_password_pepper(),
+    ))
+}
+
+fn configure_password_hash_params() -> Params {
+    Params::new(
+        *ARGON2_MEMORY_COST_KIB,
+        *ARGON2_TIME_COST,
+        *ARGON2_PARALLELISM,
+        None,
+    )
+    .expect("Invalid Argon2 parameters")
+}
+
+fn configure_password_pepper() -> PasswordPepper {
+    match PASSWORD_PEPPERS.as_ref() {
+        Some(peppers) => PasswordPepper::parse(peppers).expect("Invalid PASSWORD_PEPPERS"),
+        None => {
+            tracing::warn!("PASSWORD_PEPPERS is not set, password hashes are not peppered");
+            PasswordPepper::default()
+        }
+    }
+}
+
+fn configure_password_policy() -> PasswordPolicy {
+    let password_policy = PasswordPolicy::new(
+        *PASSWORD_MIN_LENGTH,
+        *PASSWORD_MAX_LENGTH,
+        *PASSWORD_MIN_STRENGTH_SCORE,
+    );
+
+    match BREACHED_PASSWORDS_DIR.as_ref() {
+        Some(dir) => {
+            let dir = PathBuf::from(dir);
+            if !dir.is_dir() {
+                panic!(
+                    "BREACHED_PASSWORDS_DIR {} is not a directory",
+                    dir.display()
+                );
+            }
+            password_policy
+                .with_breached_password_checker(Arc::new(HibpBreachedPasswordChecker::new(dir)))
+        }
+        None => password_policy,
+    }
 }
 
 fn configure_postmark_email_client() -> PostmarkEmailClient {
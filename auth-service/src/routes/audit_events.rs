@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventFilter, AuditEventType, AuditRecord, AuthAPIError},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Lists audit log records, oldest first. Pass the last `sequence` of a page as
+/// `afterSequence` to get the next one.
+#[tracing::instrument(name = "Get audit events", skip_all)]
+pub async fn get_audit_events(
+    State(state): State<AppState>,
+    Query(query): Query<AuditEventsQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let filter = AuditEventFilter {
+        email: query.email,
+        event_type: query.event_type,
+        from: query.from,
+        to: query.to,
+        after_sequence: query.after_sequence.unwrap_or(0),
+        limit: query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+    };
+
+    let events = state
+        .audit_log_store
+        .get_events(&filter)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(AuditEventsResponse { events }))
+}
+
+/// Adds `event` to the audit log. The action it describes already happened, so
+/// a failure is logged instead of failing the request.
+pub(crate) async fn record_audit_event(state: &AppState, event: AuditEvent) {
+    let event_type = event.event_type;
+
+    if let Err(e) = state.audit_log_store.append(event).await {
+        tracing::error!(
+            "Failed to record {} audit event: {:?}",
+            event_type.as_str(),
+            e
+        );
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventsQuery {
+    pub email: Option<String>,
+    pub event_type: Option<AuditEventType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub after_sequence: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEventsResponse {
+    pub events: Vec<AuditRecord>,
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventType, AuthAPIError, Device, Email, Password},
+    routes::{check_password_policy, record_audit_event},
+    utils::auth::AuthenticatedUser,
+};
+
+/// Sets a new password for the logged in user. The route requires a recent
+/// login, so the current password is not asked for again. Every other session
+/// and trusted device of the user is revoked.
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    device: Device,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_password_policy(&state, &password, &user.email).await?;
+
+    state
+        .user_store
+        .update_password(&user.email, password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Whoever else knew the old password may still be logged in, or may have
+    // trusted their own browser. This session stays.
+    revoke_sessions(
+        &state,
+        &user.email,
+        Some(&user.token),
+        &device,
+        "password changed",
+    )
+    .await?;
+
+    state
+        .trusted_device_store
+        .remove_all_devices(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let event = AuditEvent::new(AuditEventType::PasswordChanged)
+        .email(&user.email)
+        .device(&device);
+    record_audit_event(&state, event).await;
+
+    let response = Json(ChangePasswordResponse {
+        message: "Password changed successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Bans the sessions of `email` that may still be live, except
+/// `current_session_token` if given.
+pub(crate) async fn revoke_sessions(
+    state: &AppState,
+    email: &Email,
+    current_session_token: Option<&Secret<String>>,
+    device: &Device,
+    reason: &str,
+) -> Result<(), AuthAPIError> {
+    let session_token_hashes = state
+        .login_history_store
+        .take_session_token_hashes(email, current_session_token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    for session_token_hash in session_token_hashes {
+        state
+            .banned_token_store
+            .add_token_hash(session_token_hash)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        let event = AuditEvent::new(AuditEventType::TokenBanned)
+            .email(email)
+            .device(device)
+            .details(reason);
+        record_audit_event(state, event).await;
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
\ No newline at end of file
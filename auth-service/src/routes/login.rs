@@ -1,17 +1,27 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 use crate::{
-    app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    app_state::{AppState, EmailClientType},
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, AuthMethod, Device, Email, Login, LoginAttemptId,
+        Password, ReportToken, TwoFACode, User,
+    },
+    routes::record_audit_event,
+    utils::{
+        auth::{generate_auth_cookie, validate_trusted_device_token},
+        constants::{AUTH_SERVICE_PUBLIC_URL, TRUSTED_DEVICE_COOKIE_NAME},
+    },
 };
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    device: Device,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -25,9 +35,15 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let user_store = &state.user_store.read().await;
+    let user_store = &state.user_store;
 
     if user_store.validate_user(&email, &password).await.is_err() {
+        let event = AuditEvent::new(AuditEventType::LoginFailed)
+            .email(&email)
+            .device(&device)
+            .details("incorrect credentials");
+        record_audit_event(&state, event).await;
+
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
@@ -36,16 +52,72 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    // Set after the user reported a login as not theirs. The password is known
+    // to someone else, so it is not good enough to log in anymore.
+    match state
+        .login_history_store
+        .requires_password_reset(&user.email)
+        .await
+    {
+        Ok(false) => {}
+        Ok(true) => {
+            let event = AuditEvent::new(AuditEventType::LoginFailed)
+                .email(&user.email)
+                .device(&device)
+                .details("password reset required");
+            record_audit_event(&state, event).await;
+
+            return (jar, Err(AuthAPIError::PasswordResetRequired));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // A step-up login has to prove the second factor again, even on a trusted device.
+    let skip_2fa = match user.requires_2fa && !request.step_up {
+        true => match is_trusted_device(&state, &jar, &user, &device).await {
+            Ok(is_trusted) => is_trusted,
+            Err(e) => return (jar, Err(e)),
+        },
+        false => false,
+    };
+
+    match user.requires_2fa && !skip_2fa {
+        true => handle_2fa(&user.email, &state, device, jar).await,
+        false => handle_no_2fa(&user, &state, device, jar).await,
     }
 }
 
+/// Whether the browser was trusted by `email` on `/verify-2fa` and may skip 2FA.
+/// Cookies that are invalid, expired or revoked are ignored.
+#[tracing::instrument(name = "Check trusted device", skip_all)]
+async fn is_trusted_device(
+    state: &AppState,
+    jar: &CookieJar,
+    user: &User,
+    device: &Device,
+) -> Result<bool, AuthAPIError> {
+    let cookie = match jar.get(&TRUSTED_DEVICE_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return Ok(false),
+    };
+
+    let id = match validate_trusted_device_token(cookie.value(), &user.id, device) {
+        Ok(id) => id,
+        Err(_) => return Ok(false),
+    };
+
+    state
+        .trusted_device_store
+        .is_trusted(&user.email, &id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
 #[tracing::instrument(name = "Handle 2FA flow", skip_all)]
 async fn handle_2fa(
     email: &Email,
     state: &AppState,
+    device: Device,
     jar: CookieJar,
 ) -> (
     CookieJar,
@@ -56,8 +128,6 @@ async fn handle_2fa(
 
     if let Err(e) = state
         .two_fa_code_store
-        .write()
-        .await
         .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
     {
@@ -72,6 +142,11 @@ async fn handle_2fa(
         return (jar, Err(AuthAPIError::UnexpectedError(e)));
     }
 
+    let event = AuditEvent::new(AuditEventType::TwoFACodeSent)
+        .email(email)
+        .device(&device);
+    record_audit_event(state, event).await;
+
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
@@ -82,17 +157,24 @@ async fn handle_2fa(
 
 #[tracing::instrument(name = "Handle non-2FA flow", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    state: &AppState,
+    device: Device,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
+    let auth_cookie = match generate_auth_cookie(&user.id, &[AuthMethod::Password]) {
         Ok(cookie) => cookie,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
+    let session_token = Secret::new(auth_cookie.value().to_owned());
+    if let Err(e) = record_login(state, &user.email, device, session_token).await {
+        return (jar, Err(e));
+    }
+
     let updated_jar = jar.add(auth_cookie);
 
     (
@@ -101,10 +183,96 @@ async fn handle_no_2fa(
     )
 }
 
+/// Records a successful login. If it comes from a device the user has not logged
+/// in from before, they get an email with a link to report it.
+#[tracing::instrument(name = "Record login", skip_all)]
+pub(crate) async fn record_login(
+    state: &AppState,
+    email: &Email,
+    device: Device,
+    session_token: Secret<String>,
+) -> Result<(), AuthAPIError> {
+    let report_token = ReportToken::default();
+    let login_history_store = &state.login_history_store;
+
+    // The first login after signup is not news to the user.
+    let is_new_device = login_history_store
+        .has_logins(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        && !login_history_store
+            .has_login_from_device(email, &device)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let login = Login {
+        email: email.clone(),
+        device: device.clone(),
+        session_token,
+    };
+
+    login_history_store
+        .add_login(login, &report_token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let event = AuditEvent::new(AuditEventType::Login)
+        .email(email)
+        .device(&device);
+    record_audit_event(state, event).await;
+
+    if is_new_device {
+        notify_new_device(
+            state.email_client.clone(),
+            email.clone(),
+            new_device_email_content(&device, &report_token),
+        );
+    }
+
+    Ok(())
+}
+
+// Sent in the background, so the response time does not depend on the email
+// provider. The login itself succeeded, so a failing one must not undo it.
+fn notify_new_device(email_client: EmailClientType, email: Email, content: String) {
+    tokio::spawn(
+        async move {
+            if let Err(e) = email_client
+                .send_email(&email, "New sign-in to your account", &content)
+                .await
+            {
+                tracing::error!("Failed to send new sign-in email: {:?}", e);
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+fn new_device_email_content(device: &Device, report_token: &ReportToken) -> String {
+    format!(
+        "We noticed a new sign-in to your account.\n\n\
+        Time: {}\n\
+        IP address: {}\n\
+        Browser: {}\n\n\
+        If this was you, you can ignore this email. If this wasn't you, use the link below to \
+        sign out this session and reset your password:\n\
+        {}/report-login?token={}",
+        Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
+        device.ip_address,
+        device.browser(),
+        *AUTH_SERVICE_PUBLIC_URL,
+        report_token.as_ref().expose_secret()
+    )
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     email: Secret<String>,
     password: Secret<String>,
+    /// Set when logging in again for a route that requires a recent login. Skips
+    /// the trusted device check, so users with 2FA always get a code.
+    #[serde(rename = "stepUp", default)]
+    step_up: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -1,43 +1,56 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{cookie, CookieJar};
+use axum_extra::extract::CookieJar;
 use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuditEvent, AuditEventType, AuthAPIError, Device},
+    routes::record_audit_event,
+    utils::{
+        auth::{create_removal_auth_cookie, get_token_user, validate_token},
+        constants::JWT_COOKIE_NAME,
+    },
 };
 
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    device: Device,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
+    let cookie = match jar.get(&JWT_COOKIE_NAME) {
         Some(cookie) => cookie,
         None => return (jar, Err(AuthAPIError::MissingToken)),
     };
 
     // Validate token
     let token = Secret::new(cookie.value().to_owned());
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
+    let claims = match validate_token(&token, state.banned_token_store.clone()).await {
         Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
 
     // Add token to banned list
-    if let Err(e) = state
-        .banned_token_store
-        .write()
-        .await
-        .add_token(token.to_owned())
-        .await
-    {
+    if let Err(e) = state.banned_token_store.add_token(token.to_owned()).await {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
+    let mut logout_event = AuditEvent::new(AuditEventType::Logout).device(&device);
+    // The token is banned either way, even if its user is gone.
+    if let Ok(user) = get_token_user(&claims, &state.user_store).await {
+        logout_event = logout_event.email(&user.email);
+    }
+    let token_banned_event = AuditEvent {
+        event_type: AuditEventType::TokenBanned,
+        ..logout_event.clone()
+    }
+    .details("logout");
+
+    record_audit_event(&state, logout_event).await;
+    record_audit_event(&state, token_banned_event).await;
+
     // Remove jwt cookie
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    let jar = jar.remove(create_removal_auth_cookie());
 
     (jar, Ok(StatusCode::OK))
 }
\ No newline at end of file
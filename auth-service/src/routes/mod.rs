@@ -1,11 +1,23 @@
+mod audit_events;
+mod change_password;
 mod login;
 mod logout;
+mod pow_challenge;
+mod report_login;
+mod reset_password;
 mod signup;
+mod trusted_devices;
 mod verify_2fa;
 mod verify_token;
 
+pub use audit_events::*;
+pub use change_password::*;
 pub use login::*;
 pub use logout::*;
+pub use pow_challenge::*;
+pub use report_login::*;
+pub use reset_password::*;
 pub use signup::*;
+pub use trusted_devices::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
\ No newline at end of file
@@ -0,0 +1,21 @@
+use axum::{extract::State, response::IntoResponse, Json};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Device},
+};
+
+/// Issues a proof of work challenge for `/login` and `/signup`, which ask for
+/// one with 428 Precondition Required.
+#[tracing::instrument(name = "Get proof of work challenge", skip_all)]
+pub async fn get_pow_challenge(
+    State(state): State<AppState>,
+    device: Device,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let challenge = state
+        .proof_of_work
+        .issue_challenge(&device.ip_address)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(challenge))
+}
\ No newline at end of file
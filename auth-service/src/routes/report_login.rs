@@ -0,0 +1,88 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, Device, LoginHistoryStoreError, ReportToken,
+    },
+    routes::record_audit_event,
+};
+
+/// Target of the "this wasn't me" link in new sign-in emails. Link scanners and
+/// prefetchers follow links too, so this only opens the confirmation page in
+/// the UI, which reports the login with a POST.
+#[tracing::instrument(name = "Open login report", skip_all)]
+pub async fn open_login_report(
+    Query(query): Query<ReportLoginQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let report_token = ReportToken::parse(query.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok(Redirect::to(&format!(
+        "/?report_token={}",
+        report_token.as_ref().expose_secret()
+    )))
+}
+
+/// Signs out the reported session and blocks logins until the user chooses a
+/// new password with the same token.
+#[tracing::instrument(name = "Report login", skip_all)]
+pub async fn report_login(
+    State(state): State<AppState>,
+    device: Device,
+    Json(request): Json<ReportLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let report_token = ReportToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let reported_login = match state.login_history_store.report_login(&report_token).await {
+        Ok(reported_login) => reported_login,
+        Err(LoginHistoryStoreError::LoginNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let event = AuditEvent::new(AuditEventType::LoginReported)
+        .email(&reported_login.email)
+        .device(&device);
+    record_audit_event(&state, event).await;
+
+    if let Some(session_token_hash) = reported_login.session_token_hash {
+        state
+            .banned_token_store
+            .add_token_hash(session_token_hash)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        let event = AuditEvent::new(AuditEventType::TokenBanned)
+            .email(&reported_login.email)
+            .device(&device)
+            .details("reported login");
+        record_audit_event(&state, event).await;
+    }
+
+    let response = Json(ReportLoginResponse {
+        message: "Login reported, choose a new password".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ReportLoginQuery {
+    pub token: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReportLoginRequest {
+    pub token: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReportLoginResponse {
+    pub message: String,
+}
\ No newline at end of file
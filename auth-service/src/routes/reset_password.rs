@@ -0,0 +1,81 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, Device, LoginHistoryStoreError, Password,
+        ReportToken,
+    },
+    routes::{check_password_policy, record_audit_event, revoke_sessions},
+};
+
+/// Sets a new password after the user reported a login as not theirs. The
+/// report token from the email proves they own the address.
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    device: Device,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let report_token = ReportToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let login_history_store = &state.login_history_store;
+
+    let email = match login_history_store
+        .get_password_reset_email(&report_token)
+        .await
+    {
+        Ok(email) => email,
+        Err(LoginHistoryStoreError::LoginNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    check_password_policy(&state, &password, &email).await?;
+
+    state
+        .user_store
+        .update_password(&email, password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    login_history_store
+        .complete_password_reset(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // Whoever knew the old password may still be logged in on other devices
+    // than the reported one, or may have trusted their own browser.
+    revoke_sessions(&state, &email, None, &device, "password reset").await?;
+
+    state
+        .trusted_device_store
+        .remove_all_devices(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let event = AuditEvent::new(AuditEventType::PasswordReset)
+        .email(&email)
+        .device(&device);
+    record_audit_event(&state, event).await;
+
+    let response = Json(ResetPasswordResponse {
+        message: "Password reset successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: Secret<String>,
+    pub password: Secret<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ResetPasswordResponse {
+    pub message: String,
+}
\ No newline at end of file
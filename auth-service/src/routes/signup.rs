@@ -1,15 +1,21 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use secrecy::Secret;
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 use crate::{
-    app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    app_state::{AppState, EmailClientType},
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, Device, Email, Password, PasswordPolicyError,
+        User, UserStoreError,
+    },
+    routes::record_audit_event,
 };
 
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
+    device: Device,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let email =
@@ -17,16 +23,36 @@ pub async fn signup(
     let password =
         Password::parse(request.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let user = User::new(email, password, request.requires_2fa);
+    check_password_policy(&state, &password, &email).await?;
 
-    let mut user_store = state.user_store.write().await;
+    let user_store = &state.user_store;
 
-    if user_store.get_user(&user.email).await.is_ok() {
+    if !state.signup_hide_existing_users && user_store.get_user(&email).await.is_ok() {
         return Err(AuthAPIError::UserAlreadyExists);
     }
 
-    if let Err(e) = user_store.add_user(user).await {
-        return Err(AuthAPIError::UnexpectedError(e.into()));
+    let password_hash = user_store
+        .password_hasher()
+        .hash(&password)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let user = User::new(email, password_hash, request.requires_2fa);
+
+    let email = user.email.clone();
+
+    match user_store.add_user(user).await {
+        Ok(()) => {
+            let event = AuditEvent::new(AuditEventType::Signup)
+                .email(&email)
+                .device(&device);
+            record_audit_event(&state, event).await;
+        }
+        Err(UserStoreError::UserAlreadyExists) if state.signup_hide_existing_users => {
+            // Answer exactly like a successful signup and let the owner know instead.
+            notify_existing_user(state.email_client.clone(), email);
+        }
+        Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
     }
 
     let response = Json(SignupResponse {
@@ -36,6 +62,42 @@ pub async fn signup(
     Ok((StatusCode::CREATED, response))
 }
 
+pub(crate) async fn check_password_policy(
+    state: &AppState,
+    password: &Password,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    match state.password_policy.check(password, email).await {
+        Ok(()) => Ok(()),
+        Err(PasswordPolicyError::Violations(violations)) => {
+            Err(AuthAPIError::PasswordPolicyViolation(violations))
+        }
+        Err(PasswordPolicyError::UnexpectedError(e)) => Err(AuthAPIError::UnexpectedError(e)),
+    }
+}
+
+// Sent in the background, so the response time does not depend on the email provider.
+fn notify_existing_user(email_client: EmailClientType, email: Email) {
+    tokio::spawn(
+        async move {
+            if let Err(e) = email_client
+                .send_email(&email, "Sign-up attempt", SIGNUP_ATTEMPT_EMAIL_CONTENT)
+                .await
+            {
+                tracing::error!(
+                    "Failed to notify existing user about sign-up attempt: {:?}",
+                    e
+                );
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+const SIGNUP_ATTEMPT_EMAIL_CONTENT: &str = "Someone tried to create an account with your email \
+address. You already have an account, so you can simply log in. If this wasn't you, you can \
+ignore this email.";
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: Secret<String>,
@@ -0,0 +1,120 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, Device, TrustedDevice, TrustedDeviceStoreError,
+        UserId,
+    },
+    routes::record_audit_event,
+    utils::{
+        auth::{
+            create_removal_trusted_device_cookie, validate_trusted_device_token, AuthenticatedUser,
+        },
+        constants::TRUSTED_DEVICE_COOKIE_NAME,
+    },
+};
+
+/// Lists the browsers that currently skip 2FA for the logged in user.
+#[tracing::instrument(name = "Get trusted devices", skip_all)]
+pub async fn get_trusted_devices(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    device: Device,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let devices = state
+        .trusted_device_store
+        .get_devices(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let current_id = current_trusted_device_id(&jar, &user.id, &device);
+
+    let devices = devices
+        .into_iter()
+        .map(|trusted_device| TrustedDeviceResponse::new(trusted_device, current_id))
+        .collect();
+
+    Ok(Json(TrustedDevicesResponse { devices }))
+}
+
+/// Revokes a trusted device, so its browser needs a 2FA code again.
+#[tracing::instrument(name = "Revoke trusted device", skip_all)]
+pub async fn revoke_trusted_device(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    device: Device,
+    jar: CookieJar,
+    Path(id): Path<Uuid>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    if let Err(e) = state
+        .trusted_device_store
+        .remove_device(&user.email, &id)
+        .await
+    {
+        let error = match e {
+            TrustedDeviceStoreError::DeviceNotFound => AuthAPIError::TrustedDeviceNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        };
+        return (jar, Err(error));
+    }
+
+    let event = AuditEvent::new(AuditEventType::TrustedDeviceRevoked)
+        .email(&user.email)
+        .device(&device)
+        .details(&id.to_string());
+    record_audit_event(&state, event).await;
+
+    // The cookie is useless now, so the browser can drop it as well.
+    let jar = match current_trusted_device_id(&jar, &user.id, &device) {
+        Some(current_id) if current_id == id => jar.remove(create_removal_trusted_device_cookie()),
+        _ => jar,
+    };
+
+    (jar, Ok(StatusCode::OK))
+}
+
+fn current_trusted_device_id(jar: &CookieJar, user_id: &UserId, device: &Device) -> Option<Uuid> {
+    jar.get(&TRUSTED_DEVICE_COOKIE_NAME)
+        .and_then(|cookie| validate_trusted_device_token(cookie.value(), user_id, device).ok())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrustedDevicesResponse {
+    pub devices: Vec<TrustedDeviceResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedDeviceResponse {
+    pub id: Uuid,
+    pub browser: String,
+    pub ip_address: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the browser that made the request.
+    pub current: bool,
+}
+
+impl TrustedDeviceResponse {
+    fn new(trusted_device: TrustedDevice, current_id: Option<Uuid>) -> Self {
+        Self {
+            id: trusted_device.id,
+            browser: trusted_device.device.browser(),
+            ip_address: trusted_device.device.ip_address,
+            created_at: trusted_device.created_at,
+            expires_at: trusted_device.expires_at,
+            current: current_id == Some(trusted_device.id),
+        }
+    }
+}
\ No newline at end of file
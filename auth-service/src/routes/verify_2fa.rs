@@ -1,17 +1,22 @@
 use axum::{extract::State, response::IntoResponse, Json};
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use secrecy::Secret;
 use serde::Deserialize;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuditEvent, AuditEventType, AuthAPIError, AuthMethod, Device, Email, LoginAttemptId,
+        TrustedDevice, TwoFACode, TwoFACodeStoreError, User,
+    },
+    routes::{record_audit_event, record_login},
+    utils::auth::{generate_auth_cookie, generate_trusted_device_cookie},
 };
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    device: Device,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -30,31 +35,98 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let two_fa_code_store = &state.two_fa_code_store;
 
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
-        Ok(code_tuple) => code_tuple,
-        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    // Removing the code uses it up. Of concurrent requests with the same code,
+    // only one gets to remove it.
+    let is_valid_code = match two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &two_fa_code)
+        .await
+    {
+        Ok(()) => match two_fa_code_store
+            .remove_code(&email, &login_attempt_id)
+            .await
+        {
+            Ok(()) => true,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => false,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        },
+        Err(_) => false,
     };
 
-    if !code_tuple.0.eq(&login_attempt_id) || !code_tuple.1.eq(&two_fa_code) {
+    if !is_valid_code {
+        let event = AuditEvent::new(AuditEventType::TwoFAFailed)
+            .email(&email)
+            .device(&device);
+        record_audit_event(&state, event).await;
+
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
-    if let Err(e) = two_fa_code_store.remove_code(&email).await {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
-    }
+    // The stored address, which may differ in case from the one in the request.
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
 
-    let cookie = match generate_auth_cookie(&email) {
+    let cookie = match generate_auth_cookie(
+        &user.id,
+        &[AuthMethod::Password, AuthMethod::OneTimePassword],
+    ) {
         Ok(cookie) => cookie,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
+    let event = AuditEvent::new(AuditEventType::TwoFAVerified)
+        .email(&user.email)
+        .device(&device);
+    record_audit_event(&state, event).await;
+
+    let jar = match request.trust_device {
+        true => match trust_device(&state, &user, &device).await {
+            Ok(trusted_device_cookie) => jar.add(trusted_device_cookie),
+            Err(e) => return (jar, Err(e)),
+        },
+        false => jar,
+    };
+
+    let session_token = Secret::new(cookie.value().to_owned());
+    if let Err(e) = record_login(&state, &user.email, device, session_token).await {
+        return (jar, Err(e));
+    }
+
     let updated_jar = jar.add(cookie);
 
     (updated_jar, Ok(()))
 }
 
+/// Lets this browser skip 2FA for `TRUSTED_DEVICE_TTL_DAYS`.
+#[tracing::instrument(name = "Trust device", skip_all)]
+async fn trust_device(
+    state: &AppState,
+    user: &User,
+    device: &Device,
+) -> Result<Cookie<'static>, AuthAPIError> {
+    let trusted_device = TrustedDevice::new(user.email.clone(), device.clone());
+    let cookie = generate_trusted_device_cookie(&user.id, &trusted_device)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let details = trusted_device.id.to_string();
+
+    state
+        .trusted_device_store
+        .add_device(trusted_device)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let event = AuditEvent::new(AuditEventType::TrustedDeviceAdded)
+        .email(&user.email)
+        .device(device)
+        .details(&details);
+    record_audit_event(state, event).await;
+
+    Ok(cookie)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Verify2FARequest {
     pub email: Secret<String>,
@@ -62,4 +134,6 @@ pub struct Verify2FARequest {
     pub login_attempt_id: Secret<String>,
     #[serde(rename = "2FACode")]
     pub two_fa_code: Secret<String>,
+    #[serde(rename = "trustDevice", default)]
+    pub trust_device: bool,
 }
\ No newline at end of file
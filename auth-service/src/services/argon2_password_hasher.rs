@@ -0,0 +1,272 @@
+use argon2::{
+    password_hash::{self, SaltString},
+    Algorithm, Argon2, Params, PasswordHash as Argon2Hash, PasswordHasher as _, PasswordVerifier,
+    Version,
+};
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use crate::{
+    domain::{Password, PasswordHash, PasswordHasher},
+    services::password_pepper::PasswordPepper,
+};
+
+/// Argon2id hashing of peppered passwords.
+pub struct Argon2PasswordHasher {
+    params: Params,
+    pepper: PasswordPepper,
+    dummy_password_hash: OnceCell<Secret<String>>,
+}
+
+impl Argon2PasswordHasher {
+    pub fn new(params: Params, pepper: PasswordPepper) -> Self {
+        Self {
+            params,
+            pepper,
+            dummy_password_hash: OnceCell::new(),
+        }
+    }
+
+    // Hash of a random password, verified against when the user does not exist.
+    // It uses the current parameters so both paths cost the same.
+    async fn dummy_password_hash(&self) -> Result<Secret<String>> {
+        self.dummy_password_hash
+            .get_or_try_init(|| {
+                compute_password_hash(Secret::new(Uuid::new_v4().to_string()), self.params.clone())
+            })
+            .await
+            .cloned()
+    }
+}
+
+impl Default for Argon2PasswordHasher {
+    fn default() -> Self {
+        Self::new(Params::default(), PasswordPepper::default())
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordHasher for Argon2PasswordHasher {
+    // Peppers the password with the current pepper version, which is kept with the hash.
+    async fn hash(&self, password: &Password) -> Result<PasswordHash> {
+        let pepper_version = self.pepper.current_version();
+        let peppered_password = self.pepper.apply(pepper_version, password.as_ref())?;
+
+        let password_hash = compute_password_hash(peppered_password, self.params.clone()).await?;
+
+        Ok(PasswordHash::new(password_hash, pepper_version))
+    }
+
+    async fn verify(&self, password: &Password, password_hash: &PasswordHash) -> Result<bool> {
+        // The password cannot be checked until the key is back in PASSWORD_PEPPERS.
+        // Until then the login fails like one with a wrong password, and takes as long.
+        if !self.pepper.has_version(password_hash.pepper_version()) {
+            tracing::error!(
+                "Password hash uses pepper version {:?}, which is not in PASSWORD_PEPPERS",
+                password_hash.pepper_version()
+            );
+            self.verify_dummy(password).await?;
+            return Ok(false);
+        }
+
+        let peppered_password = self
+            .pepper
+            .apply(password_hash.pepper_version(), password.as_ref())?;
+
+        verify_password_hash(password_hash.as_ref().clone(), peppered_password).await
+    }
+
+    async fn verify_dummy(&self, password: &Password) -> Result<()> {
+        let dummy_password_hash = self.dummy_password_hash().await?;
+        let peppered_password = self
+            .pepper
+            .apply(self.pepper.current_version(), password.as_ref())?;
+        verify_password_hash(dummy_password_hash, peppered_password).await?;
+
+        Ok(())
+    }
+
+    fn needs_rehash(&self, password_hash: &PasswordHash) -> bool {
+        needs_rehash(password_hash.as_ref(), &self.params)
+            || password_hash.pepper_version() != self.pepper.current_version()
+    }
+}
+
+#[tracing::instrument(name = "Verify password hash", skip_all)]
+async fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<bool> {
+    let current_span: tracing::Span = tracing::Span::current();
+    let result = tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            let expected_password_hash: Argon2Hash<'_> =
+                Argon2Hash::new(expected_password_hash.expose_secret())
+                    .wrap_err("invalid password hash")?;
+
+            match Argon2::default().verify_password(
+                password_candidate.expose_secret().as_bytes(),
+                &expected_password_hash,
+            ) {
+                Ok(()) => Ok(true),
+                Err(password_hash::Error::Password) => Ok(false),
+                Err(e) => Err(e).wrap_err("failed to verify password hash"),
+            }
+        })
+    })
+    .await;
+
+    result?
+}
+
+#[tracing::instrument(name = "Computing password hash", skip_all)]
+async fn compute_password_hash(password: Secret<String>, params: Params) -> Result<Secret<String>> {
+    let current_span: tracing::Span = tracing::Span::current();
+
+    let result = tokio::task::spawn_blocking(move || {
+        current_span.in_scope(|| {
+            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+            let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password(password.expose_secret().as_bytes(), &salt)?
+                .to_string();
+
+            Ok(Secret::new(password_hash))
+        })
+    })
+    .await;
+
+    result?
+}
+
+// A stored hash needs upgrading when it was made with another algorithm or version,
+// or when any of its costs is lower than the current one.
+fn needs_rehash(password_hash: &Secret<String>, params: &Params) -> bool {
+    let password_hash = match Argon2Hash::new(password_hash.expose_secret()) {
+        Ok(password_hash) => password_hash,
+        Err(_) => return false,
+    };
+
+    let stored_params = match Params::try_from(&password_hash) {
+        Ok(stored_params) => stored_params,
+        Err(_) => return true,
+    };
+
+    Algorithm::try_from(password_hash.algorithm) != Ok(Algorithm::Argon2id)
+        || password_hash.version != Some(Version::V0x13.into())
+        || stored_params.m_cost() < params.m_cost()
+        || stored_params.t_cost() < params.t_cost()
+        || stored_params.p_cost() < params.p_cost()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn password_hash(algorithm: Algorithm, params: Params) -> Secret<String> {
+        let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+        let password_hash = Argon2::new(algorithm, Version::V0x13, params)
+            .hash_password(b"password123", &salt)
+            .unwrap()
+            .to_string();
+        Secret::new(password_hash)
+    }
+
+    fn params(m_cost: u32, t_cost: u32, p_cost: u32) -> Params {
+        Params::new(m_cost, t_cost, p_cost, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_with_current_params_does_not_need_rehash() {
+        let current = params(15000, 2, 1);
+        let hash = compute_password_hash(Secret::new("password123".to_owned()), current.clone())
+            .await
+            .unwrap();
+
+        assert!(!needs_rehash(&hash, &current));
+        assert!(!needs_rehash(&hash, &params(8192, 1, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_hash_with_weaker_params_needs_rehash() {
+        let hash = password_hash(Algorithm::Argon2id, params(8192, 2, 1)).await;
+
+        assert!(needs_rehash(&hash, &params(15000, 2, 1)));
+        assert!(needs_rehash(&hash, &params(8192, 3, 1)));
+        assert!(needs_rehash(&hash, &params(8192, 2, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_hash_with_other_algorithm_needs_rehash() {
+        let hash = password_hash(Algorithm::Argon2i, params(15000, 2, 1)).await;
+
+        assert!(needs_rehash(&hash, &params(15000, 2, 1)));
+    }
+
+    fn password(password: &str) -> Password {
+        Password::parse(Secret::new(password.to_owned())).unwrap()
+    }
+
+    fn hasher(peppers: &str) -> Argon2PasswordHasher {
+        let pepper = PasswordPepper::parse(&Secret::new(peppers.to_owned())).unwrap();
+        Argon2PasswordHasher::new(params(8, 1, 1), pepper)
+    }
+
+    #[tokio::test]
+    async fn test_verify_password() {
+        let hasher = hasher("1:key");
+        let hash = hasher.hash(&password("password123")).await.unwrap();
+
+        assert_eq!(hash.pepper_version(), Some(1));
+        assert!(hasher
+            .verify(&password("password123"), &hash)
+            .await
+            .unwrap());
+        assert!(!hasher
+            .verify(&password("password124"), &hash)
+            .await
+            .unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_hash_with_old_pepper_needs_rehash() {
+        let hash = hasher("1:old-key")
+            .hash(&password("password123"))
+            .await
+            .unwrap();
+        let hasher = hasher("1:old-key,2:new-key");
+
+        assert!(hasher
+            .verify(&password("password123"), &hash)
+            .await
+            .unwrap());
+        assert!(hasher.needs_rehash(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_hash_with_unknown_pepper_does_not_verify() {
+        let hash = hasher("1:old-key")
+            .hash(&password("password123"))
+            .await
+            .unwrap();
+        let hasher = hasher("2:new-key");
+
+        assert!(!hasher
+            .verify(&password("password123"), &hash)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verify_invalid_hash() {
+        let hasher = hasher("1:key");
+        let hash = PasswordHash::new(Secret::new("password123".to_owned()), Some(1));
+
+        assert!(hasher
+            .verify(&password("password123"), &hash)
+            .await
+            .is_err());
+    }
+}
\ No newline at end of file
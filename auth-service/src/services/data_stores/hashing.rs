@@ -0,0 +1,8 @@
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+// Tokens are signed and expire on their own, so a plain hash keeps them out of
+// the database well enough.
+pub(super) fn token_hash(token: &Secret<String>) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
\ No newline at end of file
@@ -0,0 +1,251 @@
+use std::sync::RwLock;
+
+use chrono::{DateTime, Duration, Utc};
+use secrecy::Secret;
+
+use crate::{
+    domain::{
+        Device, Email, Login, LoginHistoryStore, LoginHistoryStoreError, ReportToken,
+        ReportedLogin, REPORT_TOKEN_MAX_AGE_DAYS,
+    },
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+use super::hashing::token_hash;
+
+#[derive(Default)]
+pub struct HashmapLoginHistoryStore {
+    logins: RwLock<Vec<LoginRecord>>,
+}
+
+struct LoginRecord {
+    email: Email,
+    device: Device,
+    session_token_hash: Option<String>,
+    report_token_hash: String,
+    logged_in_at: DateTime<Utc>,
+    reported: bool,
+    password_reset: bool,
+}
+
+impl LoginRecord {
+    fn has_report_token(&self, report_token_hash: &str) -> bool {
+        self.report_token_hash == report_token_hash
+            && self.logged_in_at > Utc::now() - Duration::days(REPORT_TOKEN_MAX_AGE_DAYS.into())
+    }
+
+    fn awaits_password_reset(&self) -> bool {
+        self.reported && !self.password_reset
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginHistoryStore for HashmapLoginHistoryStore {
+    async fn add_login(
+        &self,
+        login: Login,
+        report_token: &ReportToken,
+    ) -> Result<(), LoginHistoryStoreError> {
+        self.logins
+            .write()
+            .expect("login history store lock poisoned")
+            .push(LoginRecord {
+                email: login.email,
+                device: login.device,
+                session_token_hash: Some(token_hash(&login.session_token)),
+                report_token_hash: report_token.hash(),
+                logged_in_at: Utc::now(),
+                reported: false,
+                password_reset: false,
+            });
+        Ok(())
+    }
+
+    async fn has_logins(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        Ok(self
+            .logins
+            .read()
+            .expect("login history store lock poisoned")
+            .iter()
+            .any(|login| &login.email == email))
+    }
+
+    async fn has_login_from_device(
+        &self,
+        email: &Email,
+        device: &Device,
+    ) -> Result<bool, LoginHistoryStoreError> {
+        Ok(self
+            .logins
+            .read()
+            .expect("login history store lock poisoned")
+            .iter()
+            .any(|login| &login.email == email && &login.device == device))
+    }
+
+    async fn report_login(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<ReportedLogin, LoginHistoryStoreError> {
+        let report_token_hash = report_token.hash();
+
+        let mut logins = self
+            .logins
+            .write()
+            .expect("login history store lock poisoned");
+        let login = logins
+            .iter_mut()
+            .find(|login| login.has_report_token(&report_token_hash))
+            .ok_or(LoginHistoryStoreError::LoginNotFound)?;
+
+        login.reported = true;
+
+        Ok(ReportedLogin {
+            email: login.email.clone(),
+            session_token_hash: login.session_token_hash.take(),
+        })
+    }
+
+    async fn requires_password_reset(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        Ok(self
+            .logins
+            .read()
+            .expect("login history store lock poisoned")
+            .iter()
+            .any(|login| &login.email == email && login.awaits_password_reset()))
+    }
+
+    async fn get_password_reset_email(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<Email, LoginHistoryStoreError> {
+        let report_token_hash = report_token.hash();
+
+        self.logins
+            .read()
+            .expect("login history store lock poisoned")
+            .iter()
+            .find(|login| {
+                login.has_report_token(&report_token_hash) && login.awaits_password_reset()
+            })
+            .map(|login| login.email.clone())
+            .ok_or(LoginHistoryStoreError::LoginNotFound)
+    }
+
+    async fn complete_password_reset(&self, email: &Email) -> Result<(), LoginHistoryStoreError> {
+        for login in self
+            .logins
+            .write()
+            .expect("login history store lock poisoned")
+            .iter_mut()
+            .filter(|login| &login.email == email && login.reported)
+        {
+            login.password_reset = true;
+        }
+        Ok(())
+    }
+
+    async fn take_session_token_hashes(
+        &self,
+        email: &Email,
+        current_session_token: Option<&Secret<String>>,
+    ) -> Result<Vec<String>, LoginHistoryStoreError> {
+        let current_session_token_hash = current_session_token.map(token_hash);
+        let live_since = Utc::now() - Duration::seconds(TOKEN_TTL_SECONDS);
+
+        Ok(self
+            .logins
+            .write()
+            .expect("login history store lock poisoned")
+            .iter_mut()
+            .filter(|login| &login.email == email && login.logged_in_at > live_since)
+            .filter(|login| {
+                current_session_token_hash.is_none()
+                    || login.session_token_hash != current_session_token_hash
+            })
+            .filter_map(|login| login.session_token_hash.take())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    fn device(user_agent: &str) -> Device {
+        Device::new("127.0.0.1".to_owned(), user_agent.to_owned())
+    }
+
+    fn login(user_agent: &str) -> Login {
+        Login {
+            email: email(),
+            device: device(user_agent),
+            session_token: Secret::new("session_token".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_old_login() {
+        let store = HashmapLoginHistoryStore::default();
+        let report_token = ReportToken::default();
+
+        store
+            .add_login(login("firefox"), &report_token)
+            .await
+            .unwrap();
+        store.report_login(&report_token).await.unwrap();
+
+        store.logins.write().unwrap()[0].logged_in_at -=
+            Duration::days(REPORT_TOKEN_MAX_AGE_DAYS.into());
+
+        let result = store.report_login(&report_token).await;
+        assert!(matches!(result, Err(LoginHistoryStoreError::LoginNotFound)));
+        assert_eq!(
+            store.get_password_reset_email(&report_token).await,
+            Err(LoginHistoryStoreError::LoginNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_take_session_token_hashes() {
+        let store = HashmapLoginHistoryStore::default();
+        let current_session_token = Secret::new("current_session_token".to_owned());
+
+        for user_agent in ["firefox", "chrome"] {
+            store
+                .add_login(login(user_agent), &ReportToken::default())
+                .await
+                .unwrap();
+        }
+        let current_login = Login {
+            session_token: current_session_token.clone(),
+            ..login("firefox")
+        };
+        store
+            .add_login(current_login, &ReportToken::default())
+            .await
+            .unwrap();
+
+        // Sessions this old have expired on their own
+        store.logins.write().unwrap()[1].logged_in_at -= Duration::seconds(TOKEN_TTL_SECONDS);
+
+        let hashes = store
+            .take_session_token_hashes(&email(), Some(&current_session_token))
+            .await
+            .unwrap();
+        assert_eq!(
+            hashes,
+            vec![token_hash(&Secret::new("session_token".to_owned()))]
+        );
+
+        let hashes = store
+            .take_session_token_hashes(&email(), Some(&current_session_token))
+            .await
+            .unwrap();
+        assert!(hashes.is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,74 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use uuid::Uuid;
+
+use crate::domain::{Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+
+#[derive(Default)]
+pub struct HashmapTrustedDeviceStore {
+    devices: RwLock<HashMap<Uuid, TrustedDevice>>,
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for HashmapTrustedDeviceStore {
+    async fn add_device(&self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        let mut devices = self
+            .devices
+            .write()
+            .expect("trusted device store lock poisoned");
+
+        devices.retain(|_, device| !device.is_expired());
+        devices.insert(device.id, device);
+        Ok(())
+    }
+
+    async fn is_trusted(&self, email: &Email, id: &Uuid) -> Result<bool, TrustedDeviceStoreError> {
+        Ok(self
+            .devices
+            .read()
+            .expect("trusted device store lock poisoned")
+            .get(id)
+            .map(|device| device.email == *email && !device.is_expired())
+            .unwrap_or(false))
+    }
+
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let mut devices: Vec<TrustedDevice> = self
+            .devices
+            .read()
+            .expect("trusted device store lock poisoned")
+            .values()
+            .filter(|device| device.email == *email && !device.is_expired())
+            .cloned()
+            .collect();
+        devices.sort_by_key(|device| device.created_at);
+
+        Ok(devices)
+    }
+
+    async fn remove_device(&self, email: &Email, id: &Uuid) -> Result<(), TrustedDeviceStoreError> {
+        let mut devices = self
+            .devices
+            .write()
+            .expect("trusted device store lock poisoned");
+
+        match devices.get(id) {
+            Some(device) if device.email == *email => {
+                devices.remove(id);
+                Ok(())
+            }
+            _ => Err(TrustedDeviceStoreError::DeviceNotFound),
+        }
+    }
+
+    async fn remove_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        self.devices
+            .write()
+            .expect("trusted device store lock poisoned")
+            .retain(|_, device| device.email != *email);
+        Ok(())
+    }
+}
\ No newline at end of file
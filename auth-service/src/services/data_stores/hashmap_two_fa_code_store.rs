@@ -1,104 +1,236 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::RwLock, time::Duration};
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+use color_eyre::eyre::Result;
+use tokio::time::Instant;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+            MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_TTL_SECONDS,
+        },
+        email::Email,
+    },
+    services::sweeper::ExpiringStore,
 };
 
-#[derive(Default)]
+/// Keeps each code for `ttl`, like the Redis store does. Expired codes are
+/// ignored until [`crate::services::sweeper::spawn_sweeper`] drops them, so
+/// adding a code does not have to go through all of them.
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    state: RwLock<PendingLogins>,
+    ttl: Duration,
+}
+
+impl HashmapTwoFACodeStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: RwLock::default(),
+            ttl,
+        }
+    }
+}
+
+impl Default for HashmapTwoFACodeStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(TWO_FA_CODE_TTL_SECONDS))
+    }
+}
+
+#[derive(Default)]
+struct PendingLogins {
+    codes: HashMap<LoginAttemptId, PendingLogin>,
+    // Orders the attempts of a user, so the oldest can be dropped.
+    next_sequence: u64,
+}
+
+struct PendingLogin {
+    email: Email,
+    code: TwoFACode,
+    sequence: u64,
+    expires_at: Instant,
+}
+
+impl PendingLogin {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at <= now
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let mut state = self.state.write().expect("2FA code store lock poisoned");
+        let now = Instant::now();
+
+        // Expired attempts do not count towards the limit.
+        let mut attempts: Vec<(u64, LoginAttemptId)> = state
+            .codes
+            .iter()
+            .filter(|(_, pending)| pending.email == email && !pending.is_expired(now))
+            .map(|(id, pending)| (pending.sequence, id.clone()))
+            .collect();
+        attempts.sort_by_key(|(sequence, _)| *sequence);
+
+        let excess = (attempts.len() + 1).saturating_sub(MAX_PENDING_LOGIN_ATTEMPTS);
+        for (_, id) in attempts.into_iter().take(excess) {
+            state.codes.remove(&id);
+        }
+
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.codes.insert(
+            login_attempt_id,
+            PendingLogin {
+                email,
+                code,
+                sequence,
+                expires_at: now + self.ttl,
+            },
+        );
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
-            Some(_) => Ok(()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+    async fn remove_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut state = self.state.write().expect("2FA code store lock poisoned");
+
+        match state.codes.get(login_attempt_id) {
+            Some(pending) if pending.email == *email => {
+                // An expired code goes as well, but does not count as used.
+                let is_expired = pending.is_expired(Instant::now());
+                state.codes.remove(login_attempt_id);
+                match is_expired {
+                    true => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+                    false => Ok(()),
+                }
+            }
+            _ => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 
-    async fn get_code(
+    async fn validate_code(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
-            Some(value) => Ok(value.clone()),
-            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let state = self.state.read().expect("2FA code store lock poisoned");
+
+        match state.codes.get(login_attempt_id) {
+            Some(pending) if pending.email == *email && !pending.is_expired(Instant::now()) => {
+                match pending.code == *code {
+                    true => Ok(()),
+                    false => Err(TwoFACodeStoreError::IncorrectCode),
+                }
+            }
+            _ => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 }
 
+#[async_trait::async_trait]
+impl ExpiringStore for HashmapTwoFACodeStore {
+    async fn delete_expired(&self) -> Result<u64> {
+        let mut state = self.state.write().expect("2FA code store lock poisoned");
+        let now = Instant::now();
+
+        let count = state.codes.len();
+        state.codes.retain(|_, pending| !pending.is_expired(now));
+        Ok((count - state.codes.len()) as u64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use secrecy::Secret;
 
     use super::*;
+
+    fn email() -> Email {
+        Email::parse(Secret::new("test@example.com".to_owned())).unwrap()
+    }
+
+    fn code(code: &str) -> TwoFACode {
+        TwoFACode::parse(Secret::new(code.to_owned())).unwrap()
+    }
+
     #[tokio::test]
     async fn test_add_code() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
 
         let result = store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(email(), login_attempt_id.clone(), code("123456"))
             .await;
 
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert!(store
+            .state
+            .read()
+            .unwrap()
+            .codes
+            .contains_key(&login_attempt_id));
     }
 
     #[tokio::test]
     async fn test_remove_code() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
 
         store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+            .add_code(email(), login_attempt_id.clone(), code("123456"))
+            .await
+            .unwrap();
 
-        let result = store.remove_code(&email).await;
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        let result = store.remove_code(&other_email, &login_attempt_id).await;
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
 
+        let result = store.remove_code(&email(), &login_attempt_id).await;
         assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), None);
+        assert!(store.state.read().unwrap().codes.is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_code() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+    async fn test_validate_code() {
+        let store = HashmapTwoFACodeStore::default();
         let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
-        store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
 
-        let result = store.get_code(&email).await;
+        store
+            .add_code(email(), login_attempt_id.clone(), code("123456"))
+            .await
+            .unwrap();
 
+        let result = store
+            .validate_code(&email(), &login_attempt_id, &code("123456"))
+            .await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), (login_attempt_id, code));
+
+        let result = store
+            .validate_code(&email(), &login_attempt_id, &code("654321"))
+            .await;
+        assert_eq!(result.unwrap_err(), TwoFACodeStoreError::IncorrectCode);
     }
 
     #[tokio::test]
-    async fn test_get_code_not_found() {
+    async fn test_validate_code_not_found() {
         let store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
 
-        let result = store.get_code(&email).await;
+        let result = store
+            .validate_code(&email(), &LoginAttemptId::default(), &code("123456"))
+            .await;
 
         assert!(result.is_err());
         assert_eq!(
@@ -106,4 +238,126 @@ mod tests {
             TwoFACodeStoreError::LoginAttemptIdNotFound
         );
     }
+
+    #[tokio::test]
+    async fn test_concurrent_attempts() {
+        let store = HashmapTwoFACodeStore::default();
+        let attempts: Vec<LoginAttemptId> = (0..=MAX_PENDING_LOGIN_ATTEMPTS)
+            .map(|_| LoginAttemptId::default())
+            .collect();
+
+        for login_attempt_id in &attempts {
+            store
+                .add_code(email(), login_attempt_id.clone(), code("123456"))
+                .await
+                .unwrap();
+        }
+
+        // The oldest attempt made room for the newest, the others still work
+        let result = store
+            .validate_code(&email(), &attempts[0], &code("123456"))
+            .await;
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+
+        for login_attempt_id in &attempts[1..] {
+            let result = store
+                .validate_code(&email(), login_attempt_id, &code("123456"))
+                .await;
+            assert!(result.is_ok());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_code_expires() {
+        let store = HashmapTwoFACodeStore::new(Duration::from_secs(60));
+        let login_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(email(), login_attempt_id.clone(), code("123456"))
+            .await
+            .unwrap();
+
+        tokio::time::advance(Duration::from_secs(59)).await;
+        let result = store
+            .validate_code(&email(), &login_attempt_id, &code("123456"))
+            .await;
+        assert!(result.is_ok());
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let result = store
+            .validate_code(&email(), &login_attempt_id, &code("123456"))
+            .await;
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+        let result = store.remove_code(&email(), &login_attempt_id).await;
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_attempts_do_not_count() {
+        let store = HashmapTwoFACodeStore::new(Duration::from_secs(60));
+        for _ in 0..MAX_PENDING_LOGIN_ATTEMPTS {
+            store
+                .add_code(email(), LoginAttemptId::default(), code("123456"))
+                .await
+                .unwrap();
+        }
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let login_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(email(), login_attempt_id.clone(), code("123456"))
+            .await
+            .unwrap();
+
+        // The older attempts expire, making room without dropping the newest
+        tokio::time::advance(Duration::from_secs(30)).await;
+        for _ in 1..MAX_PENDING_LOGIN_ATTEMPTS {
+            store
+                .add_code(email(), LoginAttemptId::default(), code("123456"))
+                .await
+                .unwrap();
+        }
+
+        let result = store
+            .validate_code(&email(), &login_attempt_id, &code("123456"))
+            .await;
+        assert!(result.is_ok());
+        let now = Instant::now();
+        assert_eq!(
+            store
+                .state
+                .read()
+                .unwrap()
+                .codes
+                .values()
+                .filter(|pending| !pending.is_expired(now))
+                .count(),
+            MAX_PENDING_LOGIN_ATTEMPTS
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delete_expired() {
+        let store = HashmapTwoFACodeStore::new(Duration::from_secs(60));
+        store
+            .add_code(email(), LoginAttemptId::default(), code("123456"))
+            .await
+            .unwrap();
+        tokio::time::advance(Duration::from_secs(30)).await;
+        store
+            .add_code(email(), LoginAttemptId::default(), code("123456"))
+            .await
+            .unwrap();
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+        assert_eq!(store.state.read().unwrap().codes.len(), 1);
+    }
 }
\ No newline at end of file
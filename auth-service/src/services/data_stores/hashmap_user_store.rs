@@ -1,61 +1,203 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::{
+    domain::{
+        Email, Password, PasswordHasherType, User, UserFilter, UserId, UserStore, UserStoreError,
+        UserUpdate,
+    },
+    services::argon2_password_hasher::Argon2PasswordHasher,
+};
 
-#[derive(Default)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: RwLock<HashMap<Email, User>>,
+    password_hasher: PasswordHasherType,
+}
+
+impl HashmapUserStore {
+    pub fn new(password_hasher: PasswordHasherType) -> Self {
+        Self {
+            users: RwLock::default(),
+            password_hasher,
+        }
+    }
+}
+
+impl Default for HashmapUserStore {
+    fn default() -> Self {
+        Self::new(Arc::new(Argon2PasswordHasher::default()))
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+    fn password_hasher(&self) -> &PasswordHasherType {
+        &self.password_hasher
+    }
+
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().expect("user store lock poisoned");
+
+        if users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
-        self.users.insert(user.email.clone(), user);
+        users.insert(user.email.clone(), user);
         Ok(())
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        match self.users.get(email) {
+        match self
+            .users
+            .read()
+            .expect("user store lock poisoned")
+            .get(email)
+        {
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
     }
 
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users
+            .read()
+            .expect("user store lock poisoned")
+            .values()
+            .find(|user| user.id == *id)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
     async fn validate_user(
         &self,
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        match self.users.get(email) {
+        let password_hash = self
+            .users
+            .read()
+            .expect("user store lock poisoned")
+            .get(email)
+            .map(|user| user.password_hash.clone());
+
+        let Some(password_hash) = password_hash else {
+            self.password_hasher
+                .verify_dummy(password)
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            return Err(UserStoreError::UserNotFound);
+        };
+
+        let is_valid = self
+            .password_hasher
+            .verify(password, &password_hash)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        if !is_valid {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        if self.password_hasher.needs_rehash(&password_hash) {
+            if let Err(e) = self.update_password(email, password.clone()).await {
+                tracing::warn!("Failed to rehash password: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = self
+            .password_hasher
+            .hash(&password)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        match self
+            .users
+            .write()
+            .expect("user store lock poisoned")
+            .get_mut(email)
+        {
+            Some(user) => {
+                user.password_hash = password_hash;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn update_user(&self, email: &Email, update: UserUpdate) -> Result<User, UserStoreError> {
+        match self
+            .users
+            .write()
+            .expect("user store lock poisoned")
+            .get_mut(email)
+        {
             Some(user) => {
-                if user.password.eq(password) {
-                    Ok(())
-                } else {
-                    Err(UserStoreError::InvalidCredentials)
-                }
+                update.apply(user);
+                Ok(user.clone())
             }
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        match self
+            .users
+            .write()
+            .expect("user store lock poisoned")
+            .remove(email)
+        {
+            Some(_) => Ok(()),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn list_users(&self, filter: &UserFilter) -> Result<Vec<User>, UserStoreError> {
+        let mut users: Vec<User> = self
+            .users
+            .read()
+            .expect("user store lock poisoned")
+            .values()
+            .filter(|user| filter.matches(user))
+            .cloned()
+            .collect();
+        users.sort_by_key(|user| (user.created_at, user.id));
+
+        Ok(users
+            .into_iter()
+            .skip(filter.offset.max(0) as usize)
+            .take(filter.limit.max(0) as usize)
+            .collect())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use secrecy::Secret;
+    use argon2::Params;
+    use secrecy::{ExposeSecret, Secret};
+
+    use crate::{domain::PasswordHasher, services::password_pepper::PasswordPepper};
 
     use super::*;
 
+    async fn user(user_store: &HashmapUserStore, email: &Email, password: &Password) -> User {
+        let password_hash = user_store.password_hasher.hash(password).await.unwrap();
+        User::new(email.clone(), password_hash, false)
+    }
+
     #[tokio::test]
     async fn test_add_user() {
-        let mut user_store = HashmapUserStore::default();
-        let user = User {
-            email: Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
-            password: Password::parse(Secret::new("password".to_string())).unwrap(),
-            requires_2fa: false,
-        };
+        let user_store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let user = user(&user_store, &email, &password).await;
 
         // Test adding a new user
         let result = user_store.add_user(user.clone()).await;
@@ -66,19 +208,41 @@ mod tests {
         assert_eq!(result, Err(UserStoreError::UserAlreadyExists));
     }
 
+    #[tokio::test]
+    async fn test_add_user_with_email_in_other_case() {
+        let user_store = HashmapUserStore::default();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let email = Email::parse(Secret::new("Alice@Example.com".to_owned())).unwrap();
+        let other_case = Email::parse(Secret::new("alice@example.com".to_owned())).unwrap();
+
+        user_store
+            .add_user(user(&user_store, &email, &password).await)
+            .await
+            .unwrap();
+
+        let result = user_store
+            .add_user(user(&user_store, &other_case, &password).await)
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserAlreadyExists));
+
+        // Found in any case, shown as it was signed up with
+        let user = user_store.get_user(&other_case).await.unwrap();
+        assert_eq!(user.email.as_ref().expose_secret(), "Alice@example.com");
+    }
+
     #[tokio::test]
     async fn test_get_user() {
-        let mut user_store = HashmapUserStore::default();
+        let user_store = HashmapUserStore::default();
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-
-        let user = User {
-            email: email.clone(),
-            password: Password::parse(Secret::new("password".to_string())).unwrap(),
-            requires_2fa: false,
-        };
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let user = user(&user_store, &email, &password).await;
 
         // Test getting a user that exists
-        user_store.users.insert(email.clone(), user.clone());
+        user_store
+            .users
+            .write()
+            .unwrap()
+            .insert(email.clone(), user.clone());
         let result = user_store.get_user(&email).await;
         assert_eq!(result, Ok(user));
 
@@ -90,20 +254,36 @@ mod tests {
         assert_eq!(result, Err(UserStoreError::UserNotFound));
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let user_store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let user = user(&user_store, &email, &password).await;
+
+        user_store.add_user(user.clone()).await.unwrap();
+
+        let result = user_store.get_user_by_id(&user.id).await;
+        assert_eq!(result, Ok(user));
+
+        let result = user_store.get_user_by_id(&UserId::default()).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
     #[tokio::test]
     async fn test_validate_user() {
-        let mut user_store = HashmapUserStore::default();
+        let user_store = HashmapUserStore::default();
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
         let password = Password::parse(Secret::new("password".to_string())).unwrap();
 
-        let user = User {
-            email: email.clone(),
-            password: password.clone(),
-            requires_2fa: false,
-        };
+        let user = user(&user_store, &email, &password).await;
 
         // Test validating a user that exists with correct password
-        user_store.users.insert(email.clone(), user.clone());
+        user_store
+            .users
+            .write()
+            .unwrap()
+            .insert(email.clone(), user.clone());
         let result = user_store.validate_user(&email, &password).await;
         assert_eq!(result, Ok(()));
 
@@ -122,4 +302,65 @@ mod tests {
 
         assert_eq!(result, Err(UserStoreError::UserNotFound));
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let user_store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+        let new_password = Password::parse(Secret::new("newpassword".to_string())).unwrap();
+
+        user_store
+            .add_user(user(&user_store, &email, &password).await)
+            .await
+            .unwrap();
+
+        // Test updating the password of a user that exists
+        let result = user_store
+            .update_password(&email, new_password.clone())
+            .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            user_store.validate_user(&email, &password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            user_store.validate_user(&email, &new_password).await,
+            Ok(())
+        );
+
+        // Test updating the password of a user that doesn't exist
+        let result = user_store
+            .update_password(
+                &Email::parse(Secret::new("nonexistent@example.com".to_string())).unwrap(),
+                new_password,
+            )
+            .await;
+
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_validate_user_rehashes_password() {
+        let pepper =
+            |peppers: &str| PasswordPepper::parse(&Secret::new(peppers.to_owned())).unwrap();
+        let old_hasher = Argon2PasswordHasher::new(Params::default(), pepper("1:old-key"));
+        let user_store = HashmapUserStore::new(Arc::new(Argon2PasswordHasher::new(
+            Params::default(),
+            pepper("1:old-key,2:new-key"),
+        )));
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password".to_string())).unwrap();
+
+        let password_hash = old_hasher.hash(&password).await.unwrap();
+        user_store
+            .add_user(User::new(email.clone(), password_hash, false))
+            .await
+            .unwrap();
+
+        assert_eq!(user_store.validate_user(&email, &password).await, Ok(()));
+        let user = user_store.get_user(&email).await.unwrap();
+        assert_eq!(user.password_hash.pepper_version(), Some(2));
+        assert_eq!(user_store.validate_user(&email, &password).await, Ok(()));
+    }
 }
\ No newline at end of file
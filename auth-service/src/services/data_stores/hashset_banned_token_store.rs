@@ -1,48 +1,162 @@
-use std::collections::HashSet;
+use std::{collections::HashMap, sync::RwLock, time::Duration};
 
-use secrecy::{ExposeSecret, Secret};
+use color_eyre::eyre::Result;
+use secrecy::Secret;
+use tokio::time::Instant;
 
-use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use crate::{
+    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    services::sweeper::ExpiringStore,
+    utils::auth::TOKEN_TTL_SECONDS,
+};
 
-#[derive(Default)]
+use super::hashing::token_hash;
+
+/// Keeps each banned token for `ttl`, like the Redis store does. Expired
+/// tokens are ignored until [`crate::services::sweeper::spawn_sweeper`] drops
+/// them, so banning a token does not have to go through all of them.
 pub struct HashsetBannedTokenStore {
-    tokens: HashSet<String>,
+    tokens: RwLock<HashMap<String, Instant>>,
+    ttl: Duration,
+}
+
+impl HashsetBannedTokenStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            tokens: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+}
+
+impl Default for HashsetBannedTokenStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(TOKEN_TTL_SECONDS as u64))
+    }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token.expose_secret().to_owned());
+    async fn add_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.add_token_hash(token_hash(&token)).await
+    }
+
+    async fn add_token_hash(&self, token_hash: String) -> Result<(), BannedTokenStoreError> {
+        self.tokens
+            .write()
+            .expect("banned token store lock poisoned")
+            .insert(token_hash, Instant::now() + self.ttl);
         Ok(())
     }
 
     async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.contains(token.expose_secret()))
+        Ok(self
+            .tokens
+            .read()
+            .expect("banned token store lock poisoned")
+            .get(&token_hash(token))
+            .is_some_and(|expires_at| *expires_at > Instant::now()))
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for HashsetBannedTokenStore {
+    async fn delete_expired(&self) -> Result<u64> {
+        let mut tokens = self
+            .tokens
+            .write()
+            .expect("banned token store lock poisoned");
+        let now = Instant::now();
+
+        let count = tokens.len();
+        tokens.retain(|_, expires_at| *expires_at > now);
+        Ok((count - tokens.len()) as u64)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use crate::services::sweeper::spawn_sweeper;
+
     use super::*;
     #[tokio::test]
     async fn test_add_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = Secret::new("test_token".to_owned());
 
         let result = store.add_token(token.clone()).await;
 
         assert!(result.is_ok());
-        assert!(store.tokens.contains(token.expose_secret()));
+        assert!(store
+            .tokens
+            .read()
+            .unwrap()
+            .contains_key(&token_hash(&token)));
     }
 
     #[tokio::test]
     async fn test_contains_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = Secret::new("test_token".to_owned());
-        store.tokens.insert(token.expose_secret().to_owned());
+        store
+            .tokens
+            .write()
+            .unwrap()
+            .insert(token_hash(&token), Instant::now() + Duration::from_secs(60));
 
         let result = store.contains_token(&token).await;
 
         assert!(result.unwrap());
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_expires() {
+        let store = HashsetBannedTokenStore::new(Duration::from_secs(60));
+        let token = Secret::new("test_token".to_owned());
+        store.add_token(token.clone()).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert!(store.contains_token(&token).await.unwrap());
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(!store.contains_token(&token).await.unwrap());
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+        assert!(store.tokens.read().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_add_token_leaves_expired_tokens_to_the_sweeper() {
+        let store = HashsetBannedTokenStore::new(Duration::from_secs(60));
+        let token = Secret::new("test_token".to_owned());
+        store.add_token(token.clone()).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        let other_token = Secret::new("other_token".to_owned());
+        store.add_token(other_token.clone()).await.unwrap();
+
+        assert!(!store.contains_token(&token).await.unwrap());
+        assert!(store.contains_token(&other_token).await.unwrap());
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sweeper_deletes_expired_tokens() {
+        let store = Arc::new(HashsetBannedTokenStore::new(Duration::from_secs(60)));
+        let token = Secret::new("test_token".to_owned());
+        store.add_token(token.clone()).await.unwrap();
+        let sweeper = spawn_sweeper("banned tokens", store.clone(), Duration::from_secs(30));
+
+        tokio::time::sleep(Duration::from_secs(59)).await;
+        assert!(store
+            .tokens
+            .read()
+            .unwrap()
+            .contains_key(&token_hash(&token)));
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(store.tokens.read().unwrap().is_empty());
+        sweeper.abort();
+    }
 }
\ No newline at end of file
@@ -1,13 +1,55 @@
+mod hashing;
+mod hashmap_login_history_store;
+mod hashmap_trusted_device_store;
 mod hashmap_two_fa_code_store;
 mod hashmap_user_store;
 mod hashset_banned_token_store;
+mod postgres_audit_log_store;
+mod postgres_banned_token_store;
+mod postgres_login_history_store;
+mod postgres_trusted_device_store;
+mod postgres_two_fa_code_store;
 mod postgres_user_store;
 mod redis_banned_token_store;
 mod redis_two_fa_code_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_audit_log_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_banned_token_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_login_history_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_trusted_device_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_two_fa_code_store;
+#[cfg(feature = "sqlite")]
+mod sqlite_user_store;
+mod two_fa_hashes;
+mod vec_audit_log_store;
 
+pub use hashmap_login_history_store::*;
+pub use hashmap_trusted_device_store::*;
 pub use hashmap_two_fa_code_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
+pub use postgres_audit_log_store::*;
+pub use postgres_banned_token_store::*;
+pub use postgres_login_history_store::*;
+pub use postgres_trusted_device_store::*;
+pub use postgres_two_fa_code_store::*;
 pub use postgres_user_store::*;
 pub use redis_banned_token_store::*;
-pub use redis_two_fa_code_store::*;
\ No newline at end of file
+pub use redis_two_fa_code_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_audit_log_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_banned_token_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_login_history_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_trusted_device_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_two_fa_code_store::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_user_store::*;
+pub use vec_audit_log_store::*;
\ No newline at end of file
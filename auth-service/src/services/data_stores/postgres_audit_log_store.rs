@@ -0,0 +1,124 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    AuditEvent, AuditEventFilter, AuditEventType, AuditLogStore, AuditLogStoreError, AuditRecord,
+    GENESIS_HASH,
+};
+
+pub struct PostgresAuditLogStore {
+    pool: PgPool,
+}
+
+impl PostgresAuditLogStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLogStore for PostgresAuditLogStore {
+    #[tracing::instrument(name = "Appending audit event to PostgreSQL", skip_all)]
+    async fn append(&self, event: AuditEvent) -> Result<AuditRecord, AuditLogStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        // Other instances append too. The lock makes them wait until this record
+        // is committed, so each record links to the one that really is before it.
+        sqlx::query!("LOCK TABLE audit_events IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        let last = sqlx::query!(
+            r#"
+            SELECT sequence, hash FROM audit_events
+            ORDER BY sequence DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        let (sequence, hash) = match last {
+            Some(last) => (last.sequence, last.hash),
+            None => (0, GENESIS_HASH.to_owned()),
+        };
+
+        let record = AuditRecord::append(event, sequence, hash);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events
+                (sequence, event_type, email, ip_address, details, occurred_at, previous_hash, hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            record.sequence,
+            record.event_type.as_str(),
+            record.email,
+            record.ip_address,
+            record.details,
+            record.occurred_at,
+            record.previous_hash,
+            record.hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Retrieving audit events from PostgreSQL", skip_all)]
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditLogStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT sequence, event_type, email, ip_address, details, occurred_at, previous_hash, hash
+            FROM audit_events
+            WHERE sequence > $1
+                AND ($2::TEXT IS NULL OR lower(email) = lower($2))
+                AND ($3::TEXT IS NULL OR event_type = $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR occurred_at >= $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR occurred_at < $5)
+            ORDER BY sequence
+            LIMIT $6
+            "#,
+            filter.after_sequence,
+            filter.email,
+            filter.event_type.map(|event_type| event_type.as_str()),
+            filter.from,
+            filter.to,
+            filter.limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AuditLogStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(AuditRecord {
+                    sequence: row.sequence,
+                    event_type: AuditEventType::parse(&row.event_type)
+                        .map_err(AuditLogStoreError::UnexpectedError)?,
+                    email: row.email,
+                    ip_address: row.ip_address,
+                    details: row.details,
+                    occurred_at: row.occurred_at,
+                    previous_hash: row.previous_hash,
+                    hash: row.hash,
+                })
+            })
+            .collect()
+    }
+}
\ No newline at end of file
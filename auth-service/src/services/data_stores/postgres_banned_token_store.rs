@@ -0,0 +1,83 @@
+use chrono::{Duration, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::Secret;
+use sqlx::PgPool;
+
+use crate::{
+    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    services::sweeper::ExpiringStore,
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+use super::hashing::token_hash;
+
+/// Shares logouts between every instance of the service through PostgreSQL.
+/// A row outlives its ban until the sweeper deletes it.
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+    async fn add_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.add_token_hash(token_hash(&token)).await
+    }
+
+    #[tracing::instrument(name = "Storing banned JWT in PostgreSQL", skip_all)]
+    async fn add_token_hash(&self, token_hash: String) -> Result<(), BannedTokenStoreError> {
+        let expires_at = Utc::now() + Duration::seconds(TOKEN_TTL_SECONDS);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO banned_tokens (token_hash, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (token_hash) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            "#,
+            token_hash,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert banned token")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking for banned JWT in PostgreSQL", skip_all)]
+    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
+        let is_banned = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM banned_tokens WHERE token_hash = $1 AND expires_at > now()
+            ) AS "is_banned!"
+            "#,
+            token_hash(token)
+        )
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check if token is banned")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(is_banned)
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresBannedTokenStore {
+    #[tracing::instrument(name = "Deleting expired banned JWTs from PostgreSQL", skip_all)]
+    async fn delete_expired(&self) -> Result<u64> {
+        let result = sqlx::query!("DELETE FROM banned_tokens WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete expired banned tokens")?;
+
+        Ok(result.rows_affected())
+    }
+}
\ No newline at end of file
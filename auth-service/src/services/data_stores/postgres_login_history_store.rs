@@ -0,0 +1,213 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        Device, Email, Login, LoginHistoryStore, LoginHistoryStoreError, ReportToken,
+        ReportedLogin, REPORT_TOKEN_MAX_AGE_DAYS,
+    },
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+use super::hashing::token_hash;
+
+pub struct PostgresLoginHistoryStore {
+    pool: PgPool,
+}
+
+impl PostgresLoginHistoryStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginHistoryStore for PostgresLoginHistoryStore {
+    // A hash of the session token is kept so a reported login can be revoked.
+    // It is removed once reported, and the JWT expires anyway.
+    #[tracing::instrument(name = "Adding login to PostgreSQL", skip_all)]
+    async fn add_login(
+        &self,
+        login: Login,
+        report_token: &ReportToken,
+    ) -> Result<(), LoginHistoryStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO login_history (user_id, ip_address, user_agent, session_token_hash, report_token_hash)
+            VALUES ((SELECT id FROM users WHERE lower(email) = lower($1)), $2, $3, $4, $5)
+            "#,
+            login.email.as_ref().expose_secret(),
+            login.device.ip_address,
+            login.device.user_agent,
+            token_hash(&login.session_token),
+            report_token.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking login history in PostgreSQL", skip_all)]
+    async fn has_logins(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+            ) AS "exists!"
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Checking login history for device in PostgreSQL", skip_all)]
+    async fn has_login_from_device(
+        &self,
+        email: &Email,
+        device: &Device,
+    ) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+                    AND ip_address = $2
+                    AND user_agent = $3
+            ) AS "exists!"
+            "#,
+            email.as_ref().expose_secret(),
+            device.ip_address,
+            device.user_agent
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))
+    }
+
+    // The old session token hash is returned by the same statement that clears it, so
+    // only the first report of a login sees it.
+    #[tracing::instrument(name = "Reporting login in PostgreSQL", skip_all)]
+    async fn report_login(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<ReportedLogin, LoginHistoryStoreError> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE login_history AS l
+            SET reported_at = COALESCE(l.reported_at, NOW()), session_token_hash = NULL
+            FROM login_history AS old, users AS u
+            WHERE l.id = old.id
+                AND u.id = l.user_id
+                AND l.report_token_hash = $1
+                AND l.logged_in_at > NOW() - make_interval(days => $2)
+            RETURNING u.email, old.session_token_hash
+            "#,
+            report_token.hash(),
+            REPORT_TOKEN_MAX_AGE_DAYS
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))?
+        .ok_or(LoginHistoryStoreError::LoginNotFound)?;
+
+        Ok(ReportedLogin {
+            email: Email::parse(Secret::new(row.email))
+                .map_err(LoginHistoryStoreError::UnexpectedError)?,
+            session_token_hash: row.session_token_hash,
+        })
+    }
+
+    #[tracing::instrument(name = "Checking pending password reset in PostgreSQL", skip_all)]
+    async fn requires_password_reset(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+                    AND reported_at IS NOT NULL
+                    AND password_reset_at IS NULL
+            ) AS "exists!"
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Retrieving password reset email from PostgreSQL", skip_all)]
+    async fn get_password_reset_email(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<Email, LoginHistoryStoreError> {
+        let email = sqlx::query_scalar!(
+            r#"
+            SELECT u.email FROM login_history AS l
+            JOIN users AS u ON u.id = l.user_id
+            WHERE l.report_token_hash = $1
+                AND l.logged_in_at > NOW() - make_interval(days => $2)
+                AND l.reported_at IS NOT NULL
+                AND l.password_reset_at IS NULL
+            "#,
+            report_token.hash(),
+            REPORT_TOKEN_MAX_AGE_DAYS
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))?
+        .ok_or(LoginHistoryStoreError::LoginNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(LoginHistoryStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Completing password reset in PostgreSQL", skip_all)]
+    async fn complete_password_reset(&self, email: &Email) -> Result<(), LoginHistoryStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE login_history
+            SET password_reset_at = NOW()
+            WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+                AND reported_at IS NOT NULL
+                AND password_reset_at IS NULL
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking session token hashes from PostgreSQL", skip_all)]
+    async fn take_session_token_hashes(
+        &self,
+        email: &Email,
+        current_session_token: Option<&Secret<String>>,
+    ) -> Result<Vec<String>, LoginHistoryStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE login_history AS l
+            SET session_token_hash = NULL
+            FROM login_history AS old
+            WHERE l.id = old.id
+                AND l.user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+                AND l.session_token_hash IS NOT NULL
+                AND ($2::TEXT IS NULL OR l.session_token_hash <> $2)
+                AND l.logged_in_at > NOW() - make_interval(secs => $3)
+            RETURNING old.session_token_hash AS "session_token_hash!"
+            "#,
+            email.as_ref().expose_secret(),
+            current_session_token.map(token_hash),
+            TOKEN_TTL_SECONDS as f64
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| LoginHistoryStoreError::UnexpectedError(e.into()))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,141 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{Device, Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+
+pub struct PostgresTrustedDeviceStore {
+    pool: PgPool,
+}
+
+impl PostgresTrustedDeviceStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for PostgresTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to PostgreSQL", skip_all)]
+    async fn add_device(&self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        // Expired devices are never used again, so this is a good time to drop them.
+        sqlx::query!(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+                AND expires_at <= NOW()
+            "#,
+            device.email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO trusted_devices (id, user_id, ip_address, user_agent, created_at, expires_at)
+            VALUES ($1, (SELECT id FROM users WHERE lower(email) = lower($2)), $3, $4, $5, $6)
+            "#,
+            device.id,
+            device.email.as_ref().expose_secret(),
+            device.device.ip_address,
+            device.device.user_agent,
+            device.created_at,
+            device.expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking trusted device in PostgreSQL", skip_all)]
+    async fn is_trusted(&self, email: &Email, id: &Uuid) -> Result<bool, TrustedDeviceStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM trusted_devices
+                WHERE id = $1
+                    AND user_id = (SELECT id FROM users WHERE lower(email) = lower($2))
+                    AND expires_at > NOW()
+            ) AS "exists!"
+            "#,
+            id,
+            email.as_ref().expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Retrieving trusted devices from PostgreSQL", skip_all)]
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT d.id, u.email, d.ip_address, d.user_agent, d.created_at, d.expires_at
+            FROM trusted_devices AS d
+            JOIN users AS u ON u.id = d.user_id
+            WHERE lower(u.email) = lower($1) AND d.expires_at > NOW()
+            ORDER BY d.created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(TrustedDevice {
+                    id: row.id,
+                    email: Email::parse(Secret::new(row.email))
+                        .map_err(TrustedDeviceStoreError::UnexpectedError)?,
+                    device: Device::new(row.ip_address, row.user_agent),
+                    created_at: row.created_at,
+                    expires_at: row.expires_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from PostgreSQL", skip_all)]
+    async fn remove_device(&self, email: &Email, id: &Uuid) -> Result<(), TrustedDeviceStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE id = $1 AND user_id = (SELECT id FROM users WHERE lower(email) = lower($2))
+            "#,
+            id,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TrustedDeviceStoreError::DeviceNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all trusted devices from PostgreSQL", skip_all)]
+    async fn remove_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE user_id = (SELECT id FROM users WHERE lower(email) = lower($1))
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TrustedDeviceStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,173 @@
+use chrono::{Duration, Utc};
+use color_eyre::eyre::{Context, Result};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+            MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_TTL_SECONDS,
+        },
+        Email,
+    },
+    services::sweeper::ExpiringStore,
+    utils::csrf::constant_time_eq,
+};
+
+use super::two_fa_hashes::{keyed_hash, TwoFAHashes};
+
+/// Pending 2FA logins shared by every instance through PostgreSQL, for
+/// deployments without Redis. A row holds keyed hashes only, so a database dump
+/// can neither finish a login nor tell whose it was.
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Storing 2FA code in PostgreSQL", skip_all)]
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let hashes =
+            TwoFAHashes::new(&email, &code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let expires_at = Utc::now() + Duration::seconds(TWO_FA_CODE_TTL_SECONDS as i64);
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO two_fa_codes (attempt_hash, email_hash, code_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            attempt_hash,
+            hashes.email,
+            hashes.code,
+            expires_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // `id` comes from a sequence, so the lowest ids of the user are dropped.
+        sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email_hash = $1 AND id NOT IN (
+                SELECT id FROM two_fa_codes
+                WHERE email_hash = $1
+                ORDER BY id DESC
+                LIMIT $2
+            )
+            "#,
+            hashes.email,
+            MAX_PENDING_LOGIN_ATTEMPTS as i64
+        )
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to delete old 2FA codes")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction")
+            .map_err(TwoFACodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from PostgreSQL", skip_all)]
+    async fn remove_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let email_hash =
+            keyed_hash(&email.normalized()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE attempt_hash = $1 AND email_hash = $2 AND expires_at > now()
+            "#,
+            attempt_hash,
+            email_hash
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Validating 2FA code in PostgreSQL", skip_all)]
+    async fn validate_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let stored = sqlx::query!(
+            r#"
+            SELECT email_hash, code_hash
+            FROM two_fa_codes
+            WHERE attempt_hash = $1 AND expires_at > now()
+            "#,
+            attempt_hash
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let given = TwoFAHashes::new(email, code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if !constant_time_eq(stored.email_hash.as_bytes(), given.email.as_bytes()) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        match constant_time_eq(stored.code_hash.as_bytes(), given.code.as_bytes()) {
+            true => Ok(()),
+            false => Err(TwoFACodeStoreError::IncorrectCode),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Deleting expired 2FA codes from PostgreSQL", skip_all)]
+    async fn delete_expired(&self) -> Result<u64> {
+        let result = sqlx::query!("DELETE FROM two_fa_codes WHERE expires_at <= now()")
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete expired 2FA codes")?;
+
+        Ok(result.rows_affected())
+    }
+}
\ No newline at end of file
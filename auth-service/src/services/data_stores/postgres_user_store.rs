@@ -1,74 +1,122 @@
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Context, Result};
 
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Email, Password, PasswordHash, PasswordHasherType, User, UserFilter, UserId, UserUpdate,
 };
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    password_hasher: PasswordHasherType,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, password_hasher: PasswordHasherType) -> Self {
+        Self {
+            pool,
+            password_hasher,
+        }
+    }
+
+    // Returns false if there is no user with this email.
+    #[tracing::instrument(name = "Storing password hash in PostgreSQL", skip_all)]
+    async fn store_password_hash(&self, email: &Email, password: &Password) -> Result<bool> {
+        let password_hash = self.password_hasher.hash(password).await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1, password_pepper_version = $2
+            WHERE lower(email) = lower($3)
+            "#,
+            password_hash.as_ref().expose_secret(),
+            password_hash.pepper_version(),
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update password hash")?;
+
+        Ok(result.rows_affected() > 0)
     }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
-    #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = compute_password_hash(user.password.as_ref().to_owned())
-            .await
-            .map_err(UserStoreError::UnexpectedError)?;
+    fn password_hasher(&self) -> &PasswordHasherType {
+        &self.password_hasher
+    }
 
+    #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (
+                id, email, password_hash, password_pepper_version, requires_2fa,
+                email_verified, display_name, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
+            user.id.as_ref(),
             user.email.as_ref().expose_secret(),
-            &password_hash.expose_secret(),
-            user.requires_2fa
+            user.password_hash.as_ref().expose_secret(),
+            user.password_hash.pepper_version(),
+            user.requires_2fa,
+            user.email_verified,
+            user.display_name,
+            user.created_at
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
         Ok(())
     }
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        sqlx::query!(
+        sqlx::query_as!(
+            UserRow,
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
             FROM users
-            WHERE email = $1
+            WHERE lower(email) = lower($1)
             "#,
             email.as_ref().expose_secret()
         )
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
-        .map(|row| {
-            Ok(User {
-                email: Email::parse(Secret::new(row.email))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                password: Password::parse(Secret::new(row.password_hash))
-                    .map_err(UserStoreError::UnexpectedError)?,
-                requires_2fa: row.requires_2fa,
-            })
-        })
         .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            FROM users
+            WHERE id = $1
+            "#,
+            id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
     }
 
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
@@ -77,60 +125,164 @@ impl UserStore for PostgresUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        let user = self.get_user(email).await?;
+        let row = sqlx::query!(
+            r#"
+            SELECT password_hash, password_pepper_version
+            FROM users
+            WHERE lower(email) = lower($1)
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let Some(row) = row else {
+            self.password_hasher
+                .verify_dummy(password)
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            return Err(UserStoreError::UserNotFound);
+        };
+        let password_hash =
+            PasswordHash::new(Secret::new(row.password_hash), row.password_pepper_version);
 
-        verify_password_hash(
-            user.password.as_ref().to_owned(),
-            password.as_ref().to_owned(),
+        let is_valid = self
+            .password_hasher
+            .verify(password, &password_hash)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        if !is_valid {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        // Only possible now that the password is known.
+        if self.password_hasher.needs_rehash(&password_hash) {
+            if let Err(e) = self.store_password_hash(email, password).await {
+                tracing::warn!("Failed to rehash password: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.store_password_hash(email, &password).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(UserStoreError::UserNotFound),
+            Err(e) => Err(UserStoreError::UnexpectedError(e)),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user in PostgreSQL", skip_all)]
+    async fn update_user(&self, email: &Email, update: UserUpdate) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            UPDATE users
+            SET requires_2fa = COALESCE($1, requires_2fa),
+                email_verified = COALESCE($2, email_verified),
+                display_name = CASE WHEN $3 THEN $4 ELSE display_name END
+            WHERE lower(email) = lower($5)
+            RETURNING id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            "#,
+            update.requires_2fa,
+            update.email_verified,
+            update.display_name.is_some(),
+            update.display_name.flatten(),
+            email.as_ref().expose_secret()
         )
+        .fetch_optional(&self.pool)
         .await
-        .map_err(|_| UserStoreError::InvalidCredentials)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
     }
-}
 
-#[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
-) -> Result<()> {
-    let current_span: tracing::Span = tracing::Span::current();
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(expected_password_hash.expose_secret())?;
-
-            Argon2::default()
-                .verify_password(
-                    password_candidate.expose_secret().as_bytes(),
-                    &expected_password_hash,
-                )
-                .wrap_err("failed to verify password hash")
-        })
-    })
-    .await;
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip_all)]
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM users
+            WHERE lower(email) = lower($1)
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Listing users in PostgreSQL", skip_all)]
+    async fn list_users(&self, filter: &UserFilter) -> Result<Vec<User>, UserStoreError> {
+        let rows = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            FROM users
+            WHERE ($1::TEXT IS NULL
+                    OR strpos(lower(email), lower($1)) > 0
+                    OR strpos(lower(display_name), lower($1)) > 0)
+                AND ($2::TIMESTAMPTZ IS NULL OR created_at > $2)
+                AND ($3::BOOLEAN IS NULL OR requires_2fa = $3)
+                AND ($4::BOOLEAN IS NULL OR email_verified = $4)
+            ORDER BY created_at, id
+            OFFSET $5
+            LIMIT $6
+            "#,
+            filter.search,
+            filter.created_after,
+            filter.requires_2fa,
+            filter.email_verified,
+            filter.offset.max(0),
+            filter.limit.max(0)
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter().map(User::try_from).collect()
+    }
+}
 
-    result?
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password_hash: String,
+    password_pepper_version: Option<i32>,
+    requires_2fa: bool,
+    email_verified: bool,
+    display_name: Option<String>,
+    created_at: DateTime<Utc>,
 }
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
-    let current_span: tracing::Span = tracing::Span::current();
-
-    let result = tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
 
-            Ok(Secret::new(password_hash))
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: row.id.into(),
+            email: Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
+            password_hash: PasswordHash::new(
+                Secret::new(row.password_hash),
+                row.password_pepper_version,
+            ),
+            requires_2fa: row.requires_2fa,
+            email_verified: row.email_verified,
+            display_name: row.display_name,
+            created_at: row.created_at,
         })
-    })
-    .await;
-
-    result?
+    }
 }
\ No newline at end of file
@@ -1,30 +1,42 @@
-use std::sync::Arc;
-
 use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use secrecy::{ExposeSecret, Secret};
-use tokio::sync::RwLock;
 
 use crate::{
     domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    services::redis_connection::RedisConnection,
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
+use super::hashing::token_hash;
+
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: RedisConnection,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: RedisConnection) -> Self {
         Self { conn }
     }
+
+    async fn connection(&self) -> Result<ConnectionManager, BannedTokenStoreError> {
+        self.conn
+            .get()
+            .await
+            .wrap_err("failed to connect to Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
+    async fn add_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.add_token_hash(token_hash(&token)).await
+    }
+
     #[tracing::instrument(name = "Storing banned JWT in Redis", skip_all)]
-    async fn add_token(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        let token_key = get_key(token.expose_secret());
+    async fn add_token_hash(&self, token_hash: String) -> Result<(), BannedTokenStoreError> {
+        let token_key = get_key(&token_hash);
 
         let value = true;
 
@@ -34,10 +46,10 @@ impl BannedTokenStore for RedisBannedTokenStore {
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
         let _: () = self
-            .conn
-            .write()
-            .await
+            .connection()
+            .await?
             .set_ex(&token_key, value, ttl)
+            .await
             .wrap_err("failed to set banned token in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
@@ -46,17 +58,19 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
     #[tracing::instrument(name = "Checking for banned JWT in Redis", skip_all)]
     async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        let token_key = get_key(token.expose_secret());
+        // Tokens banned before keys were hashed are still looked up by
+        // themselves. Those keys are gone TOKEN_TTL_SECONDS after the upgrade.
+        let token_keys = [get_key(&token_hash(token)), get_key(token.expose_secret())];
 
-        let is_banned: bool = self
-            .conn
-            .write()
+        let banned_keys: usize = self
+            .connection()
+            .await?
+            .exists(&token_keys)
             .await
-            .exists(&token_key)
             .wrap_err("failed to check if token exists in Redis")
             .map_err(BannedTokenStoreError::UnexpectedError)?;
 
-        Ok(is_banned)
+        Ok(banned_keys > 0)
     }
 }
 
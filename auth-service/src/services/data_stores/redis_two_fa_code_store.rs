@@ -1,50 +1,84 @@
-use std::sync::Arc;
-
-use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
-use secrecy::{ExposeSecret, Secret};
-use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
-
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use redis::aio::ConnectionManager;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+            MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_TTL_SECONDS,
+        },
+        Email,
+    },
+    services::redis_connection::RedisConnection,
+    utils::csrf::constant_time_eq,
 };
 
+use super::two_fa_hashes::{keyed_hash, TwoFAHashes};
+
+/// Keeps pending 2FA logins in Redis without anything that could be used to
+/// finish them or that names the user: each login attempt is stored under a
+/// keyed hash of its ID, holding keyed hashes of the email and the code. A
+/// sorted set per user, also keyed by a hash, orders the attempts so only the
+/// newest [`MAX_PENDING_LOGIN_ATTEMPTS`] stay valid. The hash key is derived
+/// from `JWT_SECRET`, which Redis never sees.
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: RedisConnection,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: RedisConnection) -> Self {
         Self { conn }
     }
+
+    async fn connection(&self) -> Result<ConnectionManager, TwoFACodeStoreError> {
+        self.conn
+            .get()
+            .await
+            .wrap_err("failed to connect to Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)
+    }
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "Storing 2FA code in Redis", skip_all)]
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let keys =
+            Keys::new(&email, &login_attempt_id).map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let data = TwoFATuple(
-            login_attempt_id.as_ref().expose_secret().to_owned(),
-            code.as_ref().expose_secret().to_owned(),
-        );
+        let data = TwoFAHashes::new(&email, &code).map_err(TwoFACodeStoreError::UnexpectedError)?;
         let serialized_data = serde_json::to_string(&data)
-            .wrap_err("failed to serialize 2FA tuple")
+            .wrap_err("failed to serialize 2FA hashes")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        let _: () = self
-            .conn
-            .write()
+        let now = Utc::now().timestamp_millis();
+        let expired = now - TWO_FA_CODE_TTL_SECONDS as i64 * 1000;
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(&keys.code, serialized_data, TWO_FA_CODE_TTL_SECONDS)
+            .ignore()
+            .zadd(&keys.attempts, &keys.attempt, now)
+            .ignore()
+            .zrembyscore(&keys.attempts, "-inf", expired)
+            .ignore()
+            // Keeps the newest attempts, dropping the oldest from the set
+            // invalidates their codes.
+            .zremrangebyrank(
+                &keys.attempts,
+                0,
+                -(MAX_PENDING_LOGIN_ATTEMPTS as isize) - 1,
+            )
+            .ignore()
+            .expire(&keys.attempts, TWO_FA_CODE_TTL_SECONDS as i64)
+            .ignore()
+            .query_async(&mut self.connection().await?)
             .await
-            .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
             .wrap_err("failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -52,52 +86,129 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     }
 
     #[tracing::instrument(name = "Removing 2FA code from Redis", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
-
-        let _: () = self
-            .conn
-            .write()
+    async fn remove_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let keys =
+            Keys::new(email, login_attempt_id).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let (_, removed): (u64, u64) = redis::pipe()
+            .atomic()
+            .del(&keys.code)
+            .zrem(&keys.attempts, &keys.attempt)
+            .query_async(&mut self.connection().await?)
             .await
-            .del(&key)
             .wrap_err("failed to delete 2FA code from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        Ok(())
+        match removed {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
     }
 
-    #[tracing::instrument(name = "Retrieving 2FA code from Redis", skip_all)]
-    async fn get_code(
+    #[tracing::instrument(name = "Validating 2FA code in Redis", skip_all)]
+    async fn validate_code(
         &self,
         email: &Email,
-    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let keys =
+            Keys::new(email, login_attempt_id).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let (value, score): (Option<String>, Option<f64>) = redis::pipe()
+            .get(&keys.code)
+            .zscore(&keys.attempts, &keys.attempt)
+            .query_async(&mut self.connection().await?)
+            .await
+            .wrap_err("failed to get 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-        match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
-                let data: TwoFATuple = serde_json::from_str(&value)
-                    .wrap_err("failed to deserialize 2FA tuple")
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let (Some(value), Some(_)) = (value, score) else {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        };
 
-                let login_attempt_id = LoginAttemptId::parse(Secret::new(data.0))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let stored: TwoFAHashes = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize 2FA hashes")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let given = TwoFAHashes::new(email, code).map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-                let email_code = TwoFACode::parse(Secret::new(data.1))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        if !constant_time_eq(stored.email.as_bytes(), given.email.as_bytes()) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
 
-                Ok((login_attempt_id, email_code))
-            }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        match constant_time_eq(stored.code.as_bytes(), given.code.as_bytes()) {
+            true => Ok(()),
+            false => Err(TwoFACodeStoreError::IncorrectCode),
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
-
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
+
+struct Keys {
+    /// Holds the [`TwoFAHashes`] of one login attempt.
+    code: String,
+    /// Sorted set of the pending attempts of the user.
+    attempts: String,
+    /// Member of `attempts` for this login attempt.
+    attempt: String,
+}
+
+impl Keys {
+    fn new(email: &Email, login_attempt_id: &LoginAttemptId) -> Result<Self> {
+        let attempt = keyed_hash(login_attempt_id.as_ref())?;
+
+        Ok(Self {
+            code: format!("{}{}", TWO_FA_CODE_PREFIX, attempt),
+            attempts: format!(
+                "{}{}",
+                TWO_FA_ATTEMPTS_PREFIX,
+                keyed_hash(&email.normalized())?
+            ),
+            attempt,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::{ExposeSecret, Secret};
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
+    use super::*;
+
+    #[test]
+    fn test_stored_data_contains_no_secrets() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let login_attempt_id = LoginAttemptId::parse(Secret::new(
+            "6f1c1f3e-3b7a-4c51-9d2b-2f6f0b8f1a2c".to_owned(),
+        ))
+        .unwrap();
+        let code = TwoFACode::parse(Secret::new("123456".to_owned())).unwrap();
+
+        let keys = Keys::new(&email, &login_attempt_id).unwrap();
+        let value = serde_json::to_string(&TwoFAHashes::new(&email, &code).unwrap()).unwrap();
+
+        let login_attempt_id = login_attempt_id.as_ref().expose_secret();
+        for stored in [&keys.code, &keys.attempts, &keys.attempt, &value] {
+            assert!(!stored.contains("test@example.com"));
+            assert!(!stored.contains(login_attempt_id.as_str()));
+        }
+        assert!(!value.contains(code.as_ref().expose_secret()));
+    }
+
+    #[test]
+    fn test_keys_differ_per_login_attempt() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let first = Keys::new(&email, &LoginAttemptId::default()).unwrap();
+        let second = Keys::new(&email, &LoginAttemptId::default()).unwrap();
+
+        assert_ne!(first.code, second.code);
+        assert_eq!(first.attempts, second.attempts);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+
+use crate::domain::{
+    AuditEvent, AuditEventFilter, AuditEventType, AuditLogStore, AuditLogStoreError, AuditRecord,
+    GENESIS_HASH,
+};
+
+/// The audit log in the SQLite file. Triggers reject changes to it, as in
+/// PostgreSQL.
+pub struct SqliteAuditLogStore {
+    pool: SqlitePool,
+    append_lock: Mutex<()>,
+}
+
+impl SqliteAuditLogStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            append_lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLogStore for SqliteAuditLogStore {
+    // Only one instance uses the file, so holding the lock keeps other appends
+    // from coming between reading the last record and inserting this one.
+    #[tracing::instrument(name = "Appending audit event to SQLite", skip_all)]
+    async fn append(&self, event: AuditEvent) -> Result<AuditRecord, AuditLogStoreError> {
+        let _append_guard = self.append_lock.lock().await;
+
+        let last: Option<(i64, String)> = sqlx::query_as(
+            r#"
+            SELECT sequence, hash FROM audit_events
+            ORDER BY sequence DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get last audit event")
+        .map_err(AuditLogStoreError::UnexpectedError)?;
+
+        let (sequence, hash) = last.unwrap_or((0, GENESIS_HASH.to_owned()));
+
+        let record = AuditRecord::append(event, sequence, hash);
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_events (
+                sequence, event_type, email, normalized_email, ip_address, details, occurred_at,
+                previous_hash, hash
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+        )
+        .bind(record.sequence)
+        .bind(record.event_type.as_str())
+        .bind(&record.email)
+        .bind(record.email.as_ref().map(|email| email.to_lowercase()))
+        .bind(&record.ip_address)
+        .bind(&record.details)
+        .bind(record.occurred_at)
+        .bind(&record.previous_hash)
+        .bind(&record.hash)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert audit event")
+        .map_err(AuditLogStoreError::UnexpectedError)?;
+
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Retrieving audit events from SQLite", skip_all)]
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditLogStoreError> {
+        let rows = sqlx::query_as::<_, AuditEventRow>(
+            r#"
+            SELECT sequence, event_type, email, ip_address, details, occurred_at, previous_hash, hash
+            FROM audit_events
+            WHERE sequence > ?1
+                AND (?2 IS NULL OR normalized_email = ?2)
+                AND (?3 IS NULL OR event_type = ?3)
+                AND (?4 IS NULL OR occurred_at >= ?4)
+                AND (?5 IS NULL OR occurred_at < ?5)
+            ORDER BY sequence
+            LIMIT ?6
+            "#,
+        )
+        .bind(filter.after_sequence)
+        .bind(filter.email.as_ref().map(|email| email.to_lowercase()))
+        .bind(filter.event_type.map(|event_type| event_type.as_str()))
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.limit.max(0))
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to get audit events")
+        .map_err(AuditLogStoreError::UnexpectedError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(AuditRecord {
+                    sequence: row.sequence,
+                    event_type: AuditEventType::parse(&row.event_type)
+                        .map_err(AuditLogStoreError::UnexpectedError)?,
+                    email: row.email,
+                    ip_address: row.ip_address,
+                    details: row.details,
+                    occurred_at: row.occurred_at,
+                    previous_hash: row.previous_hash,
+                    hash: row.hash,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditEventRow {
+    sequence: i64,
+    event_type: String,
+    email: Option<String>,
+    ip_address: Option<String>,
+    details: Option<String>,
+    occurred_at: DateTime<Utc>,
+    previous_hash: String,
+    hash: String,
+}
\ No newline at end of file
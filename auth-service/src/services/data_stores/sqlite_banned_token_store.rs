@@ -0,0 +1,87 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use secrecy::Secret;
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+use super::hashing::token_hash;
+
+/// Banned tokens of a single instance running without Redis, kept in its
+/// SQLite file so that a restart does not undo logouts.
+pub struct SqliteBannedTokenStore {
+    pool: SqlitePool,
+}
+
+impl SqliteBannedTokenStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for SqliteBannedTokenStore {
+    async fn add_token(&self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
+        self.add_token_hash(token_hash(&token)).await
+    }
+
+    #[tracing::instrument(name = "Storing banned JWT in SQLite", skip_all)]
+    async fn add_token_hash(&self, token_hash: String) -> Result<(), BannedTokenStoreError> {
+        let now = Utc::now().timestamp();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        // There is no expiry in SQLite, so each ban clears out the expired ones.
+        sqlx::query("DELETE FROM banned_tokens WHERE expires_at <= ?1")
+            .bind(now)
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to delete expired banned tokens")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO banned_tokens (token_hash, expires_at)
+            VALUES (?1, ?2)
+            ON CONFLICT (token_hash) DO UPDATE SET expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(token_hash)
+        .bind(now + TOKEN_TTL_SECONDS)
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert banned token")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction")
+            .map_err(BannedTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Checking for banned JWT in SQLite", skip_all)]
+    async fn contains_token(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM banned_tokens WHERE token_hash = ?1 AND expires_at > ?2
+            )
+            "#,
+        )
+        .bind(token_hash(token))
+        .bind(Utc::now().timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check if token is banned")
+        .map_err(BannedTokenStoreError::UnexpectedError)
+    }
+}
\ No newline at end of file
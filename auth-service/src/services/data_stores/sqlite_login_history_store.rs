@@ -0,0 +1,273 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        Device, Email, Login, LoginHistoryStore, LoginHistoryStoreError, ReportToken,
+        ReportedLogin, REPORT_TOKEN_MAX_AGE_DAYS,
+    },
+    utils::auth::TOKEN_TTL_SECONDS,
+};
+
+use super::hashing::token_hash;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The login history in the SQLite file, so that reported logins and pending
+/// password resets outlive a restart.
+pub struct SqliteLoginHistoryStore {
+    pool: SqlitePool,
+}
+
+impl SqliteLoginHistoryStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+// Logins older than this can no longer be reported.
+fn reportable_since() -> i64 {
+    Utc::now().timestamp() - REPORT_TOKEN_MAX_AGE_DAYS as i64 * SECONDS_PER_DAY
+}
+
+#[async_trait::async_trait]
+impl LoginHistoryStore for SqliteLoginHistoryStore {
+    #[tracing::instrument(name = "Adding login to SQLite", skip_all)]
+    async fn add_login(
+        &self,
+        login: Login,
+        report_token: &ReportToken,
+    ) -> Result<(), LoginHistoryStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_history (
+                user_id, ip_address, user_agent, logged_in_at, session_token_hash,
+                report_token_hash
+            )
+            VALUES ((SELECT id FROM users WHERE normalized_email = ?1), ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(login.email.normalized().expose_secret())
+        .bind(&login.device.ip_address)
+        .bind(&login.device.user_agent)
+        .bind(Utc::now().timestamp())
+        .bind(token_hash(&login.session_token))
+        .bind(report_token.hash())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert login")
+        .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking login history in SQLite", skip_all)]
+    async fn has_logins(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1)
+            )
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check login history")
+        .map_err(LoginHistoryStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Checking login history for device in SQLite", skip_all)]
+    async fn has_login_from_device(
+        &self,
+        email: &Email,
+        device: &Device,
+    ) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1)
+                    AND ip_address = ?2
+                    AND user_agent = ?3
+            )
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .bind(&device.ip_address)
+        .bind(&device.user_agent)
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check login history for device")
+        .map_err(LoginHistoryStoreError::UnexpectedError)
+    }
+
+    // RETURNING gives the new values, so the session token hash is read by the
+    // first statement and cleared by the second. As the first one writes, the
+    // transaction holds the write lock from the start.
+    #[tracing::instrument(name = "Reporting login in SQLite", skip_all)]
+    async fn report_login(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<ReportedLogin, LoginHistoryStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction")
+            .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        let (id, email, session_token_hash): (i64, String, Option<String>) = sqlx::query_as(
+            r#"
+            UPDATE login_history
+            SET reported_at = COALESCE(reported_at, ?1)
+            WHERE report_token_hash = ?2 AND logged_in_at > ?3
+            RETURNING id, (SELECT email FROM users WHERE id = user_id), session_token_hash
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(report_token.hash())
+        .bind(reportable_since())
+        .fetch_optional(&mut *transaction)
+        .await
+        .wrap_err("failed to report login")
+        .map_err(LoginHistoryStoreError::UnexpectedError)?
+        .ok_or(LoginHistoryStoreError::LoginNotFound)?;
+
+        sqlx::query("UPDATE login_history SET session_token_hash = NULL WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to clear session token hash")
+            .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction")
+            .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        Ok(ReportedLogin {
+            email: Email::parse(Secret::new(email))
+                .map_err(LoginHistoryStoreError::UnexpectedError)?,
+            session_token_hash,
+        })
+    }
+
+    #[tracing::instrument(name = "Checking pending password reset in SQLite", skip_all)]
+    async fn requires_password_reset(&self, email: &Email) -> Result<bool, LoginHistoryStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1)
+                    AND reported_at IS NOT NULL
+                    AND password_reset_at IS NULL
+            )
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check for pending password reset")
+        .map_err(LoginHistoryStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Retrieving password reset email from SQLite", skip_all)]
+    async fn get_password_reset_email(
+        &self,
+        report_token: &ReportToken,
+    ) -> Result<Email, LoginHistoryStoreError> {
+        let email: String = sqlx::query_scalar(
+            r#"
+            SELECT u.email FROM login_history AS l
+            JOIN users AS u ON u.id = l.user_id
+            WHERE l.report_token_hash = ?1
+                AND l.logged_in_at > ?2
+                AND l.reported_at IS NOT NULL
+                AND l.password_reset_at IS NULL
+            "#,
+        )
+        .bind(report_token.hash())
+        .bind(reportable_since())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get password reset email")
+        .map_err(LoginHistoryStoreError::UnexpectedError)?
+        .ok_or(LoginHistoryStoreError::LoginNotFound)?;
+
+        Email::parse(Secret::new(email)).map_err(LoginHistoryStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Completing password reset in SQLite", skip_all)]
+    async fn complete_password_reset(&self, email: &Email) -> Result<(), LoginHistoryStoreError> {
+        sqlx::query(
+            r#"
+            UPDATE login_history
+            SET password_reset_at = ?1
+            WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?2)
+                AND reported_at IS NOT NULL
+                AND password_reset_at IS NULL
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .bind(email.normalized().expose_secret())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to complete password reset")
+        .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Taking session token hashes from SQLite", skip_all)]
+    async fn take_session_token_hashes(
+        &self,
+        email: &Email,
+        current_session_token: Option<&Secret<String>>,
+    ) -> Result<Vec<String>, LoginHistoryStoreError> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            r#"
+            SELECT id, session_token_hash FROM login_history
+            WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1)
+                AND session_token_hash IS NOT NULL
+                AND (?2 IS NULL OR session_token_hash <> ?2)
+                AND logged_in_at > ?3
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .bind(current_session_token.map(token_hash))
+        .bind(Utc::now().timestamp() - TOKEN_TTL_SECONDS)
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to get session token hashes")
+        .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+        // A hash is only handed out by the request that clears it.
+        let mut session_token_hashes = vec![];
+        for (id, session_token_hash) in rows {
+            let result = sqlx::query(
+                r#"
+                UPDATE login_history
+                SET session_token_hash = NULL
+                WHERE id = ?1 AND session_token_hash = ?2
+                "#,
+            )
+            .bind(id)
+            .bind(&session_token_hash)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to clear session token hash")
+            .map_err(LoginHistoryStoreError::UnexpectedError)?;
+
+            if result.rows_affected() > 0 {
+                session_token_hashes.push(session_token_hash);
+            }
+        }
+
+        Ok(session_token_hashes)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,176 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::domain::{Device, Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError};
+
+/// Trusted devices in the SQLite file, so that a restart does not bring back
+/// the 2FA prompt for them.
+pub struct SqliteTrustedDeviceStore {
+    pool: SqlitePool,
+}
+
+impl SqliteTrustedDeviceStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrustedDeviceStore for SqliteTrustedDeviceStore {
+    #[tracing::instrument(name = "Adding trusted device to SQLite", skip_all)]
+    async fn add_device(&self, device: TrustedDevice) -> Result<(), TrustedDeviceStoreError> {
+        let normalized_email = device.email.normalized();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        // Expired devices are never used again, so this is a good time to drop them.
+        sqlx::query(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1) AND expires_at <= ?2
+            "#,
+        )
+        .bind(normalized_email.expose_secret())
+        .bind(Utc::now())
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to delete expired trusted devices")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO trusted_devices (id, user_id, ip_address, user_agent, created_at, expires_at)
+            VALUES (?1, (SELECT id FROM users WHERE normalized_email = ?2), ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(device.id)
+        .bind(normalized_email.expose_secret())
+        .bind(&device.device.ip_address)
+        .bind(&device.device.user_agent)
+        .bind(device.created_at)
+        .bind(device.expires_at)
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert trusted device")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction")
+            .map_err(TrustedDeviceStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Checking trusted device in SQLite", skip_all)]
+    async fn is_trusted(&self, email: &Email, id: &Uuid) -> Result<bool, TrustedDeviceStoreError> {
+        sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM trusted_devices
+                WHERE id = ?1
+                    AND user_id = (SELECT id FROM users WHERE normalized_email = ?2)
+                    AND expires_at > ?3
+            )
+            "#,
+        )
+        .bind(id)
+        .bind(email.normalized().expose_secret())
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check trusted device")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Retrieving trusted devices from SQLite", skip_all)]
+    async fn get_devices(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<TrustedDevice>, TrustedDeviceStoreError> {
+        let rows = sqlx::query_as::<_, TrustedDeviceRow>(
+            r#"
+            SELECT d.id, u.email, d.ip_address, d.user_agent, d.created_at, d.expires_at
+            FROM trusted_devices AS d
+            JOIN users AS u ON u.id = d.user_id
+            WHERE u.normalized_email = ?1 AND d.expires_at > ?2
+            ORDER BY d.created_at
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .bind(Utc::now())
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to get trusted devices")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(TrustedDevice {
+                    id: row.id,
+                    email: Email::parse(Secret::new(row.email))
+                        .map_err(TrustedDeviceStoreError::UnexpectedError)?,
+                    device: Device::new(row.ip_address, row.user_agent),
+                    created_at: row.created_at,
+                    expires_at: row.expires_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing trusted device from SQLite", skip_all)]
+    async fn remove_device(&self, email: &Email, id: &Uuid) -> Result<(), TrustedDeviceStoreError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE id = ?1 AND user_id = (SELECT id FROM users WHERE normalized_email = ?2)
+            "#,
+        )
+        .bind(id)
+        .bind(email.normalized().expose_secret())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete trusted device")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(TrustedDeviceStoreError::DeviceNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing all trusted devices from SQLite", skip_all)]
+    async fn remove_all_devices(&self, email: &Email) -> Result<(), TrustedDeviceStoreError> {
+        sqlx::query(
+            r#"
+            DELETE FROM trusted_devices
+            WHERE user_id = (SELECT id FROM users WHERE normalized_email = ?1)
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete trusted devices")
+        .map_err(TrustedDeviceStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TrustedDeviceRow {
+    id: Uuid,
+    email: String,
+    ip_address: String,
+    user_agent: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
\ No newline at end of file
@@ -0,0 +1,171 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+            MAX_PENDING_LOGIN_ATTEMPTS, TWO_FA_CODE_TTL_SECONDS,
+        },
+        Email,
+    },
+    utils::csrf::constant_time_eq,
+};
+
+use super::two_fa_hashes::{keyed_hash, TwoFAHashes};
+
+/// Pending 2FA logins in the SQLite file, hashed with the same keys as in
+/// PostgreSQL. Adding a login clears out the expired ones, as there is no
+/// sweeper here.
+pub struct SqliteTwoFACodeStore {
+    pool: SqlitePool,
+}
+
+impl SqliteTwoFACodeStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for SqliteTwoFACodeStore {
+    #[tracing::instrument(name = "Storing 2FA code in SQLite", skip_all)]
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let hashes =
+            TwoFAHashes::new(&email, &code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let now = Utc::now().timestamp();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start transaction")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query("DELETE FROM two_fa_codes WHERE expires_at <= ?1")
+            .bind(now)
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to delete expired 2FA codes")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO two_fa_codes (attempt_hash, email_hash, code_hash, expires_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+        .bind(&attempt_hash)
+        .bind(&hashes.email)
+        .bind(&hashes.code)
+        .bind(now + TWO_FA_CODE_TTL_SECONDS as i64)
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // A new row gets a rowid above all the others, so the lowest are the oldest.
+        sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE email_hash = ?1 AND id NOT IN (
+                SELECT id FROM two_fa_codes
+                WHERE email_hash = ?1
+                ORDER BY id DESC
+                LIMIT ?2
+            )
+            "#,
+        )
+        .bind(&hashes.email)
+        .bind(MAX_PENDING_LOGIN_ATTEMPTS as i64)
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to delete old 2FA codes")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit transaction")
+            .map_err(TwoFACodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from SQLite", skip_all)]
+    async fn remove_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let email_hash =
+            keyed_hash(&email.normalized()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let result = sqlx::query(
+            r#"
+            DELETE FROM two_fa_codes
+            WHERE attempt_hash = ?1 AND email_hash = ?2 AND expires_at > ?3
+            "#,
+        )
+        .bind(attempt_hash)
+        .bind(email_hash)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Validating 2FA code in SQLite", skip_all)]
+    async fn validate_code(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let attempt_hash =
+            keyed_hash(login_attempt_id.as_ref()).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let stored: Option<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT email_hash, code_hash
+            FROM two_fa_codes
+            WHERE attempt_hash = ?1 AND expires_at > ?2
+            "#,
+        )
+        .bind(attempt_hash)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get 2FA code")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let Some((email_hash, code_hash)) = stored else {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        };
+
+        let given = TwoFAHashes::new(email, code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if !constant_time_eq(email_hash.as_bytes(), given.email.as_bytes()) {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        match constant_time_eq(code_hash.as_bytes(), given.code.as_bytes()) {
+            true => Ok(()),
+            false => Err(TwoFACodeStoreError::IncorrectCode),
+        }
+    }
+}
\ No newline at end of file
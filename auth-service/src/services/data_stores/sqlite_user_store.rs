@@ -0,0 +1,344 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{UserStore, UserStoreError},
+    Email, Password, PasswordHash, PasswordHasherType, User, UserFilter, UserId, UserUpdate,
+};
+
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+    password_hasher: PasswordHasherType,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool, password_hasher: PasswordHasherType) -> Self {
+        Self {
+            pool,
+            password_hasher,
+        }
+    }
+
+    // Returns false if there is no user with this email.
+    #[tracing::instrument(name = "Storing password hash in SQLite", skip_all)]
+    async fn store_password_hash(&self, email: &Email, password: &Password) -> Result<bool> {
+        let password_hash = self.password_hasher.hash(password).await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET password_hash = ?1, password_pepper_version = ?2
+            WHERE normalized_email = ?3
+            "#,
+        )
+        .bind(password_hash.as_ref().expose_secret())
+        .bind(password_hash.pepper_version())
+        .bind(email.normalized().expose_secret())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update password hash")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    fn password_hasher(&self) -> &PasswordHasherType {
+        &self.password_hasher
+    }
+
+    #[tracing::instrument(name = "Adding user to SQLite", skip_all)]
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        sqlx::query(
+            r#"
+            INSERT INTO users (
+                id, email, normalized_email, password_hash, password_pepper_version,
+                requires_2fa, email_verified, display_name, normalized_display_name, created_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+        )
+        .bind(user.id.as_ref())
+        .bind(user.email.as_ref().expose_secret())
+        .bind(user.email.normalized().expose_secret())
+        .bind(user.password_hash.as_ref().expose_secret())
+        .bind(user.password_hash.pepper_version())
+        .bind(user.requires_2fa)
+        .bind(user.email_verified)
+        .bind(&user.display_name)
+        .bind(normalize_display_name(&user.display_name))
+        .bind(user.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e.as_database_error() {
+            Some(db_error) if db_error.is_unique_violation() => UserStoreError::UserAlreadyExists,
+            _ => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user from SQLite", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            FROM users
+            WHERE normalized_email = ?1
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from SQLite", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            FROM users
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Validating user credentials in SQLite", skip_all)]
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        let row: Option<(String, Option<i32>)> = sqlx::query_as(
+            r#"
+            SELECT password_hash, password_pepper_version
+            FROM users
+            WHERE normalized_email = ?1
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let Some((password_hash, pepper_version)) = row else {
+            self.password_hasher
+                .verify_dummy(password)
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            return Err(UserStoreError::UserNotFound);
+        };
+        let password_hash = PasswordHash::new(Secret::new(password_hash), pepper_version);
+
+        let is_valid = self
+            .password_hasher
+            .verify(password, &password_hash)
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        if !is_valid {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        if self.password_hasher.needs_rehash(&password_hash) {
+            if let Err(e) = self.store_password_hash(email, password).await {
+                tracing::warn!("Failed to rehash password: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password in SQLite", skip_all)]
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.store_password_hash(email, &password).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(UserStoreError::UserNotFound),
+            Err(e) => Err(UserStoreError::UnexpectedError(e)),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user in SQLite", skip_all)]
+    async fn update_user(&self, email: &Email, update: UserUpdate) -> Result<User, UserStoreError> {
+        let update_display_name = update.display_name.is_some();
+        let display_name = update.display_name.flatten();
+
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            UPDATE users
+            SET requires_2fa = COALESCE(?1, requires_2fa),
+                email_verified = COALESCE(?2, email_verified),
+                display_name = CASE WHEN ?3 THEN ?4 ELSE display_name END,
+                normalized_display_name = CASE WHEN ?3 THEN ?6 ELSE normalized_display_name END
+            WHERE normalized_email = ?5
+            RETURNING id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            "#,
+        )
+        .bind(update.requires_2fa)
+        .bind(update.email_verified)
+        .bind(update_display_name)
+        .bind(&display_name)
+        .bind(email.normalized().expose_secret())
+        .bind(normalize_display_name(&display_name))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Deleting user from SQLite", skip_all)]
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM users
+            WHERE normalized_email = ?1
+            "#,
+        )
+        .bind(email.normalized().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Listing users in SQLite", skip_all)]
+    async fn list_users(&self, filter: &UserFilter) -> Result<Vec<User>, UserStoreError> {
+        // created_at is stored as RFC 3339 text in UTC, which sorts by time.
+        let rows = sqlx::query_as::<_, UserRow>(
+            r#"
+            SELECT id, email, password_hash, password_pepper_version, requires_2fa, email_verified,
+                display_name, created_at
+            FROM users
+            WHERE (?1 IS NULL
+                    OR instr(normalized_email, ?1) > 0
+                    OR instr(normalized_display_name, ?1) > 0)
+                AND (?2 IS NULL OR created_at > ?2)
+                AND (?3 IS NULL OR requires_2fa = ?3)
+                AND (?4 IS NULL OR email_verified = ?4)
+            ORDER BY created_at, id
+            LIMIT ?6
+            OFFSET ?5
+            "#,
+        )
+        .bind(filter.search.as_ref().map(|search| search.to_lowercase()))
+        .bind(filter.created_after)
+        .bind(filter.requires_2fa)
+        .bind(filter.email_verified)
+        .bind(filter.offset.max(0))
+        .bind(filter.limit.max(0))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter().map(User::try_from).collect()
+    }
+}
+
+// SQLite lowercases only ASCII, so names are searched in this form.
+fn normalize_display_name(display_name: &Option<String>) -> Option<String> {
+    display_name
+        .as_ref()
+        .map(|display_name| display_name.to_lowercase())
+}
+
+#[derive(sqlx::FromRow)]
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password_hash: String,
+    password_pepper_version: Option<i32>,
+    requires_2fa: bool,
+    email_verified: bool,
+    display_name: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: row.id.into(),
+            email: Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
+            password_hash: PasswordHash::new(
+                Secret::new(row.password_hash),
+                row.password_pepper_version,
+            ),
+            requires_2fa: row.requires_2fa,
+            email_verified: row.email_verified,
+            display_name: row.display_name,
+            created_at: row.created_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use secrecy::Secret;
+
+    use crate::services::argon2_password_hasher::Argon2PasswordHasher;
+
+    use super::*;
+
+    async fn store() -> SqliteUserStore {
+        let pool = crate::get_sqlite_pool(&Secret::new("sqlite::memory:".to_owned()))
+            .await
+            .unwrap();
+        SqliteUserStore::new(pool, Arc::new(Argon2PasswordHasher::default()))
+    }
+
+    fn password() -> Password {
+        Password::parse(Secret::new("password123".to_owned())).unwrap()
+    }
+
+    async fn user(store: &SqliteUserStore, email: &str) -> User {
+        User::new(
+            Email::parse(Secret::new(email.to_owned())).unwrap(),
+            store.password_hasher.hash(&password()).await.unwrap(),
+            false,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_validate_user() {
+        let store = store().await;
+        let user = user(&store, "test@example.com").await;
+        store.add_user(user.clone()).await.unwrap();
+
+        assert!(store.validate_user(&user.email, &password()).await.is_ok());
+
+        let wrong_password = Password::parse(Secret::new("password124".to_owned())).unwrap();
+        let result = store.validate_user(&user.email, &wrong_password).await;
+        assert_eq!(result.unwrap_err(), UserStoreError::InvalidCredentials);
+
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        let result = store.validate_user(&other_email, &password()).await;
+        assert_eq!(result.unwrap_err(), UserStoreError::UserNotFound);
+    }
+}
\ No newline at end of file
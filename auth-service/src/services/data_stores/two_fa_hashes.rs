@@ -0,0 +1,36 @@
+use color_eyre::eyre::{Context, Result};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    domain::{data_stores::TwoFACode, Email},
+    utils::auth::derive_key,
+};
+
+/// What the 2FA stores outside of memory keep of a pending login, so their
+/// contents can neither finish the login nor name the user.
+#[derive(Serialize, Deserialize)]
+pub(super) struct TwoFAHashes {
+    pub email: String,
+    pub code: String,
+}
+
+impl TwoFAHashes {
+    pub fn new(email: &Email, code: &TwoFACode) -> Result<Self> {
+        Ok(Self {
+            email: keyed_hash(&email.normalized())?,
+            code: keyed_hash(code.as_ref())?,
+        })
+    }
+}
+
+// The key is derived from `JWT_SECRET`, which the database never sees.
+pub(super) fn keyed_hash(value: &Secret<String>) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&derive_key(b"two-fa-code")?)
+        .wrap_err("failed to create HMAC")?;
+    mac.update(value.expose_secret().as_bytes());
+
+    Ok(format!("{:x}", mac.finalize().into_bytes()))
+}
\ No newline at end of file
@@ -0,0 +1,42 @@
+use std::sync::RwLock;
+
+use crate::domain::{
+    AuditEvent, AuditEventFilter, AuditLogStore, AuditLogStoreError, AuditRecord, GENESIS_HASH,
+};
+
+#[derive(Default)]
+pub struct VecAuditLogStore {
+    records: RwLock<Vec<AuditRecord>>,
+}
+
+#[async_trait::async_trait]
+impl AuditLogStore for VecAuditLogStore {
+    async fn append(&self, event: AuditEvent) -> Result<AuditRecord, AuditLogStoreError> {
+        let mut records = self.records.write().expect("audit log store lock poisoned");
+
+        let (sequence, hash) = match records.last() {
+            Some(last) => (last.sequence, last.hash.clone()),
+            None => (0, GENESIS_HASH.to_owned()),
+        };
+
+        let record = AuditRecord::append(event, sequence, hash);
+        records.push(record.clone());
+
+        Ok(record)
+    }
+
+    async fn get_events(
+        &self,
+        filter: &AuditEventFilter,
+    ) -> Result<Vec<AuditRecord>, AuditLogStoreError> {
+        Ok(self
+            .records
+            .read()
+            .expect("audit log store lock poisoned")
+            .iter()
+            .filter(|record| filter.matches(record))
+            .take(filter.limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use color_eyre::eyre::{Context, Result};
+use secrecy::ExposeSecret;
+use sha1::{Digest, Sha1};
+
+use crate::domain::{BreachedPasswordChecker, Password};
+
+/// Looks passwords up in an offline copy of the Have I Been Pwned password list,
+/// stored as SHA-1 range files (`<PREFIX>.txt` with `SUFFIX:COUNT` lines), the
+/// layout produced by the official PwnedPasswordsDownloader.
+pub struct HibpBreachedPasswordChecker {
+    range_files_dir: PathBuf,
+}
+
+impl HibpBreachedPasswordChecker {
+    pub fn new(range_files_dir: PathBuf) -> Self {
+        Self { range_files_dir }
+    }
+}
+
+#[async_trait::async_trait]
+impl BreachedPasswordChecker for HibpBreachedPasswordChecker {
+    #[tracing::instrument(name = "Checking breached password list", skip_all)]
+    async fn is_breached(&self, password: &Password) -> Result<bool> {
+        let hash = format!(
+            "{:X}",
+            Sha1::digest(password.as_ref().expose_secret().as_bytes())
+        );
+        let (prefix, suffix) = hash.split_at(RANGE_PREFIX_LENGTH);
+
+        let range_file = self.range_files_dir.join(format!("{}.txt", prefix));
+        let contents = tokio::fs::read_to_string(&range_file)
+            .await
+            .wrap_err(format!(
+                "failed to read range file {}",
+                range_file.display()
+            ))?;
+
+        Ok(contents.lines().any(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|candidate| candidate.trim().eq_ignore_ascii_case(suffix))
+        }))
+    }
+}
+
+const RANGE_PREFIX_LENGTH: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    // SHA-1 of "password123" is CBFDAC6008F9CAB4083784CBD1874F76618D2A97.
+    async fn range_files_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        tokio::fs::write(
+            dir.join("CBFDA.txt"),
+            "0005AD76BD555C1D6D771DE417A4B87E4B4:10\r\nC6008F9CAB4083784CBD1874F76618D2A97:2427\r\n",
+        )
+        .await
+        .unwrap();
+        dir
+    }
+
+    fn password(s: &str) -> Password {
+        Password::parse(Secret::new(s.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_breached_password_is_found() {
+        let checker = HibpBreachedPasswordChecker::new(range_files_dir().await);
+
+        let result = checker.is_breached(&password("password123")).await;
+
+        assert!(result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_password_with_same_prefix_is_not_found() {
+        let dir = range_files_dir().await;
+        // Same range file, different suffix.
+        tokio::fs::write(
+            dir.join("CBFDA.txt"),
+            "0005AD76BD555C1D6D771DE417A4B87E4B4:10\n",
+        )
+        .await
+        .unwrap();
+        let checker = HibpBreachedPasswordChecker::new(dir);
+
+        let result = checker.is_breached(&password("password123")).await;
+
+        assert!(!result.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_missing_range_file_is_an_error() {
+        let checker = HibpBreachedPasswordChecker::new(range_files_dir().await);
+
+        let result = checker.is_breached(&password("Xk9#mQ2$vL7p")).await;
+
+        assert!(result.is_err());
+    }
+}
\ No newline at end of file
@@ -1,3 +1,8 @@
+pub mod argon2_password_hasher;
 pub mod data_stores;
+pub mod hibp_breached_password_checker;
 pub mod mock_email_client;
-pub mod postmark_email_client;
\ No newline at end of file
+pub mod password_pepper;
+pub mod postmark_email_client;
+pub mod redis_connection;
+pub mod sweeper;
\ No newline at end of file
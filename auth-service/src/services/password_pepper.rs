@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+/// Secret keys mixed into passwords with HMAC-SHA256 before they are hashed.
+///
+/// The keys live outside the database, so a leaked `users` table alone is not
+/// enough to attack the hashes offline. Every key has a version that is stored
+/// next to the hash; the highest version is used for new hashes, older ones are
+/// kept so existing passwords keep verifying until they are rehashed.
+#[derive(Clone, Default)]
+pub struct PasswordPepper {
+    keys: BTreeMap<i32, Secret<String>>,
+}
+
+impl PasswordPepper {
+    pub fn new(keys: BTreeMap<i32, Secret<String>>) -> Self {
+        Self { keys }
+    }
+
+    /// Parses keys in the `<version>:<key>,<version>:<key>` format.
+    pub fn parse(s: &Secret<String>) -> Result<Self> {
+        let mut keys = BTreeMap::new();
+
+        for entry in s
+            .expose_secret()
+            .split(',')
+            .filter(|e| !e.trim().is_empty())
+        {
+            let (version, key) = entry
+                .split_once(':')
+                .ok_or(eyre!("pepper entry must be in the <version>:<key> format"))?;
+            let version: i32 = version
+                .trim()
+                .parse()
+                .wrap_err("pepper version must be a number")?;
+            if key.is_empty() {
+                return Err(eyre!("pepper key for version {} is empty", version));
+            }
+            if keys.insert(version, Secret::new(key.to_owned())).is_some() {
+                return Err(eyre!("pepper version {} is defined twice", version));
+            }
+        }
+
+        Ok(Self { keys })
+    }
+
+    pub fn current_version(&self) -> Option<i32> {
+        self.keys.keys().next_back().copied()
+    }
+
+    /// Whether [`PasswordPepper::apply`] knows `version`.
+    pub fn has_version(&self, version: Option<i32>) -> bool {
+        match version {
+            Some(version) => self.keys.contains_key(&version),
+            None => true,
+        }
+    }
+
+    /// Applies the pepper with the given version. `None` stands for hashes made
+    /// before a pepper was configured and returns the password unchanged.
+    pub fn apply(&self, version: Option<i32>, password: &Secret<String>) -> Result<Secret<String>> {
+        let version = match version {
+            Some(version) => version,
+            None => return Ok(password.clone()),
+        };
+
+        let key = self
+            .keys
+            .get(&version)
+            .ok_or(eyre!("unknown pepper version {}", version))?;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+            .wrap_err("failed to create HMAC")?;
+        mac.update(password.expose_secret().as_bytes());
+
+        Ok(Secret::new(format!("{:x}", mac.finalize().into_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password() -> Secret<String> {
+        Secret::new("password123".to_owned())
+    }
+
+    #[test]
+    fn test_parse() {
+        let pepper =
+            PasswordPepper::parse(&Secret::new("1:old-key, 2:new-key".to_owned())).unwrap();
+        assert_eq!(pepper.current_version(), Some(2));
+
+        let pepper = PasswordPepper::parse(&Secret::new("".to_owned())).unwrap();
+        assert_eq!(pepper.current_version(), None);
+
+        for invalid in ["no-version", "x:key", "1:", "1:a,1:b"] {
+            assert!(
+                PasswordPepper::parse(&Secret::new(invalid.to_owned())).is_err(),
+                "Failed for input: {}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_depends_on_version() {
+        let pepper = PasswordPepper::parse(&Secret::new("1:old-key,2:new-key".to_owned())).unwrap();
+
+        let v1 = pepper.apply(Some(1), &password()).unwrap();
+        let v2 = pepper.apply(Some(2), &password()).unwrap();
+
+        assert_ne!(v1.expose_secret(), v2.expose_secret());
+        assert_ne!(v1.expose_secret(), password().expose_secret());
+        assert_eq!(
+            v1.expose_secret(),
+            pepper.apply(Some(1), &password()).unwrap().expose_secret()
+        );
+    }
+
+    #[test]
+    fn test_apply_without_version_returns_password() {
+        let pepper = PasswordPepper::parse(&Secret::new("1:key".to_owned())).unwrap();
+
+        let result = pepper.apply(None, &password()).unwrap();
+
+        assert_eq!(result.expose_secret(), password().expose_secret());
+    }
+
+    #[test]
+    fn test_apply_with_unknown_version_fails() {
+        let pepper = PasswordPepper::parse(&Secret::new("1:key".to_owned())).unwrap();
+
+        assert!(pepper.apply(Some(2), &password()).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::{sync::Arc, time::Duration};
+
+use redis::{aio::ConnectionManager, Client, RedisResult};
+use tokio::sync::OnceCell;
+
+const CONNECTION_RETRY_EXPONENT_BASE: u64 = 2;
+const CONNECTION_RETRY_FACTOR_MS: u64 = 100;
+const CONNECTION_RETRIES: usize = 3;
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Async Redis connection shared by the Redis stores.
+///
+/// Clones share one multiplexed connection, so concurrent requests do not
+/// wait for each other. It connects on first use, so the app also starts while
+/// Redis is down, and the [`ConnectionManager`] reconnects after failures.
+#[derive(Clone)]
+pub struct RedisConnection {
+    client: Client,
+    manager: Arc<OnceCell<ConnectionManager>>,
+}
+
+impl RedisConnection {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            manager: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// A handle to the shared connection. Fails if Redis cannot be reached,
+    /// in which case the next call tries again.
+    pub async fn get(&self) -> RedisResult<ConnectionManager> {
+        self.manager
+            .get_or_try_init(|| {
+                ConnectionManager::new_with_backoff_and_timeouts(
+                    self.client.clone(),
+                    CONNECTION_RETRY_EXPONENT_BASE,
+                    CONNECTION_RETRY_FACTOR_MS,
+                    CONNECTION_RETRIES,
+                    RESPONSE_TIMEOUT,
+                    CONNECTION_TIMEOUT,
+                )
+            })
+            .await
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_fails_without_redis() {
+        // Nothing listens on port 1
+        let client = Client::open("redis://127.0.0.1:1/").unwrap();
+        let connection = RedisConnection::new(client);
+
+        assert!(connection.get().await.is_err());
+        // Failed attempts are not cached
+        assert!(connection.manager.get().is_none());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::Result;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+/// A store that keeps expired entries until they are deleted.
+#[async_trait::async_trait]
+pub trait ExpiringStore: Send + Sync {
+    /// Deletes the expired entries and returns how many there were.
+    async fn delete_expired(&self) -> Result<u64>;
+}
+
+/// Deletes the expired entries of `store` every `interval`. Lookups ignore
+/// expired entries anyway, this only keeps the store from growing.
+pub fn spawn_sweeper(
+    name: &'static str,
+    store: Arc<dyn ExpiringStore>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            match store.delete_expired().await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::debug!("Deleted {} expired {}", deleted, name),
+                Err(e) => tracing::warn!("Failed to delete expired {}: {:?}", name, e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingStore {
+        sweeps: AtomicU64,
+    }
+
+    #[async_trait::async_trait]
+    impl ExpiringStore for CountingStore {
+        async fn delete_expired(&self) -> Result<u64> {
+            Ok(self.sweeps.fetch_add(1, Ordering::SeqCst))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sweeps_every_interval() {
+        let store = Arc::new(CountingStore::default());
+        let sweeper = spawn_sweeper("entries", store.clone(), Duration::from_secs(60));
+
+        // The first sweep is right away
+        tokio::time::sleep(Duration::from_secs(150)).await;
+        sweeper.abort();
+
+        assert_eq!(store.sweeps.load(Ordering::SeqCst), 3);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use secrecy::ExposeSecret;
+
+use crate::{app_state::AppState, domain::AuthAPIError};
+
+use super::csrf::constant_time_eq;
+
+/// Requires `Authorization: Bearer <ADMIN_API_TOKEN>`, used with
+/// `axum::middleware::from_fn_with_state` on the `/admin` routes.
+#[tracing::instrument(name = "Require admin token", skip_all)]
+pub async fn require_admin_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let Some(token) = token else {
+        return AuthAPIError::MissingToken.into_response();
+    };
+
+    match state.admin_api_token.as_ref() {
+        Some(admin_api_token)
+            if constant_time_eq(token.as_bytes(), admin_api_token.expose_secret().as_bytes()) =>
+        {
+            next.run(request).await
+        }
+        _ => AuthAPIError::InvalidToken.into_response(),
+    }
+}
\ No newline at end of file
@@ -1,39 +1,82 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{AppState, BannedTokenStoreType, UserStoreType},
+    domain::{
+        email::Email, AuthAPIError, AuthMethod, Device, TrustedDevice, User, UserId, UserStoreError,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::constants::{
+    ACCEPT_EMAIL_TOKEN_SUBJECTS, AUTH_COOKIE_DOMAIN, AUTH_COOKIE_SAME_SITE, AUTH_COOKIE_SECURE,
+    JWT_COOKIE_NAME, JWT_SECRET, TRUSTED_DEVICE_COOKIE_NAME,
+};
 
 #[tracing::instrument(name = "Generate auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(user_id: &UserId, amr: &[AuthMethod]) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(user_id, amr)?;
     Ok(create_auth_cookie(token))
 }
 
 #[tracing::instrument(name = "Create auth cookie", skip_all)]
 fn create_auth_cookie(token: Secret<String>) -> Cookie<'static> {
-    let cookie = Cookie::build((JWT_COOKIE_NAME, token.expose_secret().to_owned()))
+    let mut cookie = build_auth_cookie(token.expose_secret().to_owned());
+    cookie.set_max_age(time::Duration::seconds(TOKEN_TTL_SECONDS));
+
+    cookie
+}
+
+/// Cookie that deletes the auth cookie. Browsers only overwrite a cookie with the
+/// same name, domain and path, so it has to carry the attributes it was set with.
+pub fn create_removal_auth_cookie() -> Cookie<'static> {
+    let mut cookie = build_auth_cookie(String::new());
+    cookie.make_removal();
+
+    cookie
+}
+
+fn build_auth_cookie(value: String) -> Cookie<'static> {
+    build_cookie(JWT_COOKIE_NAME.as_str(), value)
+}
+
+fn build_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    let mut cookie = Cookie::build((name, value))
         .path("/")
         .http_only(true)
-        .same_site(SameSite::Lax)
+        .secure(*AUTH_COOKIE_SECURE)
+        .same_site(*AUTH_COOKIE_SAME_SITE)
         .build();
 
+    if let Some(domain) = AUTH_COOKIE_DOMAIN.as_ref() {
+        cookie.set_domain(domain.to_owned());
+    }
+
     cookie
 }
 
 pub const TOKEN_TTL_SECONDS: i64 = 600;
 
 #[tracing::instrument(name = "Generate auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
+fn generate_auth_token(user_id: &UserId, amr: &[AuthMethod]) -> Result<Secret<String>> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .wrap_err("failed to create 10 minute time delta")?;
 
-    let exp = Utc::now()
+    let now = Utc::now();
+    let auth_time: usize = now
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast auth time to usize")?;
+
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
@@ -43,9 +86,13 @@ fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
         exp
     ))?;
 
-    let sub = email.as_ref().expose_secret().to_owned();
-
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp,
+        jti: Uuid::new_v4().to_string(),
+        auth_time,
+        amr: amr.to_vec(),
+    };
 
     create_token(&claims)
 }
@@ -55,7 +102,7 @@ pub async fn validate_token(
     token: &Secret<String>,
     banned_token_store: BannedTokenStoreType,
 ) -> Result<Claims> {
-    match banned_token_store.read().await.contains_token(token).await {
+    match banned_token_store.contains_token(token).await {
         Ok(value) => {
             if value {
                 return Err(eyre!("token is banned"));
@@ -86,55 +133,254 @@ fn create_token(claims: &Claims) -> Result<Secret<String>> {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    /// The [`UserId`]. Tokens issued before users had ids carry the email.
     pub sub: String,
     pub exp: usize,
+    // Makes every token unique. Without it, logging in again within the second
+    // of a logout would return the token that was just banned.
+    #[serde(default)]
+    pub jti: String,
+    /// When the user logged in. Tokens from before these claims existed get 0,
+    /// so they never pass a step-up check.
+    #[serde(default)]
+    pub auth_time: usize,
+    #[serde(default)]
+    pub amr: Vec<AuthMethod>,
+}
+
+/// The user a token was issued to.
+#[tracing::instrument(name = "Get token user", skip_all)]
+pub async fn get_token_user(
+    claims: &Claims,
+    user_store: &UserStoreType,
+) -> Result<User, AuthAPIError> {
+    let user = match UserId::parse(&claims.sub) {
+        Ok(id) => user_store.get_user_by_id(&id).await,
+        // Instances from before user ids issue tokens with email subjects.
+        // ACCEPT_EMAIL_TOKEN_SUBJECTS keeps their sessions working during a
+        // rolling upgrade and is to be unset `TOKEN_TTL_SECONDS` after it.
+        Err(_) if *ACCEPT_EMAIL_TOKEN_SUBJECTS => {
+            match Email::parse(Secret::new(claims.sub.clone())) {
+                Ok(email) => user_store.get_user(&email).await,
+                Err(_) => return Err(AuthAPIError::InvalidToken),
+            }
+        }
+        Err(_) => return Err(AuthAPIError::InvalidToken),
+    };
+
+    user.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    })
+}
+
+/// The user behind a valid auth cookie. Rejects the request with
+/// `MissingToken` or `InvalidToken` otherwise.
+pub struct AuthenticatedUser {
+    pub id: UserId,
+    pub email: Email,
+    /// As loaded with the user, so that routes need not look it up again.
+    pub requires_2fa: bool,
+    pub token: Secret<String>,
+    pub claims: Claims,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let cookie = jar
+            .get(&JWT_COOKIE_NAME)
+            .ok_or(AuthAPIError::MissingToken)?;
+
+        let token = Secret::new(cookie.value().to_owned());
+        let claims = validate_token(&token, state.banned_token_store.clone())
+            .await
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+        let user = get_token_user(&claims, &state.user_store).await?;
+
+        Ok(Self {
+            id: user.id,
+            email: user.email,
+            requires_2fa: user.requires_2fa,
+            token,
+            claims,
+        })
+    }
+}
+
+/// Cookie that lets the browser of `trusted_device` log in as `user_id` without
+/// a 2FA code.
+#[tracing::instrument(name = "Generate trusted device cookie", skip_all)]
+pub fn generate_trusted_device_cookie(
+    user_id: &UserId,
+    trusted_device: &TrustedDevice,
+) -> Result<Cookie<'static>> {
+    let exp: usize = trusted_device
+        .expires_at
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast exp time to usize")?;
+
+    let claims = TrustedDeviceClaims {
+        sub: user_id.to_string(),
+        jti: trusted_device.id.to_string(),
+        uah: user_agent_hash(&trusted_device.device),
+        exp,
+    };
+
+    let token = encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(&derive_key(b"trusted-device")?),
+    )
+    .wrap_err("failed to create trusted device token")?;
+
+    let mut cookie = build_cookie(TRUSTED_DEVICE_COOKIE_NAME.as_str(), token);
+    cookie.set_expires(
+        time::OffsetDateTime::from_unix_timestamp(trusted_device.expires_at.timestamp())
+            .wrap_err("failed to convert trusted device expiry")?,
+    );
+
+    Ok(cookie)
+}
+
+pub fn create_removal_trusted_device_cookie() -> Cookie<'static> {
+    let mut cookie = build_cookie(TRUSTED_DEVICE_COOKIE_NAME.as_str(), String::new());
+    cookie.make_removal();
+
+    cookie
+}
+
+/// Id of the trusted device in the cookie, if it was issued to `user_id` for the
+/// browser that sent the request. The caller still has to check that the device
+/// was not revoked. Cookies that name the user by email predate user ids and
+/// are rejected, so those browsers are asked for a code once more.
+#[tracing::instrument(name = "Validate trusted device token", skip_all)]
+pub fn validate_trusted_device_token(
+    token: &str,
+    user_id: &UserId,
+    device: &Device,
+) -> Result<Uuid> {
+    let claims = decode::<TrustedDeviceClaims>(
+        token,
+        &DecodingKey::from_secret(&derive_key(b"trusted-device")?),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+    .wrap_err("failed to decode trusted device token")?;
+
+    if UserId::parse(&claims.sub).ok().as_ref() != Some(user_id) {
+        return Err(eyre!("trusted device token was issued to another user"));
+    }
+    // A copied cookie only works with the same browser.
+    if claims.uah != user_agent_hash(device) {
+        return Err(eyre!("trusted device token was issued to another browser"));
+    }
+
+    Uuid::parse_str(&claims.jti).wrap_err("invalid trusted device id")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TrustedDeviceClaims {
+    sub: String,
+    jti: String,
+    // Hash of the User-Agent the device was trusted with.
+    uah: String,
+    exp: usize,
+}
+
+/// Signing key for tokens that are not auth tokens, derived from `JWT_SECRET`.
+/// Each `purpose` gets a key of its own, so no other token passes as an auth
+/// token, e.g. a trusted device token that outlives auth tokens by far.
+pub(crate) fn derive_key(purpose: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(JWT_SECRET.expose_secret().as_bytes())
+        .wrap_err("failed to create HMAC")?;
+    mac.update(purpose);
+
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn user_agent_hash(device: &Device) -> String {
+    format!("{:x}", Sha256::digest(device.user_agent.as_bytes()))
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
-    use tokio::sync::RwLock;
 
-    use crate::{domain::BannedTokenStore, services::data_stores::HashsetBannedTokenStore};
+    use axum_extra::extract::cookie::SameSite;
+
+    use crate::{
+        domain::{BannedTokenStore, PasswordHash},
+        services::data_stores::{HashmapUserStore, HashsetBannedTokenStore},
+    };
 
     use super::*;
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
-        assert_eq!(cookie.name(), JWT_COOKIE_NAME);
+        let cookie = generate_auth_cookie(&UserId::default(), &[AuthMethod::Password]).unwrap();
+        assert_eq!(cookie.name(), *JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(TOKEN_TTL_SECONDS))
+        );
     }
 
     #[tokio::test]
     async fn test_create_auth_cookie() {
         let token = Secret::new("test_token".to_owned());
         let cookie = create_auth_cookie(token.clone());
-        assert_eq!(cookie.name(), JWT_COOKIE_NAME);
+        assert_eq!(cookie.name(), *JWT_COOKIE_NAME);
         assert_eq!(cookie.value(), token.expose_secret());
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(
+            cookie.max_age(),
+            Some(time::Duration::seconds(TOKEN_TTL_SECONDS))
+        );
+    }
+
+    #[test]
+    fn test_create_removal_auth_cookie() {
+        let cookie = create_removal_auth_cookie();
+        let auth_cookie = create_auth_cookie(Secret::new("test_token".to_owned()));
+        assert_eq!(cookie.name(), auth_cookie.name());
+        assert_eq!(cookie.value(), "");
+        assert_eq!(cookie.path(), auth_cookie.path());
+        assert_eq!(cookie.domain(), auth_cookie.domain());
+        assert_eq!(cookie.secure(), auth_cookie.secure());
+        assert_eq!(cookie.same_site(), auth_cookie.same_site());
+        assert_eq!(cookie.max_age(), Some(time::Duration::ZERO));
     }
 
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&UserId::default(), &[AuthMethod::Password]).unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let user_id = UserId::default();
+        let amr = [AuthMethod::Password, AuthMethod::OneTimePassword];
+        let token = generate_auth_token(&user_id, &amr).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
         let result = validate_token(&token, banned_token_store).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.amr, amr);
+        assert!(result.auth_time as i64 >= Utc::now().timestamp() - 1);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -144,22 +390,111 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[test]
+    fn test_generate_auth_token_is_unique() {
+        let user_id = UserId::default();
+        let first = generate_auth_token(&user_id, &[AuthMethod::Password]).unwrap();
+        let second = generate_auth_token(&user_id, &[AuthMethod::Password]).unwrap();
+        assert_ne!(first.expose_secret(), second.expose_secret());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Secret::new("invalid_token".to_owned());
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
         let result = validate_token(&token, banned_token_store).await;
         assert!(result.is_err());
     }
 
+    fn trusted_device() -> TrustedDevice {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let device = Device::new("127.0.0.1".to_owned(), "curl/8.5.0".to_owned());
+        TrustedDevice::new(email, device)
+    }
+
+    #[test]
+    fn test_validate_trusted_device_token() {
+        let user_id = UserId::default();
+        let trusted_device = trusted_device();
+        let cookie = generate_trusted_device_cookie(&user_id, &trusted_device).unwrap();
+        assert_eq!(cookie.name(), *TRUSTED_DEVICE_COOKIE_NAME);
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let id = validate_trusted_device_token(cookie.value(), &user_id, &trusted_device.device)
+            .unwrap();
+        assert_eq!(id, trusted_device.id);
+
+        assert!(validate_trusted_device_token(
+            cookie.value(),
+            &UserId::default(),
+            &trusted_device.device
+        )
+        .is_err());
+
+        // The IP address may change, the browser may not
+        let other_ip = Device::new("10.0.0.1".to_owned(), "curl/8.5.0".to_owned());
+        assert!(validate_trusted_device_token(cookie.value(), &user_id, &other_ip).is_ok());
+        let other_browser = Device::new("127.0.0.1".to_owned(), "curl/8.6.0".to_owned());
+        assert!(validate_trusted_device_token(cookie.value(), &user_id, &other_browser).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trusted_device_token_is_not_an_auth_token() {
+        let user_id = UserId::default();
+        let trusted_device = trusted_device();
+        let cookie = generate_trusted_device_cookie(&user_id, &trusted_device).unwrap();
+        let token = Secret::new(cookie.value().to_owned());
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        assert!(validate_token(&token, banned_token_store).await.is_err());
+
+        let auth_token = generate_auth_token(&user_id, &[AuthMethod::Password]).unwrap();
+        assert!(validate_trusted_device_token(
+            auth_token.expose_secret(),
+            &user_id,
+            &trusted_device.device
+        )
+        .is_err());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let mut hs = HashsetBannedTokenStore::default();
+        let token = generate_auth_token(&UserId::default(), &[AuthMethod::Password]).unwrap();
+        let hs = HashsetBannedTokenStore::default();
         hs.add_token(token.clone()).await.unwrap();
-        let banned_token_store = Arc::new(RwLock::new(hs));
+        let banned_token_store = Arc::new(hs);
         let result = validate_token(&token, banned_token_store).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_get_token_user() {
+        let user_store: UserStoreType = Arc::new(HashmapUserStore::default());
+        let user = User::new(
+            Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            PasswordHash::new(Secret::new("password-hash".to_owned()), None),
+            false,
+        );
+        user_store.add_user(user.clone()).await.unwrap();
+
+        let claims = |sub: String| Claims {
+            sub,
+            exp: 0,
+            jti: String::new(),
+            auth_time: 0,
+            amr: vec![],
+        };
+
+        let result = get_token_user(&claims(user.id.to_string()), &user_store).await;
+        assert_eq!(result.unwrap(), user);
+
+        // Tokens from before user ids, unless ACCEPT_EMAIL_TOKEN_SUBJECTS is set
+        let result = get_token_user(&claims("test@example.com".to_owned()), &user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+
+        let result = get_token_user(&claims(UserId::default().to_string()), &user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+        let result = get_token_user(&claims("not a user".to_owned()), &user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
 }
\ No newline at end of file
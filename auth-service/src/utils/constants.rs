@@ -1,3 +1,4 @@
+use axum_extra::extract::cookie::SameSite;
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use secrecy::Secret;
@@ -5,9 +6,41 @@ use std::env as std_env;
 
 lazy_static! {
     pub static ref JWT_SECRET: Secret<String> = set_token();
+    pub static ref ACCEPT_EMAIL_TOKEN_SUBJECTS: bool = set_accept_email_token_subjects();
     pub static ref DATABASE_URL: Secret<String> = set_db_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref TOKEN_STORE: TokenStore = set_token_store();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref SIGNUP_HIDE_EXISTING_USERS: bool = set_signup_hide_existing_users();
+    pub static ref PASSWORD_MIN_LENGTH: usize = set_password_min_length();
+    pub static ref PASSWORD_MAX_LENGTH: usize = set_password_max_length();
+    pub static ref PASSWORD_MIN_STRENGTH_SCORE: u8 = set_password_min_strength_score();
+    pub static ref BREACHED_PASSWORDS_DIR: Option<String> = set_breached_passwords_dir();
+    pub static ref ARGON2_MEMORY_COST_KIB: u32 = set_argon2_memory_cost_kib();
+    pub static ref ARGON2_TIME_COST: u32 = set_argon2_time_cost();
+    pub static ref ARGON2_PARALLELISM: u32 = set_argon2_parallelism();
+    pub static ref PASSWORD_PEPPERS: Option<Secret<String>> = set_password_peppers();
+    pub static ref AUTH_COOKIE_SECURE: bool = set_auth_cookie_secure();
+    pub static ref AUTH_COOKIE_DOMAIN: Option<String> = set_auth_cookie_domain();
+    pub static ref AUTH_COOKIE_SAME_SITE: SameSite = set_auth_cookie_same_site();
+    pub static ref AUTH_COOKIE_HOST_PREFIX: bool = set_auth_cookie_host_prefix();
+    pub static ref JWT_COOKIE_NAME: String = set_cookie_name(DEFAULT_JWT_COOKIE_NAME);
+    pub static ref TRUSTED_DEVICE_COOKIE_NAME: String =
+        set_cookie_name(DEFAULT_TRUSTED_DEVICE_COOKIE_NAME);
+    pub static ref CSRF_COOKIE_NAME: String = set_cookie_name(DEFAULT_CSRF_COOKIE_NAME);
+    pub static ref HSTS_MAX_AGE_SECONDS: u64 = set_hsts_max_age_seconds();
+    pub static ref UI_CONTENT_SECURITY_POLICY: String = set_ui_content_security_policy();
+    pub static ref AUTH_SERVICE_PUBLIC_URL: String = set_auth_service_public_url();
+    pub static ref CORS_ALLOWED_ORIGINS: Option<String> =
+        set_optional_env_var(env::CORS_ALLOWED_ORIGINS_ENV_VAR);
+    pub static ref CORS_ALLOWED_METHODS: Option<String> =
+        set_optional_env_var(env::CORS_ALLOWED_METHODS_ENV_VAR);
+    pub static ref CORS_ALLOWED_HEADERS: Option<String> =
+        set_optional_env_var(env::CORS_ALLOWED_HEADERS_ENV_VAR);
+    pub static ref IP_FILTER_CONFIG: Option<String> =
+        set_optional_env_var(env::IP_FILTER_CONFIG_ENV_VAR);
+    pub static ref ADMIN_API_TOKEN: Option<Secret<String>> =
+        set_optional_env_var(env::ADMIN_API_TOKEN_ENV_VAR).map(Secret::new);
 }
 
 fn set_token() -> Secret<String> {
@@ -29,6 +62,24 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_accept_email_token_subjects() -> bool {
+    dotenv().ok();
+    std_env::var(env::ACCEPT_EMAIL_TOKEN_SUBJECTS_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+fn set_token_store() -> TokenStore {
+    dotenv().ok();
+    std_env::var(env::TOKEN_STORE_ENV_VAR)
+        .map(|value| match value.to_lowercase().as_str() {
+            "redis" => TokenStore::Redis,
+            "postgres" => TokenStore::Postgres,
+            _ => panic!("TOKEN_STORE must be one of redis or postgres."),
+        })
+        .unwrap_or(TokenStore::Redis)
+}
+
 fn set_postmark_auth_token() -> Secret<String> {
     dotenv().ok();
     Secret::new(
@@ -36,18 +87,242 @@ fn set_postmark_auth_token() -> Secret<String> {
     )
 }
 
+fn set_signup_hide_existing_users() -> bool {
+    dotenv().ok();
+    std_env::var(env::SIGNUP_HIDE_EXISTING_USERS_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+fn set_password_min_length() -> usize {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_MIN_LENGTH_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .expect("PASSWORD_MIN_LENGTH must be a number.")
+        })
+        .unwrap_or(DEFAULT_PASSWORD_MIN_LENGTH)
+}
+
+fn set_password_max_length() -> usize {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_MAX_LENGTH_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .expect("PASSWORD_MAX_LENGTH must be a number.")
+        })
+        .unwrap_or(DEFAULT_PASSWORD_MAX_LENGTH)
+}
+
+fn set_password_min_strength_score() -> u8 {
+    dotenv().ok();
+    let score = std_env::var(env::PASSWORD_MIN_STRENGTH_SCORE_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .expect("PASSWORD_MIN_STRENGTH_SCORE must be a number.")
+        })
+        .unwrap_or(DEFAULT_PASSWORD_MIN_STRENGTH_SCORE);
+    if score > 4 {
+        panic!("PASSWORD_MIN_STRENGTH_SCORE must be between 0 and 4.");
+    }
+    score
+}
+
+fn set_breached_passwords_dir() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::BREACHED_PASSWORDS_DIR_ENV_VAR)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+}
+
+fn set_argon2_memory_cost_kib() -> u32 {
+    dotenv().ok();
+    std_env::var(env::ARGON2_MEMORY_COST_KIB_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .expect("ARGON2_MEMORY_COST_KIB must be a number.")
+        })
+        .unwrap_or(DEFAULT_ARGON2_MEMORY_COST_KIB)
+}
+
+fn set_argon2_time_cost() -> u32 {
+    dotenv().ok();
+    std_env::var(env::ARGON2_TIME_COST_ENV_VAR)
+        .map(|value| value.parse().expect("ARGON2_TIME_COST must be a number."))
+        .unwrap_or(DEFAULT_ARGON2_TIME_COST)
+}
+
+fn set_argon2_parallelism() -> u32 {
+    dotenv().ok();
+    std_env::var(env::ARGON2_PARALLELISM_ENV_VAR)
+        .map(|value| value.parse().expect("ARGON2_PARALLELISM must be a number."))
+        .unwrap_or(DEFAULT_ARGON2_PARALLELISM)
+}
+
+fn set_password_peppers() -> Option<Secret<String>> {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_PEPPERS_ENV_VAR)
+        .ok()
+        .filter(|peppers| !peppers.is_empty())
+        .map(Secret::new)
+}
+
+fn set_auth_cookie_secure() -> bool {
+    dotenv().ok();
+    std_env::var(env::AUTH_COOKIE_SECURE_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+fn set_auth_cookie_domain() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::AUTH_COOKIE_DOMAIN_ENV_VAR)
+        .ok()
+        .filter(|domain| !domain.is_empty())
+}
+
+fn set_auth_cookie_same_site() -> SameSite {
+    dotenv().ok();
+    let same_site = std_env::var(env::AUTH_COOKIE_SAME_SITE_ENV_VAR)
+        .map(|value| match value.to_lowercase().as_str() {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            _ => panic!("AUTH_COOKIE_SAME_SITE must be one of strict, lax or none."),
+        })
+        .unwrap_or(SameSite::Lax);
+    // Browsers reject SameSite=None cookies that are not Secure.
+    if same_site == SameSite::None && !*AUTH_COOKIE_SECURE {
+        panic!("AUTH_COOKIE_SAME_SITE=none requires AUTH_COOKIE_SECURE=true.");
+    }
+    same_site
+}
+
+fn set_auth_cookie_host_prefix() -> bool {
+    dotenv().ok();
+    std_env::var(env::AUTH_COOKIE_HOST_PREFIX_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+fn set_cookie_name(name: &str) -> String {
+    if !*AUTH_COOKIE_HOST_PREFIX {
+        return name.to_owned();
+    }
+    // Browsers only accept __Host- cookies that are Secure, have Path=/ and no
+    // Domain, which pins the cookie to the exact host that set it.
+    if !*AUTH_COOKIE_SECURE || AUTH_COOKIE_DOMAIN.is_some() {
+        panic!("AUTH_COOKIE_HOST_PREFIX=true requires AUTH_COOKIE_SECURE=true and no AUTH_COOKIE_DOMAIN.");
+    }
+    format!("__Host-{}", name)
+}
+
+fn set_hsts_max_age_seconds() -> u64 {
+    dotenv().ok();
+    std_env::var(env::HSTS_MAX_AGE_SECONDS_ENV_VAR)
+        .map(|value| {
+            value
+                .parse()
+                .expect("HSTS_MAX_AGE_SECONDS must be a number.")
+        })
+        .unwrap_or(DEFAULT_HSTS_MAX_AGE_SECONDS)
+}
+
+fn set_ui_content_security_policy() -> String {
+    dotenv().ok();
+    std_env::var(env::UI_CONTENT_SECURITY_POLICY_ENV_VAR)
+        .ok()
+        .filter(|policy| !policy.is_empty())
+        .unwrap_or(DEFAULT_UI_CONTENT_SECURITY_POLICY.to_owned())
+}
+
+fn set_auth_service_public_url() -> String {
+    set_optional_env_var(env::AUTH_SERVICE_PUBLIC_URL_ENV_VAR)
+        .map(|url| url.trim_end_matches('/').to_owned())
+        .unwrap_or(DEFAULT_AUTH_SERVICE_PUBLIC_URL.to_owned())
+}
+
+fn set_optional_env_var(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
 pub mod env {
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const ACCEPT_EMAIL_TOKEN_SUBJECTS_ENV_VAR: &str = "ACCEPT_EMAIL_TOKEN_SUBJECTS";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const TOKEN_STORE_ENV_VAR: &str = "TOKEN_STORE";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    pub const SIGNUP_HIDE_EXISTING_USERS_ENV_VAR: &str = "SIGNUP_HIDE_EXISTING_USERS";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_MIN_STRENGTH_SCORE_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH_SCORE";
+    pub const BREACHED_PASSWORDS_DIR_ENV_VAR: &str = "BREACHED_PASSWORDS_DIR";
+    pub const ARGON2_MEMORY_COST_KIB_ENV_VAR: &str = "ARGON2_MEMORY_COST_KIB";
+    pub const ARGON2_TIME_COST_ENV_VAR: &str = "ARGON2_TIME_COST";
+    pub const ARGON2_PARALLELISM_ENV_VAR: &str = "ARGON2_PARALLELISM";
+    pub const PASSWORD_PEPPERS_ENV_VAR: &str = "PASSWORD_PEPPERS";
+    pub const AUTH_COOKIE_SECURE_ENV_VAR: &str = "AUTH_COOKIE_SECURE";
+    pub const AUTH_COOKIE_DOMAIN_ENV_VAR: &str = "AUTH_COOKIE_DOMAIN";
+    pub const AUTH_COOKIE_SAME_SITE_ENV_VAR: &str = "AUTH_COOKIE_SAME_SITE";
+    pub const AUTH_COOKIE_HOST_PREFIX_ENV_VAR: &str = "AUTH_COOKIE_HOST_PREFIX";
+    pub const HSTS_MAX_AGE_SECONDS_ENV_VAR: &str = "HSTS_MAX_AGE_SECONDS";
+    pub const UI_CONTENT_SECURITY_POLICY_ENV_VAR: &str = "UI_CONTENT_SECURITY_POLICY";
+    pub const AUTH_SERVICE_PUBLIC_URL_ENV_VAR: &str = "AUTH_SERVICE_PUBLIC_URL";
+    pub const CORS_ALLOWED_ORIGINS_ENV_VAR: &str = "CORS_ALLOWED_ORIGINS";
+    pub const CORS_ALLOWED_METHODS_ENV_VAR: &str = "CORS_ALLOWED_METHODS";
+    pub const CORS_ALLOWED_HEADERS_ENV_VAR: &str = "CORS_ALLOWED_HEADERS";
+    pub const IP_FILTER_CONFIG_ENV_VAR: &str = "IP_FILTER_CONFIG";
+    pub const ADMIN_API_TOKEN_ENV_VAR: &str = "ADMIN_API_TOKEN";
+}
+
+/// Where banned auth tokens and pending 2FA codes are kept, next to the
+/// Postgres database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenStore {
+    Redis,
+    Postgres,
 }
 
-pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const DEFAULT_JWT_COOKIE_NAME: &str = "jwt";
+pub const DEFAULT_TRUSTED_DEVICE_COOKIE_NAME: &str = "trusted_device";
+pub const DEFAULT_CSRF_COOKIE_NAME: &str = "csrf_token";
+pub const CSRF_HEADER_NAME: &str = "x-csrf-token";
+pub const PROOF_OF_WORK_HEADER_NAME: &str = "x-proof-of-work";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_PASSWORD_MIN_LENGTH: usize = 8;
+pub const DEFAULT_PASSWORD_MAX_LENGTH: usize = 128;
+pub const DEFAULT_PASSWORD_MIN_STRENGTH_SCORE: u8 = 2;
+pub const DEFAULT_ARGON2_MEMORY_COST_KIB: u32 = 15000;
+pub const DEFAULT_ARGON2_TIME_COST: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+pub const DEFAULT_HSTS_MAX_AGE_SECONDS: u64 = 31_536_000;
+// Base of links in emails.
+pub const DEFAULT_AUTH_SERVICE_PUBLIC_URL: &str = "http://localhost:3000";
+// The auth UI loads Bootstrap from jsDelivr and uses inline style attributes.
+pub const DEFAULT_UI_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; \
+    script-src 'self' https://cdn.jsdelivr.net; \
+    style-src 'self' 'unsafe-inline' https://cdn.jsdelivr.net; \
+    img-src 'self' data:; connect-src 'self'; \
+    frame-ancestors 'none'; base-uri 'self'; form-action 'self'";
 
 pub mod prod {
+    use std::time::Duration;
+
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
+    // How often expired rows are deleted from the Postgres token stores.
+    pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+    // Defaults when the CORS_ALLOWED_* variables are not set.
+    pub mod cors {
+        pub const ALLOWED_ORIGINS: &str = "http://localhost:8000";
+        pub const ALLOWED_METHODS: &str = "GET,POST,DELETE";
+        pub const ALLOWED_HEADERS: &str = "content-type,x-csrf-token,x-proof-of-work";
+    }
     pub mod email_client {
         use std::time::Duration;
 
@@ -59,6 +334,11 @@ pub mod prod {
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub mod cors {
+        pub const ALLOWED_ORIGINS: &str = "http://localhost:8000,https://*.example.com";
+        pub const ALLOWED_METHODS: &str = "GET,POST,DELETE";
+        pub const ALLOWED_HEADERS: &str = "content-type,x-csrf-token,x-proof-of-work";
+    }
     pub mod email_client {
         use std::time::Duration;
 
@@ -0,0 +1,234 @@
+use std::{net::Ipv6Addr, str::FromStr};
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use color_eyre::eyre::{eyre, Context, Result};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Origins, methods and headers other sites may use to call the API with the
+/// user's cookies.
+///
+/// Origins are `scheme://host[:port]`. A host of the form `*.example.com`
+/// allows every subdomain of `example.com`, but not `example.com` itself.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    allowed_origins: Vec<AllowedOrigin>,
+    allowed_methods: Vec<Method>,
+    allowed_headers: Vec<HeaderName>,
+}
+
+impl CorsConfig {
+    /// Parses comma-separated lists, failing on the first malformed entry.
+    pub fn parse(origins: &str, methods: &str, headers: &str) -> Result<Self> {
+        let allowed_origins = split_list(origins)
+            .map(AllowedOrigin::parse)
+            .collect::<Result<_>>()?;
+        let allowed_methods = split_list(methods)
+            .map(|method| {
+                Method::from_str(&method.to_uppercase())
+                    .wrap_err(format!("invalid CORS method: {:?}", method))
+            })
+            .collect::<Result<_>>()?;
+        let allowed_headers = split_list(headers)
+            .map(|header| {
+                HeaderName::from_str(header).wrap_err(format!("invalid CORS header: {:?}", header))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            allowed_origins,
+            allowed_methods,
+            allowed_headers,
+        })
+    }
+
+    pub fn is_allowed_origin(&self, origin: &HeaderValue) -> bool {
+        let origin = match origin.to_str() {
+            Ok(origin) => origin.to_lowercase(),
+            Err(_) => return false,
+        };
+
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed.matches(&origin))
+    }
+
+    pub fn layer(self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_methods(self.allowed_methods.clone())
+            .allow_headers(self.allowed_headers.clone())
+            .allow_credentials(true)
+            .allow_origin(AllowOrigin::predicate(move |origin, _| {
+                self.is_allowed_origin(origin)
+            }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum AllowedOrigin {
+    Exact(String),
+    // The origin's scheme, the domain after "*." and the port part, if any.
+    WildcardSubdomain {
+        scheme: String,
+        domain: String,
+        port: String,
+    },
+}
+
+impl AllowedOrigin {
+    fn parse(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| eyre!("invalid CORS origin {:?}: {}", s, reason);
+
+        let origin = s.to_lowercase();
+        let (scheme, authority) = origin
+            .split_once("://")
+            .ok_or_else(|| invalid("expected scheme://host[:port]"))?;
+
+        if scheme != "http" && scheme != "https" {
+            return Err(invalid("scheme must be http or https"));
+        }
+        if authority.contains(['/', '?', '#', '@']) {
+            return Err(invalid(
+                "origins must not contain a path, query or credentials",
+            ));
+        }
+
+        // An IPv6 host contains colons itself, so the port starts after its "]".
+        let host_end = match authority.find(']') {
+            Some(end) => end + 1,
+            None => authority.rfind(':').unwrap_or(authority.len()),
+        };
+        let (host, port) = authority.split_at(host_end);
+
+        if !port.is_empty()
+            && port
+                .strip_prefix(':')
+                .and_then(|port| port.parse::<u16>().ok())
+                .is_none()
+        {
+            return Err(invalid("port must be a number"));
+        }
+
+        let (wildcard, domain) = match host.strip_prefix("*.") {
+            Some(domain) => (true, domain),
+            None => (false, host),
+        };
+
+        if !is_valid_host(domain) {
+            return Err(invalid("host is empty or contains invalid characters"));
+        }
+
+        if wildcard {
+            Ok(Self::WildcardSubdomain {
+                scheme: scheme.to_owned(),
+                domain: domain.to_owned(),
+                port: port.to_owned(),
+            })
+        } else {
+            Ok(Self::Exact(origin))
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Exact(allowed) => allowed == origin,
+            Self::WildcardSubdomain {
+                scheme,
+                domain,
+                port,
+            } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|rest| rest.strip_suffix(port.as_str()))
+                .and_then(|host| host.strip_suffix(domain.as_str()))
+                .and_then(|subdomain| subdomain.strip_suffix('.'))
+                .is_some_and(is_valid_host),
+        }
+    }
+}
+
+fn is_valid_host(host: &str) -> bool {
+    let is_ipv6 = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .is_some_and(|address| address.parse::<Ipv6Addr>().is_ok());
+
+    is_ipv6
+        || (!host.is_empty()
+            && host.split('.').all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            }))
+}
+
+fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(origins: &str) -> CorsConfig {
+        CorsConfig::parse(origins, "GET,POST", "content-type").unwrap()
+    }
+
+    fn is_allowed(config: &CorsConfig, origin: &str) -> bool {
+        config.is_allowed_origin(&HeaderValue::from_str(origin).unwrap())
+    }
+
+    #[test]
+    fn test_exact_origins() {
+        let config = config("http://localhost:8000, https://App.example.com, http://[::1]:8000");
+
+        assert!(is_allowed(&config, "http://localhost:8000"));
+        assert!(is_allowed(&config, "https://app.example.com"));
+        assert!(is_allowed(&config, "http://[::1]:8000"));
+        assert!(!is_allowed(&config, "http://localhost:3000"));
+        assert!(!is_allowed(&config, "http://app.example.com"));
+        assert!(!is_allowed(&config, "https://evil.app.example.com"));
+    }
+
+    #[test]
+    fn test_wildcard_subdomains() {
+        let config = config("https://*.example.com,http://*.localhost:8000");
+
+        assert!(is_allowed(&config, "https://app.example.com"));
+        assert!(is_allowed(&config, "https://a.b.example.com"));
+        assert!(is_allowed(&config, "http://app.localhost:8000"));
+        assert!(!is_allowed(&config, "https://example.com"));
+        assert!(!is_allowed(&config, "https://evilexample.com"));
+        assert!(!is_allowed(&config, "https://app.example.com.evil.com"));
+        assert!(!is_allowed(&config, "http://app.example.com"));
+        assert!(!is_allowed(&config, "https://app.example.com:8443"));
+        assert!(!is_allowed(&config, "http://app.localhost:9000"));
+    }
+
+    #[test]
+    fn test_malformed_origins_are_rejected() {
+        for origin in [
+            "localhost:8000",
+            "http://[YOUR_DROPLET_IP]:8000",
+            "ftp://example.com",
+            "http://example.com/",
+            "http://:8000",
+            "http://example.com:port",
+            "http://*",
+            "*",
+            "http://user@example.com",
+        ] {
+            assert!(
+                CorsConfig::parse(origin, "GET", "content-type").is_err(),
+                "Failed for origin: {}",
+                origin
+            );
+        }
+    }
+
+    #[test]
+    fn test_methods_and_headers() {
+        assert!(CorsConfig::parse("http://localhost", "get, post", "x-csrf-token").is_ok());
+        assert!(CorsConfig::parse("http://localhost", "GET POST", "content-type").is_err());
+        assert!(CorsConfig::parse("http://localhost", "GET", "bad header").is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+use axum::{
+    extract::Request,
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use rand::Rng;
+
+use crate::domain::AuthAPIError;
+
+use super::constants::{
+    AUTH_COOKIE_DOMAIN, AUTH_COOKIE_SECURE, CSRF_COOKIE_NAME, CSRF_HEADER_NAME, JWT_COOKIE_NAME,
+};
+
+/// Double-submit CSRF protection, used with `axum::middleware::from_fn`.
+///
+/// Every response to a client without a CSRF cookie sets one. The cookie is
+/// readable from JavaScript, so the UI can copy it into the `X-CSRF-Token`
+/// header. State-changing requests that carry the auth cookie are rejected
+/// unless the header matches the cookie: another site can make the browser send
+/// our cookies, but it cannot read them.
+#[tracing::instrument(name = "CSRF protection", skip_all)]
+pub async fn csrf_protection(jar: CookieJar, request: Request, next: Next) -> Response {
+    let cookie_token = jar.get(&CSRF_COOKIE_NAME).map(|c| c.value().to_owned());
+
+    let response = if requires_csrf_token(&request, &jar)
+        && !is_valid_csrf_token(&request, cookie_token.as_deref())
+    {
+        AuthAPIError::InvalidCsrfToken.into_response()
+    } else {
+        next.run(request).await
+    };
+
+    match cookie_token {
+        Some(_) => response,
+        None => (jar.add(create_csrf_cookie()), response).into_response(),
+    }
+}
+
+/// Rejects requests without a valid CSRF token even when they carry no auth
+/// cookie. For routes that act on a token in the request instead of the
+/// session, used with `axum::middleware::from_fn` under [`csrf_protection`].
+#[tracing::instrument(name = "Requiring CSRF token", skip_all)]
+pub async fn require_csrf_token(jar: CookieJar, request: Request, next: Next) -> Response {
+    let cookie_token = jar.get(&CSRF_COOKIE_NAME).map(|c| c.value().to_owned());
+
+    match is_valid_csrf_token(&request, cookie_token.as_deref()) {
+        true => next.run(request).await,
+        false => AuthAPIError::InvalidCsrfToken.into_response(),
+    }
+}
+
+// Safe methods must not change state, and requests without the auth cookie
+// cannot act on behalf of a user, so neither needs a token.
+fn requires_csrf_token(request: &Request, jar: &CookieJar) -> bool {
+    let is_safe_method = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+
+    !is_safe_method && jar.get(&JWT_COOKIE_NAME).is_some()
+}
+
+fn is_valid_csrf_token(request: &Request, cookie_token: Option<&str>) -> bool {
+    let header_token = request
+        .headers()
+        .get(CSRF_HEADER_NAME)
+        .and_then(|value| value.to_str().ok());
+
+    match (header_token, cookie_token) {
+        (Some(header_token), Some(cookie_token)) if !cookie_token.is_empty() => {
+            constant_time_eq(header_token.as_bytes(), cookie_token.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn create_csrf_cookie() -> Cookie<'static> {
+    let token: String = rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    // Shares the auth cookie's scope and name prefix, so every page that can use
+    // the session can also read the token.
+    let mut cookie = Cookie::build((CSRF_COOKIE_NAME.as_str(), token))
+        .path("/")
+        .http_only(false)
+        .secure(*AUTH_COOKIE_SECURE)
+        .same_site(SameSite::Strict)
+        .build();
+
+    if let Some(domain) = AUTH_COOKIE_DOMAIN.as_ref() {
+        cookie.set_domain(domain.to_owned());
+    }
+
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokeN"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(!constant_time_eq(b"", b"token"));
+    }
+
+    #[test]
+    fn test_csrf_cookie_is_readable_by_scripts() {
+        let cookie = create_csrf_cookie();
+
+        assert_eq!(cookie.value().len(), 64);
+        assert_eq!(cookie.http_only(), Some(false));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_ne!(cookie.value(), create_csrf_cookie().value());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,44 @@
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, Extensions},
+};
+
+use crate::domain::Device;
+
+use super::ip_filter::ClientIp;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Device
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = client_ip_address(&parts.extensions);
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        Ok(Device::new(ip_address, user_agent))
+    }
+}
+
+/// Address of the client that sent the request with these extensions.
+pub(crate) fn client_ip_address(extensions: &Extensions) -> String {
+    // The IP filter resolves the client address behind trusted proxies.
+    match extensions.get::<ClientIp>() {
+        Some(ClientIp(ip)) => ip.to_string(),
+        None => extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_owned()),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,405 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use color_eyre::eyre::{eyre, Context, Result};
+use ipnet::IpNet;
+use serde::Deserialize;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::RwLock,
+};
+
+use crate::domain::AuthAPIError;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Routes that share allow and deny lists. Every request is checked against the
+/// `default` rules, and admin and introspection routes against their own rules
+/// as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteGroup {
+    Default,
+    Admin,
+    Introspection,
+}
+
+/// The address a request came from, after looking through trusted proxies.
+/// Added to the request extensions by [`filter_ip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Trusted proxies and per route group CIDR lists, loaded from JSON:
+///
+/// ```json
+/// {
+///     "trustedProxies": ["10.0.0.0/8"],
+///     "groups": {
+///         "default": { "deny": ["203.0.113.0/24"] },
+///         "introspection": { "allow": ["10.0.0.0/8", "127.0.0.1"] }
+///     }
+/// }
+/// ```
+///
+/// A plain address is a range of one. Deny wins over allow, and an empty allow
+/// list allows everything that is not denied.
+#[derive(Debug, Default, Clone)]
+pub struct IpFilterRules {
+    trusted_proxies: Vec<IpNet>,
+    groups: HashMap<RouteGroup, CidrRules>,
+}
+
+impl IpFilterRules {
+    pub fn parse(json: &str) -> Result<Self> {
+        let file: IpFilterFile =
+            serde_json::from_str(json).wrap_err("invalid IP filter configuration")?;
+
+        let groups = file
+            .groups
+            .into_iter()
+            .map(|(group, rules)| Ok((group, CidrRules::parse(rules)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            trusted_proxies: parse_ranges(&file.trusted_proxies)?,
+            groups,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json =
+            std::fs::read_to_string(path).wrap_err(format!("failed to read {}", path.display()))?;
+
+        Self::parse(&json).wrap_err(format!("failed to load {}", path.display()))
+    }
+
+    pub fn is_allowed(&self, group: RouteGroup, ip: IpAddr) -> bool {
+        match self.groups.get(&group) {
+            Some(rules) => rules.is_allowed(ip.to_canonical()),
+            None => true,
+        }
+    }
+
+    /// Resolves the client address of a request from `peer`.
+    ///
+    /// `X-Forwarded-For` is only believed when it was sent by a trusted proxy.
+    /// Each proxy appends the address it got the request from, so the list is
+    /// walked from the right and the first hop that is not a trusted proxy is
+    /// the client. Anything further left could have been made up by the client.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+
+        if !self.is_trusted_proxy(client) {
+            return client;
+        }
+
+        let hops = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>();
+
+        for hop in hops.into_iter().rev() {
+            // A hop we cannot read ends the trusted part of the chain.
+            let Some(ip) = parse_forwarded_ip(hop) else {
+                break;
+            };
+
+            client = ip;
+
+            if !self.is_trusted_proxy(ip) {
+                break;
+            }
+        }
+
+        client
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|range| range.contains(&ip))
+    }
+}
+
+/// Rules shared by the `filter_ip` middleware of every route group. Cloning
+/// shares the rules, so a reload applies to all of them at once.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    rules: Arc<RwLock<IpFilterRules>>,
+}
+
+impl IpFilter {
+    pub fn new(rules: IpFilterRules) -> Self {
+        Self {
+            rules: Arc::new(RwLock::new(rules)),
+        }
+    }
+
+    pub async fn reload(&self, rules: IpFilterRules) {
+        *self.rules.write().await = rules;
+    }
+
+    /// State for [`filter_ip`] on the routes of `group`.
+    pub fn group(&self, group: RouteGroup) -> IpFilterGroup {
+        IpFilterGroup {
+            filter: self.clone(),
+            group,
+        }
+    }
+
+    /// Reloads the rules from `path` whenever the process receives SIGHUP. If the
+    /// file cannot be loaded, the error is logged and the current rules stay.
+    pub fn reload_on_hangup(&self, path: PathBuf) -> Result<()> {
+        let mut hangup = signal(SignalKind::hangup()).wrap_err("failed to listen for SIGHUP")?;
+        let filter = self.clone();
+
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match IpFilterRules::load(&path) {
+                    Ok(rules) => {
+                        filter.reload(rules).await;
+                        tracing::info!("Reloaded IP filter rules from {}", path.display());
+                    }
+                    Err(e) => tracing::error!("Failed to reload IP filter rules: {:?}", e),
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IpFilterGroup {
+    filter: IpFilter,
+    group: RouteGroup,
+}
+
+/// Rejects requests from addresses the rules of a route group do not allow,
+/// used with `axum::middleware::from_fn_with_state`.
+#[tracing::instrument(name = "IP filter", skip_all)]
+pub async fn filter_ip(
+    State(state): State<IpFilterGroup>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    {
+        let rules = state.filter.rules.read().await;
+
+        let client_ip = match request.extensions().get::<ClientIp>() {
+            Some(client_ip) => *client_ip,
+            None => match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+                Some(ConnectInfo(peer)) => ClientIp(rules.client_ip(peer.ip(), request.headers())),
+                None => {
+                    return AuthAPIError::UnexpectedError(eyre!("missing connection info"))
+                        .into_response()
+                }
+            },
+        };
+
+        if !rules.is_allowed(state.group, client_ip.0) {
+            tracing::warn!("Rejected request from {}", client_ip.0);
+            return AuthAPIError::IpAddressNotAllowed.into_response();
+        }
+
+        request.extensions_mut().insert(client_ip);
+    }
+
+    next.run(request).await
+}
+
+#[derive(Debug, Default, Clone)]
+struct CidrRules {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl CidrRules {
+    fn parse(file: CidrRulesFile) -> Result<Self> {
+        Ok(Self {
+            allow: parse_ranges(&file.allow)?,
+            deny: parse_ranges(&file.deny)?,
+        })
+    }
+
+    fn is_allowed(&self, ip: IpAddr) -> bool {
+        let in_any = |ranges: &[IpNet]| ranges.iter().any(|range| range.contains(&ip));
+
+        !in_any(&self.deny) && (self.allow.is_empty() || in_any(&self.allow))
+    }
+}
+
+fn parse_ranges(ranges: &[String]) -> Result<Vec<IpNet>> {
+    ranges
+        .iter()
+        .map(|range| {
+            range
+                .parse::<IpNet>()
+                .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| eyre!("invalid CIDR range: {:?}", range))
+        })
+        .collect()
+}
+
+// Proxies usually send a bare address, but some add the port.
+fn parse_forwarded_ip(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim();
+
+    hop.parse::<IpAddr>()
+        .or_else(|_| hop.parse::<SocketAddr>().map(|address| address.ip()))
+        .ok()
+        .map(|ip| ip.to_canonical())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct IpFilterFile {
+    #[serde(default)]
+    trusted_proxies: Vec<String>,
+    #[serde(default)]
+    groups: HashMap<RouteGroup, CidrRulesFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CidrRulesFile {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn rules() -> IpFilterRules {
+        IpFilterRules::parse(
+            r#"{
+                "trustedProxies": ["10.0.0.0/8", "2001:db8::1"],
+                "groups": {
+                    "default": { "deny": ["203.0.113.0/24"] },
+                    "introspection": { "allow": ["10.0.0.0/8", "127.0.0.1"], "deny": ["10.0.0.66"] }
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(X_FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_configuration() {
+        let test_cases = [
+            r#"{ "trustedProxies": ["10.0.0.0/33"] }"#,
+            r#"{ "groups": { "default": { "deny": ["example.com"] } } }"#,
+            r#"{ "groups": { "internal": {} } }"#,
+            r#"{ "groups": { "default": { "block": [] } } }"#,
+            "not json",
+        ];
+
+        for json in test_cases {
+            assert!(IpFilterRules::parse(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let rules = rules();
+
+        assert!(rules.is_allowed(RouteGroup::Default, ip("198.51.100.1")));
+        assert!(!rules.is_allowed(RouteGroup::Default, ip("203.0.113.7")));
+
+        assert!(rules.is_allowed(RouteGroup::Introspection, ip("10.1.2.3")));
+        assert!(rules.is_allowed(RouteGroup::Introspection, ip("127.0.0.1")));
+        assert!(rules.is_allowed(RouteGroup::Introspection, ip("::ffff:127.0.0.1")));
+        assert!(!rules.is_allowed(RouteGroup::Introspection, ip("127.0.0.2")));
+        assert!(!rules.is_allowed(RouteGroup::Introspection, ip("10.0.0.66")));
+
+        assert!(IpFilterRules::default().is_allowed(RouteGroup::Introspection, ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_from_untrusted_peer() {
+        let rules = rules();
+        let headers = forwarded_for(&["198.51.100.1"]);
+
+        assert_eq!(
+            rules.client_ip(ip("203.0.113.7"), &headers),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_proxies() {
+        let rules = rules();
+
+        let test_cases = [
+            (vec![], "10.0.0.1"),
+            (vec!["198.51.100.1"], "198.51.100.1"),
+            // The client made up the first entry, our proxies added the rest
+            (vec!["127.0.0.1, 198.51.100.1, 10.0.0.2"], "198.51.100.1"),
+            (
+                vec!["127.0.0.1", "198.51.100.1:4711", "10.0.0.2"],
+                "198.51.100.1",
+            ),
+            (vec!["[2001:db8::7]:4711, 2001:db8::1"], "2001:db8::7"),
+            (vec!["10.0.0.3, 10.0.0.2"], "10.0.0.3"),
+            (vec!["198.51.100.1, unknown, 10.0.0.2"], "10.0.0.2"),
+        ];
+
+        for (values, expected) in test_cases {
+            let headers = forwarded_for(&values);
+
+            assert_eq!(
+                rules.client_ip(ip("10.0.0.1"), &headers),
+                ip(expected),
+                "{:?}",
+                values
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let filter = IpFilter::default();
+
+        assert!(filter
+            .rules
+            .read()
+            .await
+            .is_allowed(RouteGroup::Default, ip("203.0.113.7")));
+
+        filter.reload(rules()).await;
+
+        assert!(!filter
+            .group(RouteGroup::Introspection)
+            .filter
+            .rules
+            .read()
+            .await
+            .is_allowed(RouteGroup::Default, ip("203.0.113.7")));
+    }
+}
\ No newline at end of file
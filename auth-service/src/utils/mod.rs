@@ -1,3 +1,11 @@
 pub mod constants;
+pub mod admin;
 pub mod auth;
+pub mod cors;
+pub mod csrf;
+pub mod device;
+pub mod ip_filter;
+pub mod proof_of_work;
+pub mod security_headers;
+pub mod step_up;
 pub mod tracing;
\ No newline at end of file
@@ -0,0 +1,344 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::AuthAPIError;
+
+use super::{auth::derive_key, constants::PROOF_OF_WORK_HEADER_NAME, device::client_ip_address};
+
+/// Failed attempts are counted over this window.
+const FAILURE_WINDOW: Duration = Duration::from_secs(600);
+/// Failed attempts of one client before it has to solve challenges.
+const CLIENT_FAILURE_THRESHOLD: u32 = 5;
+/// Failed attempts of all clients together before every client has to solve
+/// challenges. Catches credential stuffing spread over many addresses.
+const GLOBAL_FAILURE_THRESHOLD: u32 = 500;
+/// Every this many failures over the threshold double the work.
+const FAILURES_PER_DIFFICULTY_BIT: u32 = 5;
+/// Leading zero bits of the easiest challenge, about 65 000 hashes.
+pub const MIN_DIFFICULTY: u8 = 16;
+/// Keeps the hardest challenge solvable by the UI within seconds.
+pub const MAX_DIFFICULTY: u8 = 20;
+const CHALLENGE_TTL_SECONDS: i64 = 120;
+// Stop growing the per-client counters before they exhaust memory.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Hashcash-style proof of work for clients that look like they are guessing
+/// credentials.
+///
+/// Every client error from a protected route counts as a failed attempt of its
+/// address. Over a threshold, requests must carry the solution to a challenge
+/// from `/pow-challenge` in the `X-Proof-Of-Work` header: `<challenge>:<nonce>`,
+/// where the SHA-256 of that string starts with `difficulty` zero bits.
+/// Challenges are signed, bound to the address and can be used once.
+#[derive(Clone, Default)]
+pub struct ProofOfWork {
+    state: Arc<Mutex<ThreatState>>,
+}
+
+#[derive(Default)]
+struct ThreatState {
+    clients: HashMap<String, FailureCount>,
+    global: FailureCount,
+    // Ids of solved challenges with their expiry, so a solution works only once.
+    used_challenges: HashMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FailureCount {
+    window_start: Instant,
+    count: u32,
+}
+
+impl Default for FailureCount {
+    fn default() -> Self {
+        Self {
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+}
+
+impl FailureCount {
+    fn current(&self) -> u32 {
+        match self.window_start.elapsed() < FAILURE_WINDOW {
+            true => self.count,
+            false => 0,
+        }
+    }
+
+    fn add(&mut self) {
+        if self.window_start.elapsed() >= FAILURE_WINDOW {
+            *self = Self::default();
+        }
+        self.count += 1;
+    }
+}
+
+/// A challenge from `/pow-challenge`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofOfWorkChallenge {
+    pub challenge: String,
+    pub difficulty: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChallengeClaims {
+    ip: String,
+    dif: u8,
+    exp: usize,
+    jti: String,
+}
+
+impl ProofOfWork {
+    /// Leading zero bits required from requests of `ip_address`, or 0 if it
+    /// does not have to solve a challenge.
+    pub fn difficulty(&self, ip_address: &str) -> u8 {
+        let state = self.state.lock().expect("proof of work state poisoned");
+
+        let client_failures = state
+            .clients
+            .get(ip_address)
+            .map(FailureCount::current)
+            .unwrap_or(0);
+
+        if client_failures < CLIENT_FAILURE_THRESHOLD
+            && state.global.current() < GLOBAL_FAILURE_THRESHOLD
+        {
+            return 0;
+        }
+
+        let extra_bits =
+            client_failures.saturating_sub(CLIENT_FAILURE_THRESHOLD) / FAILURES_PER_DIFFICULTY_BIT;
+
+        (MIN_DIFFICULTY as u32 + extra_bits).min(MAX_DIFFICULTY as u32) as u8
+    }
+
+    pub fn record_failure(&self, ip_address: &str) {
+        let mut state = self.state.lock().expect("proof of work state poisoned");
+
+        state.global.add();
+
+        if state.clients.len() >= MAX_TRACKED_CLIENTS {
+            state.clients.retain(|_, failures| failures.current() > 0);
+        }
+        if state.clients.len() < MAX_TRACKED_CLIENTS || state.clients.contains_key(ip_address) {
+            state
+                .clients
+                .entry(ip_address.to_owned())
+                .or_default()
+                .add();
+        }
+    }
+
+    /// A challenge for `ip_address` at its current difficulty, but never easier
+    /// than [`MIN_DIFFICULTY`].
+    pub fn issue_challenge(&self, ip_address: &str) -> Result<ProofOfWorkChallenge> {
+        create_challenge(ip_address, self.difficulty(ip_address).max(MIN_DIFFICULTY))
+    }
+
+    /// Checks a solution from the `X-Proof-Of-Work` header and marks its
+    /// challenge as used.
+    pub fn verify(&self, solution: &str, ip_address: &str, difficulty: u8) -> Result<()> {
+        let (challenge, nonce) = solution
+            .rsplit_once(':')
+            .ok_or(eyre!("malformed proof of work"))?;
+
+        let claims = decode::<ChallengeClaims>(
+            challenge,
+            &DecodingKey::from_secret(&derive_key(b"proof-of-work")?),
+            &Validation::default(),
+        )
+        .map(|data| data.claims)
+        .wrap_err("failed to decode proof of work challenge")?;
+
+        if claims.ip != ip_address {
+            return Err(eyre!("challenge was issued to another address"));
+        }
+        // The threat level may have gone up since the challenge was issued.
+        if claims.dif < difficulty {
+            return Err(eyre!("challenge is too easy"));
+        }
+        if leading_zero_bits(challenge, nonce) < u32::from(claims.dif) {
+            return Err(eyre!("proof of work does not solve the challenge"));
+        }
+
+        let mut state = self.state.lock().expect("proof of work state poisoned");
+        let now = Utc::now().timestamp();
+
+        state.used_challenges.retain(|_, exp| *exp > now);
+        if state
+            .used_challenges
+            .insert(claims.jti, claims.exp as i64)
+            .is_some()
+        {
+            return Err(eyre!("challenge was already used"));
+        }
+
+        Ok(())
+    }
+}
+
+fn create_challenge(ip_address: &str, difficulty: u8) -> Result<ProofOfWorkChallenge> {
+    let exp: usize = (Utc::now().timestamp() + CHALLENGE_TTL_SECONDS)
+        .try_into()
+        .wrap_err("failed to cast exp time to usize")?;
+
+    let claims = ChallengeClaims {
+        ip: ip_address.to_owned(),
+        dif: difficulty,
+        exp,
+        jti: Uuid::new_v4().to_string(),
+    };
+
+    let challenge = encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(&derive_key(b"proof-of-work")?),
+    )
+    .wrap_err("failed to create proof of work challenge")?;
+
+    Ok(ProofOfWorkChallenge {
+        challenge,
+        difficulty,
+    })
+}
+
+fn leading_zero_bits(challenge: &str, nonce: &str) -> u32 {
+    let digest = Sha256::digest(format!("{}:{}", challenge, nonce).as_bytes());
+
+    let mut bits = 0;
+    for byte in digest {
+        bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+
+    bits
+}
+
+/// Asks clients with too many failed attempts for proof of work, used with
+/// `axum::middleware::from_fn_with_state`.
+#[tracing::instrument(name = "Proof of work", skip_all)]
+pub async fn require_proof_of_work(
+    State(proof_of_work): State<ProofOfWork>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip_address = client_ip_address(request.extensions());
+    let difficulty = proof_of_work.difficulty(&ip_address);
+
+    if difficulty > 0 {
+        let solution = request
+            .headers()
+            .get(PROOF_OF_WORK_HEADER_NAME)
+            .and_then(|value| value.to_str().ok());
+
+        let result = match solution {
+            Some(solution) => proof_of_work.verify(solution, &ip_address, difficulty),
+            None => Err(eyre!("missing proof of work")),
+        };
+
+        if let Err(e) = result {
+            tracing::info!("Proof of work required from {}: {}", ip_address, e);
+            return AuthAPIError::ProofOfWorkRequired.into_response();
+        }
+    }
+
+    let response = next.run(request).await;
+
+    if response.status().is_client_error() {
+        proof_of_work.record_failure(&ip_address);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP_ADDRESS: &str = "203.0.113.7";
+
+    fn solve(challenge: &ProofOfWorkChallenge) -> String {
+        (0u64..)
+            .map(|nonce| nonce.to_string())
+            .find(|nonce| {
+                leading_zero_bits(&challenge.challenge, nonce) >= u32::from(challenge.difficulty)
+            })
+            .map(|nonce| format!("{}:{}", challenge.challenge, nonce))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_difficulty_adapts_to_failures() {
+        let proof_of_work = ProofOfWork::default();
+
+        for _ in 0..CLIENT_FAILURE_THRESHOLD - 1 {
+            proof_of_work.record_failure(IP_ADDRESS);
+        }
+        assert_eq!(proof_of_work.difficulty(IP_ADDRESS), 0);
+
+        proof_of_work.record_failure(IP_ADDRESS);
+        assert_eq!(proof_of_work.difficulty(IP_ADDRESS), MIN_DIFFICULTY);
+        assert_eq!(proof_of_work.difficulty("198.51.100.1"), 0);
+
+        for _ in 0..FAILURES_PER_DIFFICULTY_BIT {
+            proof_of_work.record_failure(IP_ADDRESS);
+        }
+        assert_eq!(proof_of_work.difficulty(IP_ADDRESS), MIN_DIFFICULTY + 1);
+
+        for _ in 0..100 {
+            proof_of_work.record_failure(IP_ADDRESS);
+        }
+        assert_eq!(proof_of_work.difficulty(IP_ADDRESS), MAX_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_global_failures_require_proof_of_work_from_everyone() {
+        let proof_of_work = ProofOfWork::default();
+
+        for i in 0..GLOBAL_FAILURE_THRESHOLD {
+            proof_of_work.record_failure(&format!("10.0.{}.{}", i / 256, i % 256));
+        }
+
+        assert_eq!(proof_of_work.difficulty(IP_ADDRESS), MIN_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_verify() {
+        let proof_of_work = ProofOfWork::default();
+        let challenge = create_challenge(IP_ADDRESS, 8).unwrap();
+        let solution = solve(&challenge);
+
+        assert!(proof_of_work.verify(&solution, "198.51.100.1", 8).is_err());
+        assert!(proof_of_work.verify(&solution, IP_ADDRESS, 9).is_err());
+        assert!(proof_of_work
+            .verify(
+                &format!("{}:not-a-solution", challenge.challenge),
+                IP_ADDRESS,
+                8
+            )
+            .is_err());
+
+        proof_of_work.verify(&solution, IP_ADDRESS, 8).unwrap();
+
+        // Each challenge can only be used once
+        assert!(proof_of_work.verify(&solution, IP_ADDRESS, 8).is_err());
+    }
+}
\ No newline at end of file
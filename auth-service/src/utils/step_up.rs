@@ -0,0 +1,129 @@
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthMethod, Reauthentication},
+};
+
+use super::auth::{AuthenticatedUser, Claims};
+
+/// How long a login is good enough for sensitive operations.
+pub const STEP_UP_MAX_AGE_SECONDS: i64 = 300;
+
+/// How recently and with which methods the user must have logged in to use a
+/// route. Used with [`require_step_up`].
+#[derive(Debug, Clone, Copy)]
+pub struct StepUp {
+    max_age_seconds: i64,
+    second_factor: bool,
+}
+
+impl StepUp {
+    pub fn within_seconds(max_age_seconds: i64) -> Self {
+        Self {
+            max_age_seconds,
+            second_factor: false,
+        }
+    }
+
+    /// Also requires the 2FA code, for users that have 2FA enabled.
+    pub fn with_second_factor(mut self) -> Self {
+        self.second_factor = true;
+        self
+    }
+
+    fn required_methods(&self, requires_2fa: bool) -> Vec<AuthMethod> {
+        match self.second_factor && requires_2fa {
+            true => vec![AuthMethod::Password, AuthMethod::OneTimePassword],
+            false => vec![AuthMethod::Password],
+        }
+    }
+
+    fn is_satisfied_by(&self, claims: &Claims, methods: &[AuthMethod]) -> bool {
+        let age = Utc::now().timestamp() - claims.auth_time as i64;
+
+        age <= self.max_age_seconds && methods.iter().all(|method| claims.amr.contains(method))
+    }
+}
+
+/// Rejects requests whose session does not meet the [`StepUp`] requirement,
+/// used with `axum::middleware::from_fn_with_state`.
+///
+/// The response tells the client how to log in again. With the new session
+/// the request can be retried.
+#[tracing::instrument(name = "Require step-up authentication", skip_all)]
+pub async fn require_step_up(
+    State((state, step_up)): State<(AppState, StepUp)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+
+    let user = match AuthenticatedUser::from_request_parts(&mut parts, &state).await {
+        Ok(user) => user,
+        Err(e) => return e.into_response(),
+    };
+
+    let methods = step_up.required_methods(user.requires_2fa);
+
+    if !step_up.is_satisfied_by(&user.claims, &methods) {
+        return AuthAPIError::ReauthenticationRequired(Reauthentication {
+            max_age: step_up.max_age_seconds,
+            methods,
+        })
+        .into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::UserId;
+
+    use super::*;
+
+    fn claims(age_seconds: i64, amr: Vec<AuthMethod>) -> Claims {
+        Claims {
+            sub: UserId::default().to_string(),
+            exp: 0,
+            jti: String::new(),
+            auth_time: (Utc::now().timestamp() - age_seconds) as usize,
+            amr,
+        }
+    }
+
+    #[test]
+    fn test_required_methods() {
+        let step_up = StepUp::within_seconds(STEP_UP_MAX_AGE_SECONDS);
+        assert_eq!(step_up.required_methods(true), vec![AuthMethod::Password]);
+
+        let step_up = step_up.with_second_factor();
+        assert_eq!(step_up.required_methods(false), vec![AuthMethod::Password]);
+        assert_eq!(
+            step_up.required_methods(true),
+            vec![AuthMethod::Password, AuthMethod::OneTimePassword]
+        );
+    }
+
+    #[test]
+    fn test_is_satisfied_by() {
+        let step_up = StepUp::within_seconds(STEP_UP_MAX_AGE_SECONDS).with_second_factor();
+        let methods = step_up.required_methods(true);
+        let both = vec![AuthMethod::Password, AuthMethod::OneTimePassword];
+
+        assert!(step_up.is_satisfied_by(&claims(10, both.clone()), &methods));
+        assert!(!step_up.is_satisfied_by(&claims(10, vec![AuthMethod::Password]), &methods));
+        assert!(!step_up.is_satisfied_by(&claims(STEP_UP_MAX_AGE_SECONDS + 1, both), &methods));
+
+        // Tokens from before auth_time existed
+        let mut old = claims(0, vec![AuthMethod::Password]);
+        old.auth_time = 0;
+        assert!(!step_up.is_satisfied_by(&old, &[AuthMethod::Password]));
+    }
+}
\ No newline at end of file
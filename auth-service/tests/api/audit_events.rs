@@ -0,0 +1,135 @@
+use auth_service::{
+    domain::{AuditChainVerifier, AuditEventType},
+    routes::AuditEventsResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[api_test]
+async fn should_record_login_events() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong_password",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let events = get_audit_events(&app, &[("email", &random_email)]).await;
+
+    let event_types = events
+        .events
+        .iter()
+        .map(|event| event.event_type)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        event_types,
+        vec![
+            AuditEventType::Signup,
+            AuditEventType::LoginFailed,
+            AuditEventType::Login,
+            AuditEventType::Logout,
+            AuditEventType::TokenBanned,
+        ]
+    );
+    assert!(events
+        .events
+        .iter()
+        .all(|event| event.ip_address.as_deref() == Some("127.0.0.1")));
+
+    let mut verifier = AuditChainVerifier::default();
+    for event in &events.events {
+        verifier.verify(event).unwrap();
+    }
+}
+
+#[api_test]
+async fn should_filter_audit_events() {
+    for _ in 0..3 {
+        let signup_body = serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+            "requires2FA": false
+        });
+
+        let response = app.post_signup(&signup_body).await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+
+    let events = get_audit_events(&app, &[("eventType", "signup")]).await;
+    assert_eq!(events.events.len(), 3);
+
+    let events = get_audit_events(&app, &[("eventType", "login")]).await;
+    assert!(events.events.is_empty());
+
+    let events = get_audit_events(&app, &[("afterSequence", "1"), ("limit", "1")]).await;
+    assert_eq!(events.events.len(), 1);
+    assert_eq!(events.events[0].sequence, 2);
+
+    let events = get_audit_events(&app, &[("from", "2000-01-01T00:00:00Z")]).await;
+    assert_eq!(events.events.len(), 3);
+
+    let events = get_audit_events(&app, &[("to", "2000-01-01T00:00:00Z")]).await;
+    assert!(events.events.is_empty());
+}
+
+#[api_test]
+async fn should_require_admin_token() {
+    let url = format!("{}/admin/audit-events", &app.address);
+
+    let response = app.http_client.get(&url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .http_client
+        .get(&url)
+        .bearer_auth("wrong-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_query() {
+    let response = app.get_audit_events(&[("eventType", "unknown")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_audit_events(&[("from", "yesterday")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn get_audit_events(app: &TestApp, query: &[(&str, &str)]) -> AuditEventsResponse {
+    let response = app.get_audit_events(query).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse")
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+use auth_service::{
+    domain::{AuthMethod, Reauthentication},
+    routes::TwoFactorAuthResponse,
+    utils::{constants::JWT_COOKIE_NAME, step_up::STEP_UP_MAX_AGE_SECONDS},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::{
+    helpers::{get_random_email, TestApp},
+    trusted_devices::{login_with_2fa, signup_with_2fa},
+};
+
+fn change_password_body() -> serde_json::Value {
+    serde_json::json!({ "newPassword": "correct horse battery staple" })
+}
+
+#[api_test]
+async fn should_return_200_after_recent_login_with_2fa() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_with_2fa(&app, &random_email, false).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&change_password_body()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_revoke_other_sessions_and_trusted_devices() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    // The 2FA codes of the first login, of the step-up login and of the login
+    // after the change
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let other_session_token = auth_cookie_value(&response);
+
+    let step_up_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "stepUp": true
+    });
+
+    let response = app.post_login(&step_up_login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": app.get_2fa_code(&random_email).await,
+    });
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let current_session_token = auth_cookie_value(&response);
+
+    let response = app.post_change_password(&change_password_body()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_session_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": current_session_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "correct horse battery staple"
+    });
+
+    // The browser is not trusted anymore
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_return_401_with_reauthentication_if_2fa_was_skipped() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    // The 2FA codes of the first login and of the step-up login
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    // The trusted device skips 2FA, which is not good enough to change the password
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&change_password_body()).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let error_response = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(error_response.error, "Reauthentication required".to_owned());
+    assert_eq!(
+        error_response.reauthenticate,
+        Some(Reauthentication {
+            max_age: STEP_UP_MAX_AGE_SECONDS,
+            methods: vec![AuthMethod::Password, AuthMethod::OneTimePassword],
+        })
+    );
+
+    let step_up_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "stepUp": true
+    });
+
+    let response = app.post_login(&step_up_login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_not_require_2fa_from_users_without_it() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_change_password(&change_password_body()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_change_password(&change_password_body()).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+fn auth_cookie_value(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
\ No newline at end of file
@@ -0,0 +1,153 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::data_stores::SqliteAuditLogStore;
+use auth_service::{
+    domain::{
+        AuditChainVerifier, AuditEvent, AuditEventFilter, AuditEventType, AuditLogStore,
+        AuditRecord,
+    },
+    services::data_stores::{PostgresAuditLogStore, VecAuditLogStore},
+};
+use chrono::{Duration, Utc};
+
+use super::email;
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool;
+use crate::helpers::TestApp;
+
+fn sequences(records: Vec<AuditRecord>) -> Vec<i64> {
+    records.iter().map(|record| record.sequence).collect()
+}
+
+async fn append_chains_records(store: &dyn AuditLogStore) {
+    let first = store
+        .append(AuditEvent::new(AuditEventType::Signup))
+        .await
+        .unwrap();
+    let second = store
+        .append(AuditEvent::new(AuditEventType::Login).email(&email("test@example.com")))
+        .await
+        .unwrap();
+
+    assert_eq!(first.sequence, 1);
+    assert_eq!(second.sequence, 2);
+    assert_eq!(second.previous_hash, first.hash);
+
+    let records = store
+        .get_events(&AuditEventFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(records, vec![first, second]);
+
+    let mut verifier = AuditChainVerifier::default();
+    for record in &records {
+        verifier.verify(record).unwrap();
+    }
+    assert_eq!(verifier.verified_records(), 2);
+}
+
+async fn get_events_filters(store: &dyn AuditLogStore) {
+    let alice = email("alice@example.com");
+    let bob = email("bob@example.com");
+
+    for event in [
+        AuditEvent::new(AuditEventType::Signup).email(&alice),
+        AuditEvent::new(AuditEventType::Signup).email(&bob),
+        AuditEvent::new(AuditEventType::LoginFailed).email(&alice),
+        AuditEvent::new(AuditEventType::Login).email(&alice),
+    ] {
+        store.append(event).await.unwrap();
+    }
+
+    let filter = AuditEventFilter {
+        email: Some("alice@example.com".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        sequences(store.get_events(&filter).await.unwrap()),
+        vec![1, 3, 4]
+    );
+
+    let filter = AuditEventFilter {
+        event_type: Some(AuditEventType::Signup),
+        ..Default::default()
+    };
+    assert_eq!(
+        sequences(store.get_events(&filter).await.unwrap()),
+        vec![1, 2]
+    );
+
+    let filter = AuditEventFilter {
+        after_sequence: 1,
+        limit: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        sequences(store.get_events(&filter).await.unwrap()),
+        vec![2, 3]
+    );
+
+    let filter = AuditEventFilter {
+        from: Some(Utc::now() - Duration::hours(1)),
+        to: Some(Utc::now() + Duration::hours(1)),
+        ..Default::default()
+    };
+    assert_eq!(
+        sequences(store.get_events(&filter).await.unwrap()),
+        vec![1, 2, 3, 4]
+    );
+
+    let filter = AuditEventFilter {
+        to: Some(Utc::now() - Duration::hours(1)),
+        ..Default::default()
+    };
+    assert!(store.get_events(&filter).await.unwrap().is_empty());
+}
+
+// Events keep the email as it was typed, e.g. in a failed login.
+async fn get_events_by_email_regardless_of_case(store: &dyn AuditLogStore) {
+    for event in [
+        AuditEvent::new(AuditEventType::Signup).email(&email("alice@example.com")),
+        AuditEvent::new(AuditEventType::LoginFailed).email(&email("Alice@example.com")),
+        AuditEvent::new(AuditEventType::Signup).email(&email("bob@example.com")),
+    ] {
+        store.append(event).await.unwrap();
+    }
+
+    let filter = AuditEventFilter {
+        email: Some("ALICE@example.com".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(
+        sequences(store.get_events(&filter).await.unwrap()),
+        vec![1, 2]
+    );
+}
+
+store_tests!(
+    vec,
+    VecAuditLogStore::default(),
+    [
+        append_chains_records,
+        get_events_filters,
+        get_events_by_email_regardless_of_case,
+    ]
+);
+store_tests!(
+    postgres,
+    |app| &PostgresAuditLogStore::new(app.pg_pool.clone()),
+    [
+        append_chains_records,
+        get_events_filters,
+        get_events_by_email_regardless_of_case,
+    ]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteAuditLogStore::new(sqlite_pool().await),
+    [
+        append_chains_records,
+        get_events_filters,
+        get_events_by_email_regardless_of_case,
+    ]
+);
\ No newline at end of file
@@ -0,0 +1,94 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::data_stores::SqliteBannedTokenStore;
+use auth_service::{
+    domain::BannedTokenStore,
+    services::{
+        data_stores::{HashsetBannedTokenStore, PostgresBannedTokenStore},
+        sweeper::ExpiringStore,
+    },
+};
+#[cfg(feature = "sqlite")]
+use chrono::Utc;
+use secrecy::Secret;
+use test_helpers::api_test;
+use uuid::Uuid;
+
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool;
+use crate::helpers::TestApp;
+
+fn token() -> Secret<String> {
+    Secret::new(Uuid::new_v4().to_string())
+}
+
+async fn ban_token(banned_token_store: &dyn BannedTokenStore) {
+    let token = token();
+    assert!(!banned_token_store.contains_token(&token).await.unwrap());
+
+    banned_token_store.add_token(token.clone()).await.unwrap();
+    assert!(banned_token_store.contains_token(&token).await.unwrap());
+
+    // Banning a token again is fine
+    banned_token_store.add_token(token.clone()).await.unwrap();
+    assert!(banned_token_store.contains_token(&token).await.unwrap());
+
+    assert!(!banned_token_store
+        .contains_token(&self::token())
+        .await
+        .unwrap());
+}
+
+store_tests!(hashset, HashsetBannedTokenStore::default(), [ban_token]);
+store_tests!(redis, |app| app.banned_token_store.as_ref(), [ban_token]);
+store_tests!(
+    postgres,
+    |app| &PostgresBannedTokenStore::new(app.pg_pool.clone()),
+    [ban_token]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteBannedTokenStore::new(sqlite_pool().await),
+    [ban_token]
+);
+
+#[api_test]
+async fn postgres_banned_token_store_should_ignore_and_sweep_expired_tokens() {
+    let banned_token_store = PostgresBannedTokenStore::new(app.pg_pool.clone());
+    let token = token();
+    banned_token_store.add_token(token.clone()).await.unwrap();
+
+    sqlx::query("UPDATE banned_tokens SET expires_at = now() - interval '1 second'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    assert!(!banned_token_store.contains_token(&token).await.unwrap());
+    assert_eq!(banned_token_store.delete_expired().await.unwrap(), 1);
+    assert_eq!(banned_token_store.delete_expired().await.unwrap(), 0);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_banned_token_store_should_ignore_and_drop_expired_tokens() {
+    let pool = sqlite_pool().await;
+    let banned_token_store = SqliteBannedTokenStore::new(pool.clone());
+    let token = token();
+    banned_token_store.add_token(token.clone()).await.unwrap();
+
+    sqlx::query("UPDATE banned_tokens SET expires_at = ?1")
+        .bind(Utc::now().timestamp() - 1)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert!(!banned_token_store.contains_token(&token).await.unwrap());
+
+    // Nothing sweeps SQLite, banning another token drops the expired one
+    banned_token_store.add_token(self::token()).await.unwrap();
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM banned_tokens")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
\ No newline at end of file
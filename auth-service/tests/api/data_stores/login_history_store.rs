@@ -0,0 +1,166 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::data_stores::SqliteLoginHistoryStore;
+use auth_service::{
+    domain::{Device, Email, Login, LoginHistoryStore, LoginHistoryStoreError, ReportToken},
+    services::data_stores::HashmapLoginHistoryStore,
+};
+use secrecy::Secret;
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool_with_user;
+
+fn email() -> Email {
+    super::email("test@example.com")
+}
+
+fn device(user_agent: &str) -> Device {
+    Device::new("127.0.0.1".to_owned(), user_agent.to_owned())
+}
+
+fn login(user_agent: &str, session_token: &str) -> Login {
+    Login {
+        email: email(),
+        device: device(user_agent),
+        session_token: Secret::new(session_token.to_owned()),
+    }
+}
+
+// The hex SHA-256 that banned tokens are stored by.
+fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+async fn add_login(store: &dyn LoginHistoryStore) {
+    assert!(!store.has_logins(&email()).await.unwrap());
+
+    store
+        .add_login(login("firefox", "session_token"), &ReportToken::default())
+        .await
+        .unwrap();
+
+    assert!(store.has_logins(&email()).await.unwrap());
+    assert!(store
+        .has_login_from_device(&email(), &device("firefox"))
+        .await
+        .unwrap());
+    assert!(!store
+        .has_login_from_device(&email(), &device("chrome"))
+        .await
+        .unwrap());
+}
+
+async fn report_login(store: &dyn LoginHistoryStore) {
+    let report_token = ReportToken::default();
+    store
+        .add_login(login("firefox", "session_token"), &report_token)
+        .await
+        .unwrap();
+
+    assert!(!store.requires_password_reset(&email()).await.unwrap());
+    assert!(matches!(
+        store.get_password_reset_email(&report_token).await,
+        Err(LoginHistoryStoreError::LoginNotFound)
+    ));
+
+    let reported = store.report_login(&report_token).await.unwrap();
+    assert_eq!(reported.email, email());
+    assert_eq!(
+        reported.session_token_hash,
+        Some(token_hash("session_token"))
+    );
+
+    // Reporting again is harmless, but the session was already handed out
+    let reported = store.report_login(&report_token).await.unwrap();
+    assert!(reported.session_token_hash.is_none());
+
+    assert!(store.requires_password_reset(&email()).await.unwrap());
+    assert_eq!(
+        store.get_password_reset_email(&report_token).await.unwrap(),
+        email()
+    );
+
+    store.complete_password_reset(&email()).await.unwrap();
+
+    assert!(!store.requires_password_reset(&email()).await.unwrap());
+    assert!(matches!(
+        store.get_password_reset_email(&report_token).await,
+        Err(LoginHistoryStoreError::LoginNotFound)
+    ));
+}
+
+async fn report_unknown_login(store: &dyn LoginHistoryStore) {
+    let result = store.report_login(&ReportToken::default()).await;
+
+    assert!(matches!(result, Err(LoginHistoryStoreError::LoginNotFound)));
+}
+
+async fn take_session_token_hashes(store: &dyn LoginHistoryStore) {
+    for (user_agent, session_token) in [("firefox", "first"), ("chrome", "current")] {
+        store
+            .add_login(login(user_agent, session_token), &ReportToken::default())
+            .await
+            .unwrap();
+    }
+    let current_session_token = Secret::new("current".to_owned());
+
+    let hashes = store
+        .take_session_token_hashes(&email(), Some(&current_session_token))
+        .await
+        .unwrap();
+    assert_eq!(hashes, vec![token_hash("first")]);
+
+    let hashes = store
+        .take_session_token_hashes(&email(), Some(&current_session_token))
+        .await
+        .unwrap();
+    assert!(hashes.is_empty());
+}
+
+async fn take_all_session_token_hashes(store: &dyn LoginHistoryStore) {
+    for (user_agent, session_token) in [("firefox", "first"), ("chrome", "second")] {
+        store
+            .add_login(login(user_agent, session_token), &ReportToken::default())
+            .await
+            .unwrap();
+    }
+
+    let mut hashes = store
+        .take_session_token_hashes(&email(), None)
+        .await
+        .unwrap();
+    hashes.sort();
+    let mut expected = vec![token_hash("first"), token_hash("second")];
+    expected.sort();
+    assert_eq!(hashes, expected);
+
+    let hashes = store
+        .take_session_token_hashes(&email(), None)
+        .await
+        .unwrap();
+    assert!(hashes.is_empty());
+}
+
+store_tests!(
+    hashmap,
+    HashmapLoginHistoryStore::default(),
+    [
+        add_login,
+        report_login,
+        report_unknown_login,
+        take_session_token_hashes,
+        take_all_session_token_hashes,
+    ]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteLoginHistoryStore::new(sqlite_pool_with_user(&email()).await),
+    [
+        add_login,
+        report_login,
+        report_unknown_login,
+        take_session_token_hashes,
+        take_all_session_token_hashes,
+    ]
+);
\ No newline at end of file
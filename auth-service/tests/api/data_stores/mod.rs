@@ -0,0 +1,84 @@
+//! Cases every implementation of a store trait has to pass. Each case is an
+//! async function taking the store as a trait object, and [`store_tests`]
+//! runs it against every implementation.
+
+use auth_service::domain::Email;
+#[cfg(feature = "sqlite")]
+use auth_service::{
+    domain::{PasswordHash, User, UserStore},
+    get_sqlite_pool,
+    services::{argon2_password_hasher::Argon2PasswordHasher, data_stores::SqliteUserStore},
+};
+use secrecy::Secret;
+#[cfg(feature = "sqlite")]
+use sqlx::SqlitePool;
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+
+/// Adds a module named `$store` with one test per case, so a case that fails
+/// shows up as e.g. `user_store::postgres::list_users`. Stores that need
+/// Postgres or Redis are borrowed with `|app| ...` from the app of an
+/// `api_test`.
+macro_rules! store_tests {
+    ($store:ident, |$app:ident| $new_store:expr, [$($case:ident),+ $(,)?]) => {
+        mod $store {
+            use super::*;
+
+            $(
+                #[test_helpers::api_test]
+                async fn $case() {
+                    let $app = &app;
+                    super::$case($new_store).await;
+                }
+            )+
+        }
+    };
+    ($store:ident, $new_store:expr, [$($case:ident),+ $(,)?]) => {
+        mod $store {
+            use super::*;
+
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    super::$case(&$new_store).await;
+                }
+            )+
+        }
+    };
+}
+
+mod audit_log_store;
+mod banned_token_store;
+mod login_history_store;
+mod trusted_device_store;
+mod two_fa_code_store;
+mod user_store;
+
+fn email(email: &str) -> Email {
+    Email::parse(Secret::new(email.to_owned())).unwrap()
+}
+
+#[cfg(feature = "sqlite")]
+async fn sqlite_pool() -> SqlitePool {
+    get_sqlite_pool(&Secret::new("sqlite::memory:".to_owned()))
+        .await
+        .unwrap()
+}
+
+/// The tables that reference users need the user to exist.
+#[cfg(feature = "sqlite")]
+async fn sqlite_pool_with_user(email: &Email) -> SqlitePool {
+    let pool = sqlite_pool().await;
+    let user = User::new(
+        email.clone(),
+        PasswordHash::new(Secret::new("password-hash".to_owned()), None),
+        false,
+    );
+
+    SqliteUserStore::new(pool.clone(), Arc::new(Argon2PasswordHasher::default()))
+        .add_user(user)
+        .await
+        .unwrap();
+
+    pool
+}
\ No newline at end of file
@@ -0,0 +1,91 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::data_stores::SqliteTrustedDeviceStore;
+use auth_service::{
+    domain::{Device, Email, TrustedDevice, TrustedDeviceStore, TrustedDeviceStoreError},
+    services::data_stores::HashmapTrustedDeviceStore,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool_with_user;
+
+fn email() -> Email {
+    super::email("test@example.com")
+}
+
+fn trusted_device(user_agent: &str) -> TrustedDevice {
+    TrustedDevice::new(
+        email(),
+        Device::new("127.0.0.1".to_owned(), user_agent.to_owned()),
+    )
+}
+
+async fn add_and_get_devices(store: &dyn TrustedDeviceStore) {
+    let firefox = trusted_device("firefox");
+    let chrome = TrustedDevice {
+        created_at: firefox.created_at + Duration::seconds(1),
+        ..trusted_device("chrome")
+    };
+    store.add_device(firefox.clone()).await.unwrap();
+    store.add_device(chrome.clone()).await.unwrap();
+
+    assert_eq!(
+        store.get_devices(&email()).await.unwrap(),
+        vec![firefox.clone(), chrome]
+    );
+    assert!(store.is_trusted(&email(), &firefox.id).await.unwrap());
+    assert!(!store.is_trusted(&email(), &Uuid::new_v4()).await.unwrap());
+
+    let other_email = super::email("other@example.com");
+    assert!(!store.is_trusted(&other_email, &firefox.id).await.unwrap());
+    assert!(store.get_devices(&other_email).await.unwrap().is_empty());
+}
+
+async fn ignore_expired_devices(store: &dyn TrustedDeviceStore) {
+    let expired = TrustedDevice {
+        expires_at: Utc::now() - Duration::seconds(1),
+        ..trusted_device("firefox")
+    };
+    store.add_device(expired.clone()).await.unwrap();
+
+    assert!(!store.is_trusted(&email(), &expired.id).await.unwrap());
+    assert!(store.get_devices(&email()).await.unwrap().is_empty());
+}
+
+async fn remove_devices(store: &dyn TrustedDeviceStore) {
+    let devices = vec![trusted_device("firefox"), trusted_device("chrome")];
+    for device in &devices {
+        store.add_device(device.clone()).await.unwrap();
+    }
+
+    // Users can only revoke their own devices
+    let other_email = super::email("other@example.com");
+    assert_eq!(
+        store.remove_device(&other_email, &devices[0].id).await,
+        Err(TrustedDeviceStoreError::DeviceNotFound)
+    );
+
+    store.remove_device(&email(), &devices[0].id).await.unwrap();
+    assert!(!store.is_trusted(&email(), &devices[0].id).await.unwrap());
+    assert!(store.is_trusted(&email(), &devices[1].id).await.unwrap());
+    assert_eq!(
+        store.remove_device(&email(), &devices[0].id).await,
+        Err(TrustedDeviceStoreError::DeviceNotFound)
+    );
+
+    store.remove_all_devices(&email()).await.unwrap();
+    assert!(store.get_devices(&email()).await.unwrap().is_empty());
+}
+
+store_tests!(
+    hashmap,
+    HashmapTrustedDeviceStore::default(),
+    [add_and_get_devices, ignore_expired_devices, remove_devices]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteTrustedDeviceStore::new(sqlite_pool_with_user(&email()).await),
+    [add_and_get_devices, ignore_expired_devices, remove_devices]
+);
\ No newline at end of file
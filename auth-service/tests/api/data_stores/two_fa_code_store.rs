@@ -0,0 +1,222 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::data_stores::SqliteTwoFACodeStore;
+use auth_service::{
+    domain::{
+        Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        MAX_PENDING_LOGIN_ATTEMPTS,
+    },
+    services::{
+        data_stores::{HashmapTwoFACodeStore, PostgresTwoFACodeStore},
+        sweeper::ExpiringStore,
+    },
+};
+#[cfg(feature = "sqlite")]
+use chrono::Utc;
+use secrecy::Secret;
+use test_helpers::api_test;
+
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool;
+use crate::helpers::{get_random_email, TestApp};
+
+fn email() -> Email {
+    super::email(&get_random_email())
+}
+
+fn code(code: &str) -> TwoFACode {
+    TwoFACode::parse(Secret::new(code.to_owned())).unwrap()
+}
+
+async fn validate_and_remove_code(two_fa_code_store: &dyn TwoFACodeStore) {
+    let email = email();
+    let login_attempt_id = LoginAttemptId::default();
+    two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), code("123456"))
+        .await
+        .unwrap();
+
+    let result = two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &code("654321"))
+        .await;
+    assert_eq!(result.unwrap_err(), TwoFACodeStoreError::IncorrectCode);
+
+    let result = two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &code("123456"))
+        .await;
+    assert!(result.is_ok());
+
+    // The login attempt belongs to another user
+    let result = two_fa_code_store
+        .validate_code(&self::email(), &login_attempt_id, &code("123456"))
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+    let result = two_fa_code_store
+        .remove_code(&self::email(), &login_attempt_id)
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+
+    two_fa_code_store
+        .remove_code(&email, &login_attempt_id)
+        .await
+        .unwrap();
+
+    let result = two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &code("123456"))
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+    let result = two_fa_code_store
+        .remove_code(&email, &login_attempt_id)
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+}
+
+async fn keep_newest_attempts(two_fa_code_store: &dyn TwoFACodeStore) {
+    let email = email();
+    let attempts: Vec<LoginAttemptId> = (0..=MAX_PENDING_LOGIN_ATTEMPTS)
+        .map(|_| LoginAttemptId::default())
+        .collect();
+
+    for login_attempt_id in &attempts {
+        two_fa_code_store
+            .add_code(email.clone(), login_attempt_id.clone(), code("123456"))
+            .await
+            .unwrap();
+    }
+
+    let result = two_fa_code_store
+        .validate_code(&email, &attempts[0], &code("123456"))
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+
+    for login_attempt_id in &attempts[1..] {
+        let result = two_fa_code_store
+            .validate_code(&email, login_attempt_id, &code("123456"))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    // Other users keep their attempts
+    let other_email = self::email();
+    let login_attempt_id = LoginAttemptId::default();
+    two_fa_code_store
+        .add_code(
+            other_email.clone(),
+            login_attempt_id.clone(),
+            code("123456"),
+        )
+        .await
+        .unwrap();
+    let result = two_fa_code_store
+        .validate_code(&email, &attempts[1], &code("123456"))
+        .await;
+    assert!(result.is_ok());
+}
+
+store_tests!(
+    hashmap,
+    HashmapTwoFACodeStore::default(),
+    [validate_and_remove_code, keep_newest_attempts]
+);
+store_tests!(
+    redis,
+    |app| app.two_fa_code_store.as_ref(),
+    [validate_and_remove_code, keep_newest_attempts]
+);
+store_tests!(
+    postgres,
+    |app| &PostgresTwoFACodeStore::new(app.pg_pool.clone()),
+    [validate_and_remove_code, keep_newest_attempts]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteTwoFACodeStore::new(sqlite_pool().await),
+    [validate_and_remove_code, keep_newest_attempts]
+);
+
+#[api_test]
+async fn postgres_two_fa_code_store_should_ignore_and_sweep_expired_codes() {
+    let two_fa_code_store = PostgresTwoFACodeStore::new(app.pg_pool.clone());
+    let email = email();
+    let login_attempt_id = LoginAttemptId::default();
+    two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), code("123456"))
+        .await
+        .unwrap();
+
+    sqlx::query("UPDATE two_fa_codes SET expires_at = now() - interval '1 second'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let result = two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &code("123456"))
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+    let result = two_fa_code_store
+        .remove_code(&email, &login_attempt_id)
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+
+    assert_eq!(two_fa_code_store.delete_expired().await.unwrap(), 1);
+    assert_eq!(two_fa_code_store.delete_expired().await.unwrap(), 0);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_two_fa_code_store_should_ignore_and_drop_expired_codes() {
+    let pool = sqlite_pool().await;
+    let two_fa_code_store = SqliteTwoFACodeStore::new(pool.clone());
+    let email = email();
+    let login_attempt_id = LoginAttemptId::default();
+    two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), code("123456"))
+        .await
+        .unwrap();
+
+    sqlx::query("UPDATE two_fa_codes SET expires_at = ?1")
+        .bind(Utc::now().timestamp() - 1)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let result = two_fa_code_store
+        .validate_code(&email, &login_attempt_id, &code("123456"))
+        .await;
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+
+    // Nothing sweeps SQLite, adding another code drops the expired one
+    two_fa_code_store
+        .add_code(self::email(), LoginAttemptId::default(), code("123456"))
+        .await
+        .unwrap();
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM two_fa_codes")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
\ No newline at end of file
@@ -0,0 +1,281 @@
+#[cfg(feature = "sqlite")]
+use auth_service::services::{
+    argon2_password_hasher::Argon2PasswordHasher, data_stores::SqliteUserStore,
+};
+use auth_service::{
+    domain::{Email, PasswordHash, User, UserFilter, UserStore, UserStoreError, UserUpdate},
+    services::data_stores::HashmapUserStore,
+};
+use chrono::{Duration, SubsecRound, Utc};
+use secrecy::Secret;
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+
+use super::email;
+#[cfg(feature = "sqlite")]
+use super::sqlite_pool;
+use crate::helpers::{get_random_email, TestApp};
+
+// These cases never check passwords, so any hash will do.
+fn password_hash() -> PasswordHash {
+    PasswordHash::new(Secret::new("password-hash".to_owned()), None)
+}
+
+fn user(email_address: &str) -> User {
+    User::new(email(email_address), password_hash(), false)
+}
+
+// Signed up a minute apart, in the order given.
+async fn add_users(user_store: &dyn UserStore, users: Vec<User>) -> Vec<User> {
+    let start = (Utc::now() - Duration::days(1)).trunc_subsecs(6);
+    let mut added = vec![];
+
+    for (minutes, user) in users.into_iter().enumerate() {
+        let user = User {
+            created_at: start + Duration::minutes(minutes as i64),
+            ..user
+        };
+        user_store.add_user(user.clone()).await.unwrap();
+        added.push(user);
+    }
+
+    added
+}
+
+fn emails(users: &[User]) -> Vec<Email> {
+    users.iter().map(|user| user.email.clone()).collect()
+}
+
+async fn add_and_get_user(user_store: &dyn UserStore) {
+    let address = get_random_email();
+    let user = user(&address);
+    user_store.add_user(user.clone()).await.unwrap();
+
+    assert_eq!(user_store.get_user(&user.email).await.unwrap(), user);
+    assert_eq!(user_store.get_user_by_id(&user.id).await.unwrap(), user);
+
+    // Emails are unique regardless of case
+    let result = user_store
+        .add_user(self::user(&address.to_uppercase()))
+        .await;
+    assert_eq!(result, Err(UserStoreError::UserAlreadyExists));
+    let result = user_store.get_user(&email(&address.to_uppercase())).await;
+    assert_eq!(result.unwrap(), user);
+}
+
+async fn update_user(user_store: &dyn UserStore) {
+    let user = user(&get_random_email());
+    user_store.add_user(user.clone()).await.unwrap();
+
+    let update = UserUpdate {
+        requires_2fa: Some(true),
+        display_name: Some(Some("Alice".to_owned())),
+        ..Default::default()
+    };
+    let updated = user_store.update_user(&user.email, update).await.unwrap();
+    assert!(updated.requires_2fa);
+    assert!(!updated.email_verified);
+    assert_eq!(updated.display_name.as_deref(), Some("Alice"));
+    assert_eq!(user_store.get_user(&user.email).await.unwrap(), updated);
+
+    // Unset fields stay as they are
+    let update = UserUpdate {
+        email_verified: Some(true),
+        ..Default::default()
+    };
+    let updated = user_store.update_user(&user.email, update).await.unwrap();
+    assert!(updated.requires_2fa);
+    assert!(updated.email_verified);
+    assert_eq!(updated.display_name.as_deref(), Some("Alice"));
+
+    let update = UserUpdate {
+        display_name: Some(None),
+        ..Default::default()
+    };
+    let updated = user_store.update_user(&user.email, update).await.unwrap();
+    assert_eq!(updated.display_name, None);
+
+    let result = user_store
+        .update_user(&email(&get_random_email()), UserUpdate::default())
+        .await;
+    assert_eq!(result, Err(UserStoreError::UserNotFound));
+}
+
+async fn delete_user(user_store: &dyn UserStore) {
+    let user = user(&get_random_email());
+    user_store.add_user(user.clone()).await.unwrap();
+
+    assert_eq!(user_store.delete_user(&user.email).await, Ok(()));
+    assert_eq!(
+        user_store.get_user(&user.email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        user_store.delete_user(&user.email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // The email can be used again
+    assert_eq!(user_store.add_user(user).await, Ok(()));
+}
+
+async fn list_users(user_store: &dyn UserStore) {
+    let users = add_users(
+        user_store,
+        (0..5).map(|_| user(&get_random_email())).collect(),
+    )
+    .await;
+
+    let page = |offset, limit| UserFilter {
+        offset,
+        limit,
+        ..Default::default()
+    };
+
+    let result = user_store.list_users(&page(0, 2)).await.unwrap();
+    assert_eq!(result, users[..2]);
+    let result = user_store.list_users(&page(2, 2)).await.unwrap();
+    assert_eq!(result, users[2..4]);
+    let result = user_store.list_users(&page(4, 2)).await.unwrap();
+    assert_eq!(result, users[4..]);
+    let result = user_store.list_users(&page(6, 2)).await.unwrap();
+    assert!(result.is_empty());
+}
+
+async fn search_users(user_store: &dyn UserStore) {
+    let mut alice = user("Alice@example.com");
+    alice.display_name = Some("Alice Liddell".to_owned());
+    let mut bob = user("bob@example.com");
+    bob.display_name = Some("Bob".to_owned());
+    let carol = user("carol@alice.example");
+    let users = add_users(user_store, vec![alice, bob, carol]).await;
+
+    let search = |search: &str| UserFilter {
+        search: Some(search.to_owned()),
+        ..Default::default()
+    };
+
+    let result = user_store.list_users(&search("ALICE")).await.unwrap();
+    assert_eq!(
+        emails(&result),
+        vec![email("Alice@example.com"), email("carol@alice.example")]
+    );
+    let result = user_store.list_users(&search("liddell")).await.unwrap();
+    assert_eq!(emails(&result), vec![email("Alice@example.com")]);
+    let result = user_store.list_users(&search("dave")).await.unwrap();
+    assert!(result.is_empty());
+
+    let filter = UserFilter {
+        created_after: Some(users[0].created_at),
+        ..Default::default()
+    };
+    let result = user_store.list_users(&filter).await.unwrap();
+    assert_eq!(result, users[1..]);
+}
+
+// PostgreSQL lowercases by the locale of the database, so it only runs this
+// where that locale knows these letters.
+async fn search_non_ascii_names(user_store: &dyn UserStore) {
+    let mut bob = user("bob@example.com");
+    bob.display_name = Some("Bob Østergård".to_owned());
+    user_store.add_user(bob.clone()).await.unwrap();
+
+    let search = |search: &str| UserFilter {
+        search: Some(search.to_owned()),
+        ..Default::default()
+    };
+
+    let result = user_store.list_users(&search("ØSTERGÅRD")).await.unwrap();
+    assert_eq!(result, vec![bob.clone()]);
+
+    let update = UserUpdate {
+        display_name: Some(Some("Bob Ærøskøbing".to_owned())),
+        ..Default::default()
+    };
+    let updated = user_store.update_user(&bob.email, update).await.unwrap();
+
+    let result = user_store.list_users(&search("ÆRØ")).await.unwrap();
+    assert_eq!(result, vec![updated]);
+    let result = user_store.list_users(&search("østergård")).await.unwrap();
+    assert!(result.is_empty());
+}
+
+async fn filter_users_by_flags(user_store: &dyn UserStore) {
+    let users = add_users(
+        user_store,
+        vec![
+            user("plain@example.com"),
+            User::new(email("2fa@example.com"), password_hash(), true),
+            User {
+                email_verified: true,
+                ..user("verified@example.com")
+            },
+        ],
+    )
+    .await;
+
+    let filter = UserFilter {
+        requires_2fa: Some(true),
+        ..Default::default()
+    };
+    let result = user_store.list_users(&filter).await.unwrap();
+    assert_eq!(result, users[1..2]);
+
+    let filter = UserFilter {
+        email_verified: Some(true),
+        ..Default::default()
+    };
+    let result = user_store.list_users(&filter).await.unwrap();
+    assert_eq!(result, users[2..]);
+
+    let filter = UserFilter {
+        requires_2fa: Some(false),
+        email_verified: Some(false),
+        ..Default::default()
+    };
+    let result = user_store.list_users(&filter).await.unwrap();
+    assert_eq!(result, users[..1]);
+}
+
+store_tests!(
+    hashmap,
+    HashmapUserStore::default(),
+    [
+        add_and_get_user,
+        update_user,
+        delete_user,
+        list_users,
+        search_users,
+        search_non_ascii_names,
+        filter_users_by_flags,
+    ]
+);
+store_tests!(
+    postgres,
+    |app| app.user_store.as_ref(),
+    [
+        add_and_get_user,
+        update_user,
+        delete_user,
+        list_users,
+        search_users,
+        filter_users_by_flags,
+    ]
+);
+#[cfg(feature = "sqlite")]
+store_tests!(
+    sqlite,
+    SqliteUserStore::new(
+        sqlite_pool().await,
+        Arc::new(Argon2PasswordHasher::default())
+    ),
+    [
+        add_and_get_user,
+        update_user,
+        delete_user,
+        list_users,
+        search_users,
+        search_non_ascii_names,
+        filter_users_by_flags,
+    ]
+);
\ No newline at end of file
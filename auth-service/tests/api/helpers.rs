@@ -1,64 +1,117 @@
+use argon2::Params;
 use core::panic;
-use reqwest::{cookie::Jar, Client};
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    Client, Url,
+};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
 };
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use wiremock::MockServer;
+use std::{sync::Arc, time::Duration};
+use wiremock::{MockServer, Request};
 
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    domain::Email,
+    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType, UserStoreType},
+    domain::{Email, PasswordHasherType, PasswordPolicy},
     get_postgres_pool, get_redis_client,
     services::{
-        data_stores::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
+        argon2_password_hasher::Argon2PasswordHasher,
+        data_stores::{
+            PostgresAuditLogStore, PostgresLoginHistoryStore, PostgresTrustedDeviceStore,
+            PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore,
+        },
+        password_pepper::PasswordPepper,
         postmark_email_client::PostmarkEmailClient,
+        redis_connection::RedisConnection,
+    },
+    utils::{
+        constants::{
+            test, CSRF_COOKIE_NAME, CSRF_HEADER_NAME, DATABASE_URL, DEFAULT_ARGON2_MEMORY_COST_KIB,
+            DEFAULT_ARGON2_PARALLELISM, DEFAULT_ARGON2_TIME_COST, DEFAULT_REDIS_HOSTNAME,
+            PROOF_OF_WORK_HEADER_NAME,
+        },
+        cors::CorsConfig,
+        ip_filter::IpFilter,
     },
-    utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
     Application,
 };
 
 use std::str::FromStr;
 use uuid::Uuid;
 
+pub const ADMIN_API_TOKEN: &str = "test-admin-token";
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
+    pub pg_pool: PgPool,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub ip_filter: IpFilter,
     pub http_client: reqwest::Client,
     pub email_server: MockServer,
     pub db_name: String,
     pub clean_up_called: bool,
 }
 
+/// How a test app differs from the one `TestApp::new` builds.
+#[derive(Default)]
+pub struct TestAppConfig {
+    /// Replaces [`test_password_hasher`].
+    pub password_hasher: Option<PasswordHasherType>,
+    pub signup_hide_existing_users: bool,
+}
+
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_config(TestAppConfig::default()).await
+    }
+
+    pub async fn with_config(config: TestAppConfig) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(&db_name).await;
-        let redis_connection = Arc::new(RwLock::new(configure_redis()));
+        let redis_connection = configure_redis();
 
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-        let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-            redis_connection.clone(),
-        )));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
+        let password_hasher = config.password_hasher.unwrap_or_else(test_password_hasher);
+        let user_store: UserStoreType =
+            Arc::new(PostgresUserStore::new(pg_pool.clone(), password_hasher));
+        let banned_token_store = Arc::new(RedisBannedTokenStore::new(redis_connection.clone()));
+        let two_fa_code_store = Arc::new(RedisTwoFACodeStore::new(redis_connection));
+        let login_history_store = Arc::new(PostgresLoginHistoryStore::new(pg_pool.clone()));
+        let audit_log_store = Arc::new(PostgresAuditLogStore::new(pg_pool.clone()));
+        let trusted_device_store = Arc::new(PostgresTrustedDeviceStore::new(pg_pool.clone()));
 
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
         let email_client = Arc::new(configure_postmark_email_client(base_url));
+        let password_policy = Arc::new(PasswordPolicy::default());
 
         let app_state = AppState::new(
-            user_store,
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
+            login_history_store,
+            audit_log_store,
+            trusted_device_store,
             email_client,
-        );
+            password_policy,
+        )
+        .with_admin_api_token(Secret::new(ADMIN_API_TOKEN.to_owned()))
+        .with_signup_hide_existing_users(config.signup_hide_existing_users);
+
+        let cors_config = CorsConfig::parse(
+            test::cors::ALLOWED_ORIGINS,
+            test::cors::ALLOWED_METHODS,
+            test::cors::ALLOWED_HEADERS,
+        )
+        .unwrap();
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let ip_filter = IpFilter::default();
+
+        let app = Application::build(app_state, test::APP_ADDRESS, cors_config, ip_filter.clone())
             .await
             .expect("Failed to build app");
 
@@ -76,8 +129,11 @@ impl TestApp {
         Self {
             address,
             cookie_jar,
+            pg_pool,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            ip_filter,
             http_client,
             email_server,
             db_name,
@@ -87,7 +143,7 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -97,8 +153,7 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.http_client
-            .post(&format!("{}/signup", &self.address))
+        self.post("/signup")
             .json(body)
             .send()
             .await
@@ -109,17 +164,55 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.http_client
-            .post(&format!("{}/login", &self.address))
+        self.post("/login")
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn post_login_with_user_agent<Body>(
+        &self,
+        body: &Body,
+        user_agent: &str,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post("/login")
+            .header(reqwest::header::USER_AGENT, user_agent)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_login_with_proof_of_work<Body>(
+        &self,
+        body: &Body,
+        solution: &str,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post("/login")
+            .header(PROOF_OF_WORK_HEADER_NAME, solution)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_pow_challenge(&self) -> reqwest::Response {
         self.http_client
-            .post(format!("{}/logout", &self.address))
+            .get(format!("{}/pow-challenge", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post("/logout")
             .send()
             .await
             .expect("Failed to execute request.")
@@ -129,8 +222,7 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.http_client
-            .post(format!("{}/verify-2fa", &self.address))
+        self.post("/verify-2fa")
             .json(body)
             .send()
             .await
@@ -141,14 +233,143 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
+        self.post("/verify-token")
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_report_login(&self, token: &str) -> reqwest::Response {
         self.http_client
-            .post(format!("{}/verify-token", &self.address))
+            .get(format!("{}/report-login", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_report_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post("/report-login")
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post("/reset-password")
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post("/change-password")
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_events(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-events", &self.address))
+            .bearer_auth(ADMIN_API_TOKEN)
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trusted_devices(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/trusted-devices", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_trusted_device(&self, id: &str) -> reqwest::Response {
+        self.with_csrf_token(
+            self.http_client
+                .delete(format!("{}/trusted-devices/{}", &self.address, id)),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.")
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.with_csrf_token(self.http_client.post(format!("{}{}", &self.address, path)))
+    }
+
+    // Sends the CSRF cookie back in the header, the same way assets/app.js does.
+    fn with_csrf_token(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.csrf_token() {
+            Some(token) => request.header(CSRF_HEADER_NAME, token),
+            None => request,
+        }
+    }
+
+    pub fn csrf_token(&self) -> Option<String> {
+        let url = Url::parse(&self.address).expect("Failed to parse URL");
+        let cookies = self.cookie_jar.cookies(&url)?;
+
+        cookies.to_str().ok()?.split("; ").find_map(|cookie| {
+            cookie
+                .strip_prefix(CSRF_COOKIE_NAME.as_str())
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(str::to_owned)
+        })
+    }
+
+    /// The code of the last 2FA email sent to `email`. The app only stores
+    /// hashes of codes, so tests read them the way users do.
+    pub async fn get_2fa_code(&self, email: &str) -> String {
+        let requests = self
+            .email_server
+            .received_requests()
+            .await
+            .expect("Request recording is disabled");
+
+        requests
+            .iter()
+            .rev()
+            .filter_map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).ok())
+            .find(|body| body["To"] == email && body["Subject"] == "2FA Code")
+            .and_then(|body| body["TextBody"].as_str().map(str::to_owned))
+            .expect("No 2FA email sent")
+    }
+
+    /// The emails sent so far, once there are at least `count` of them. Some
+    /// emails are sent in the background, after the response.
+    pub async fn received_emails(&self, count: usize) -> Vec<Request> {
+        let mut requests = vec![];
+        for _ in 0..50 {
+            requests = self
+                .email_server
+                .received_requests()
+                .await
+                .expect("Request recording is disabled");
+            if requests.len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        requests
+    }
+
     pub async fn clean_up(&mut self) {
         if self.clean_up_called {
             return;
@@ -168,6 +389,27 @@ impl Drop for TestApp {
     }
 }
 
+/// Argon2 with the cheap default parameters and a single pepper.
+pub fn test_password_hasher() -> PasswordHasherType {
+    test_password_hasher_with_peppers("1:test-pepper")
+}
+
+/// Like [`test_password_hasher`], with peppers in the `PASSWORD_PEPPERS` format.
+pub fn test_password_hasher_with_peppers(peppers: &str) -> PasswordHasherType {
+    let password_hash_params = Params::new(
+        DEFAULT_ARGON2_MEMORY_COST_KIB,
+        DEFAULT_ARGON2_TIME_COST,
+        DEFAULT_ARGON2_PARALLELISM,
+        None,
+    )
+    .unwrap();
+
+    Arc::new(Argon2PasswordHasher::new(
+        password_hash_params,
+        PasswordPepper::parse(&Secret::new(peppers.to_owned())).unwrap(),
+    ))
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
@@ -248,13 +490,10 @@ async fn configure_database(db_conn_string: &Secret<String>, db_name: &str) {
         .expect("Failed to migrate the database");
 }
 
-fn configure_redis() -> redis::Connection {
+fn configure_redis() -> RedisConnection {
     let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_owned();
 
-    get_redis_client(redis_hostname)
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+    RedisConnection::new(get_redis_client(redis_hostname).expect("Failed to get Redis client"))
 }
 
 fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
@@ -0,0 +1,78 @@
+use auth_service::{utils::ip_filter::IpFilterRules, ErrorResponse};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_return_403_if_ip_address_denied() {
+    let response = app.get_root().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rules =
+        IpFilterRules::parse(r#"{ "groups": { "introspection": { "allow": ["10.0.0.0/8"] } } }"#)
+            .unwrap();
+    app.ip_filter.reload(rules).await;
+
+    // Only the introspection routes are restricted
+    let response = app.get_root().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": "token" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "IP address not allowed".to_owned()
+    );
+
+    app.ip_filter.reload(IpFilterRules::default()).await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": "token" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_use_forwarded_for_only_from_trusted_proxies() {
+    let get_root_forwarded_for = |forwarded_for: &'static str| {
+        app.http_client
+            .get(format!("{}/", &app.address))
+            .header("X-Forwarded-For", forwarded_for)
+            .send()
+    };
+
+    let rules =
+        IpFilterRules::parse(r#"{ "groups": { "default": { "deny": ["203.0.113.0/24"] } } }"#)
+            .unwrap();
+    app.ip_filter.reload(rules).await;
+
+    // The test client is not a trusted proxy, so the header is ignored
+    let response = get_root_forwarded_for("203.0.113.7").await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rules = IpFilterRules::parse(
+        r#"{
+            "trustedProxies": ["127.0.0.1"],
+            "groups": { "default": { "deny": ["203.0.113.0/24"] } }
+        }"#,
+    )
+    .unwrap();
+    app.ip_filter.reload(rules).await;
+
+    let response = get_root_forwarded_for("203.0.113.7").await.unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Entries left of the first untrusted hop could be forged by the client
+    let response = get_root_forwarded_for("203.0.113.7, 198.51.100.1")
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
\ No newline at end of file
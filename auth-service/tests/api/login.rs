@@ -1,6 +1,22 @@
-use crate::helpers::{get_random_email, TestApp};
-use auth_service::domain::Email;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::helpers::{
+    get_random_email, test_password_hasher, test_password_hasher_with_peppers, TestApp,
+    TestAppConfig,
+};
+use argon2::Params;
+use auth_service::domain::{
+    Email, LoginAttemptId, Password, PasswordHash, PasswordHasher, PasswordHasherType, TwoFACode,
+    UserId, MAX_PASSWORD_BYTES,
+};
 use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::services::{
+    argon2_password_hasher::Argon2PasswordHasher, password_pepper::PasswordPepper,
+};
+use auth_service::utils::auth::{validate_token, TOKEN_TTL_SECONDS};
 use auth_service::utils::constants::JWT_COOKIE_NAME;
 use auth_service::ErrorResponse;
 use secrecy::{ExposeSecret, Secret};
@@ -33,10 +49,77 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
+    assert_eq!(
+        auth_cookie.max_age(),
+        Some(std::time::Duration::from_secs(TOKEN_TTL_SECONDS as u64))
+    );
+}
+
+#[api_test]
+async fn should_return_200_if_email_in_other_case() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email.to_uppercase(),
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_put_email_in_auth_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    let claims = validate_token(
+        &Secret::new(auth_cookie.value().to_owned()),
+        app.banned_token_store.clone(),
+    )
+    .await
+    .expect("Failed to validate auth token");
+
+    assert!(UserId::parse(&claims.sub).is_ok());
+    assert!(!auth_cookie.value().contains(&random_email));
 }
 
 #[api_test]
@@ -76,17 +159,17 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let two_fa_code_store = app.two_fa_code_store.read().await;
+    let code = app.get_2fa_code(&random_email).await;
 
-    let code_tuple = two_fa_code_store
-        .get_code(&Email::parse(Secret::new(random_email)).unwrap())
+    // The code from the email belongs to the login attempt from the response
+    app.two_fa_code_store
+        .validate_code(
+            &Email::parse(Secret::new(random_email)).unwrap(),
+            &LoginAttemptId::parse(Secret::new(json_body.login_attempt_id)).unwrap(),
+            &TwoFACode::parse(Secret::new(code)).unwrap(),
+        )
         .await
-        .expect("Failed to get 2FA code");
-
-    assert_eq!(
-        code_tuple.0.as_ref().expose_secret(),
-        &json_body.login_attempt_id
-    );
+        .expect("Failed to validate 2FA code");
 }
 
 #[api_test]
@@ -103,11 +186,11 @@ async fn should_return_400_if_invalid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    let oversized_password = "a".repeat(MAX_PASSWORD_BYTES + 1);
     let test_cases = vec![
         ("invalid_email", "password123"),
-        (random_email.as_str(), "invalid"),
+        (random_email.as_str(), oversized_password.as_str()),
         ("", "password123"),
-        (random_email.as_str(), ""),
         ("", ""),
     ];
 
@@ -181,6 +264,210 @@ async fn should_return_401_if_incorrect_credentials() {
     }
 }
 
+/// Counts how often a password is checked against the dummy hash.
+struct DummyCountingHasher {
+    inner: PasswordHasherType,
+    dummy_verifications: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl PasswordHasher for DummyCountingHasher {
+    async fn hash(&self, password: &Password) -> color_eyre::Result<PasswordHash> {
+        self.inner.hash(password).await
+    }
+
+    async fn verify(
+        &self,
+        password: &Password,
+        password_hash: &PasswordHash,
+    ) -> color_eyre::Result<bool> {
+        self.inner.verify(password, password_hash).await
+    }
+
+    async fn verify_dummy(&self, password: &Password) -> color_eyre::Result<()> {
+        self.dummy_verifications.fetch_add(1, Ordering::SeqCst);
+        self.inner.verify_dummy(password).await
+    }
+
+    fn needs_rehash(&self, password_hash: &PasswordHash) -> bool {
+        self.inner.needs_rehash(password_hash)
+    }
+}
+
+#[tokio::test]
+async fn should_verify_dummy_hash_if_user_does_not_exist() {
+    let password_hasher = Arc::new(DummyCountingHasher {
+        inner: test_password_hasher(),
+        dummy_verifications: AtomicUsize::new(0),
+    });
+    let mut app = TestApp::with_config(TestAppConfig {
+        password_hasher: Some(password_hasher.clone()),
+        ..Default::default()
+    })
+    .await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // A wrong password is checked against the real hash
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password"
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        password_hasher.dummy_verifications.load(Ordering::SeqCst),
+        0
+    );
+
+    // An unknown user costs a hash as well, and gets the same answer
+    let login_body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "password123"
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Incorrect credentials".to_owned()
+    );
+    assert_eq!(
+        password_hasher.dummy_verifications.load(Ordering::SeqCst),
+        1
+    );
+
+    app.clean_up().await;
+}
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+    app.post_login(&login_body).await
+}
+
+// Stands in for a hash stored by an older configuration.
+async fn replace_password_hash(app: &TestApp, email: &str, password_hasher: PasswordHasherType) {
+    let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+    let password_hash = password_hasher.hash(&password).await.unwrap();
+
+    sqlx::query(
+        "UPDATE users SET password_hash = $1, password_pepper_version = $2 WHERE email = $3",
+    )
+    .bind(password_hash.as_ref().expose_secret())
+    .bind(password_hash.pepper_version())
+    .bind(email)
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+}
+
+async fn stored_password_hash(app: &TestApp, email: &str) -> PasswordHash {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    app.user_store.get_user(&email).await.unwrap().password_hash
+}
+
+#[api_test]
+async fn should_rehash_password_hashed_with_weaker_params() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    let weaker_password_hasher = Arc::new(Argon2PasswordHasher::new(
+        Params::new(8, 1, 1, None).unwrap(),
+        PasswordPepper::parse(&Secret::new("1:test-pepper".to_owned())).unwrap(),
+    ));
+    replace_password_hash(&app, &random_email, weaker_password_hasher).await;
+    let outdated = stored_password_hash(&app, &random_email).await;
+    assert!(test_password_hasher().needs_rehash(&outdated));
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rehashed = stored_password_hash(&app, &random_email).await;
+    assert_ne!(rehashed, outdated);
+    assert!(!test_password_hasher().needs_rehash(&rehashed));
+
+    // The new hash still takes the same password
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_rehash_password_peppered_with_old_key() {
+    let mut app = TestApp::with_config(TestAppConfig {
+        password_hasher: Some(test_password_hasher_with_peppers(
+            "1:old-pepper,2:new-pepper",
+        )),
+        ..Default::default()
+    })
+    .await;
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    replace_password_hash(
+        &app,
+        &random_email,
+        test_password_hasher_with_peppers("1:old-pepper"),
+    )
+    .await;
+    assert_eq!(
+        stored_password_hash(&app, &random_email)
+            .await
+            .pepper_version(),
+        Some(1)
+    );
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        stored_password_hash(&app, &random_email)
+            .await
+            .pepper_version(),
+        Some(2)
+    );
+
+    app.clean_up().await;
+}
+
+#[api_test]
+async fn should_return_401_if_pepper_of_password_hash_is_not_configured() {
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+
+    replace_password_hash(
+        &app,
+        &random_email,
+        test_password_hasher_with_peppers("7:removed-pepper"),
+    )
+    .await;
+
+    let response = login(&app, &random_email).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Incorrect credentials".to_owned()
+    );
+}
+
 #[api_test]
 async fn should_return_422_if_malformed_credentials() {
     let random_email = get_random_email();
@@ -215,4 +502,39 @@ async fn should_return_422_if_malformed_credentials() {
             test_case
         );
     }
+}
+
+#[api_test]
+async fn should_only_allow_cors_from_configured_origins() {
+    let test_cases = [
+        ("http://localhost:8000", true),
+        ("https://app.example.com", true),
+        ("https://example.com", false),
+        ("http://localhost:3001", false),
+    ];
+
+    for (origin, allowed) in test_cases {
+        let response = app
+            .http_client
+            .request(reqwest::Method::OPTIONS, format!("{}/login", &app.address))
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type")
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        let allow_origin = response.headers().get("access-control-allow-origin");
+
+        if allowed {
+            assert_eq!(
+                allow_origin.unwrap(),
+                origin,
+                "Failed for origin: {}",
+                origin
+            );
+        } else {
+            assert!(allow_origin.is_none(), "Failed for origin: {}", origin);
+        }
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    utils::constants::{CSRF_HEADER_NAME, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
 use reqwest::Url;
 use secrecy::Secret;
 use test_helpers::api_test;
@@ -30,7 +33,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -43,13 +46,15 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(auth_cookie.value().is_empty());
+    assert_eq!(auth_cookie.path(), Some("/"));
+    assert_eq!(auth_cookie.max_age(), Some(std::time::Duration::ZERO));
 
-    let banned_token_store = app.banned_token_store.read().await;
-    let contains_token = banned_token_store
+    let contains_token = app
+        .banned_token_store
         .contains_token(&token)
         .await
         .expect("Failed to check if token is banned");
@@ -69,7 +74,7 @@ async fn should_return_400_if_jwt_cookie_missing() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME);
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME);
 
     assert!(auth_cookie.is_none());
 
@@ -108,7 +113,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -118,7 +123,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(auth_cookie.value().is_empty());
@@ -138,11 +143,14 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
 #[api_test]
 async fn should_return_401_if_invalid_token() {
+    // pick up a CSRF cookie first, like the UI does when the page loads
+    app.get_root().await;
+
     // add invalid cookie
     app.cookie_jar.add_cookie_str(
         &format!(
             "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
-            JWT_COOKIE_NAME
+            *JWT_COOKIE_NAME
         ),
         &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
     );
@@ -153,7 +161,7 @@ async fn should_return_401_if_invalid_token() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME);
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME);
 
     assert!(auth_cookie.is_none());
 
@@ -165,4 +173,65 @@ async fn should_return_401_if_invalid_token() {
             .error,
         "Invalid auth token".to_owned()
     );
+}
+
+#[api_test]
+async fn should_return_403_if_csrf_token_missing_or_mismatched() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let csrf_token = app.csrf_token().expect("No CSRF cookie found");
+
+    let test_cases = [
+        None,
+        Some("invalid".to_owned()),
+        Some(format!("{}0", csrf_token)),
+    ];
+
+    for test_case in test_cases {
+        let request = app.http_client.post(format!("{}/logout", &app.address));
+        let request = match &test_case {
+            Some(token) => request.header(CSRF_HEADER_NAME, token),
+            None => request,
+        };
+        let response = request.send().await.expect("Failed to execute request.");
+
+        assert_eq!(
+            response.status().as_u16(),
+            403,
+            "Failed for CSRF header: {:?}",
+            test_case
+        );
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid CSRF token".to_owned()
+        );
+    }
+
+    // the session is still usable with the right token
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
 }
\ No newline at end of file
@@ -1,7 +1,15 @@
 mod helpers;
 mod root;
+mod audit_events;
+mod change_password;
+mod data_stores;
+mod ip_filter;
 mod login;
 mod logout;
+mod proof_of_work;
+mod report_login;
+mod reset_password;
 mod signup;
+mod trusted_devices;
 mod verify_2fa;
 mod verify_token;
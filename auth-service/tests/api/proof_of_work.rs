@@ -0,0 +1,145 @@
+use auth_service::{
+    utils::proof_of_work::{ProofOfWorkChallenge, MIN_DIFFICULTY},
+    ErrorResponse,
+};
+use sha2::{Digest, Sha256};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn solve(challenge: &ProofOfWorkChallenge) -> String {
+    (0u64..)
+        .map(|nonce| format!("{}:{}", challenge.challenge, nonce))
+        .find(|solution| {
+            let digest = Sha256::digest(solution.as_bytes());
+            let zeros = digest
+                .iter()
+                .position(|byte| *byte != 0)
+                .map(|i| i as u32 * 8 + digest[i].leading_zeros())
+                .unwrap_or(256);
+
+            zeros >= u32::from(challenge.difficulty)
+        })
+        .unwrap()
+}
+
+async fn fail_logins(app: &TestApp, login_body: &serde_json::Value, attempts: usize) {
+    for _ in 0..attempts {
+        let response = app.post_login(login_body).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
+
+#[api_test]
+async fn should_require_proof_of_work_after_failed_logins() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password"
+    });
+
+    fail_logins(&app, &wrong_login_body, 5).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 428);
+
+    let error_response = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(error_response.error, "Proof of work required".to_owned());
+
+    let response = app.get_pow_challenge().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let challenge = response
+        .json::<ProofOfWorkChallenge>()
+        .await
+        .expect("Could not deserialize response body to ProofOfWorkChallenge");
+
+    assert_eq!(challenge.difficulty, MIN_DIFFICULTY);
+
+    let solution = solve(&challenge);
+
+    let response = app
+        .post_login_with_proof_of_work(&login_body, &solution)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A solution can only be used once
+    let response = app
+        .post_login_with_proof_of_work(&login_body, &solution)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 428);
+}
+
+#[api_test]
+async fn should_return_428_if_proof_of_work_is_invalid() {
+    let random_email = get_random_email();
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    fail_logins(&app, &login_body, 5).await;
+
+    let response = app.get_pow_challenge().await;
+
+    let challenge = response
+        .json::<ProofOfWorkChallenge>()
+        .await
+        .expect("Could not deserialize response body to ProofOfWorkChallenge");
+
+    let response = app
+        .post_login_with_proof_of_work(&login_body, &format!("{}:0", challenge.challenge))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 428);
+
+    let response = app
+        .post_login_with_proof_of_work(&login_body, "not-a-challenge:0")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 428);
+}
+
+#[api_test]
+async fn should_not_require_proof_of_work_before_failed_logins() {
+    let response = app.get_pow_challenge().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let random_email = get_random_email();
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
\ No newline at end of file
@@ -0,0 +1,266 @@
+use auth_service::{
+    domain::REPORT_TOKEN_MAX_AGE_DAYS, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
+};
+use test_helpers::api_test;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+const CHROME: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+
+#[api_test]
+async fn should_send_email_for_login_from_new_device() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // The first login and logins from known devices are not reported
+    for user_agent in [FIREFOX, FIREFOX, CHROME, CHROME] {
+        let response = app
+            .post_login_with_user_agent(&login_body, user_agent)
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let requests = app.received_emails(1).await;
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+
+    assert_eq!(body["Subject"], "New sign-in to your account");
+
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.contains("Chrome on Windows"));
+    assert!(text_body.contains("/report-login?token="));
+}
+
+#[api_test]
+async fn should_revoke_session_and_require_password_reset() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login_with_user_agent(&login_body, FIREFOX).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login_with_user_agent(&login_body, CHROME).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let session_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let report_token = report_token_from_email(&app).await;
+
+    let response = app
+        .post_report_login(&serde_json::json!({ "token": report_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": session_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login_with_user_agent(&login_body, FIREFOX).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Password reset required".to_owned()
+    );
+}
+
+#[api_test]
+async fn following_the_link_should_not_report_login() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login_with_user_agent(&login_body, FIREFOX).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login_with_user_agent(&login_body, CHROME).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let session_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let report_token = report_token_from_email(&app).await;
+
+    // What a link scanner or prefetcher does with the email
+    let response = app.get_report_login(&report_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.url().query(),
+        Some(format!("report_token={}", report_token).as_str())
+    );
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": session_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login_with_user_agent(&login_body, FIREFOX).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_login_is_too_old() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    for user_agent in [FIREFOX, CHROME] {
+        let response = app
+            .post_login_with_user_agent(&login_body, user_agent)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let report_token = report_token_from_email(&app).await;
+
+    sqlx::query("UPDATE login_history SET logged_in_at = now() - make_interval(days => $1)")
+        .bind(REPORT_TOKEN_MAX_AGE_DAYS)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to backdate logins");
+
+    let response = app
+        .post_report_login(&serde_json::json!({ "token": report_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_login_with_user_agent(&login_body, FIREFOX).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_403_if_csrf_token_missing() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/report-login", &app.address))
+        .json(&serde_json::json!({ "token": "0".repeat(64) }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_token() {
+    let test_cases = [
+        "not-a-token".to_owned(),
+        // Well formed, but never sent to anyone
+        "0".repeat(64),
+    ];
+
+    // Picks up a CSRF cookie, like the confirmation page does
+    app.get_root().await;
+
+    for token in test_cases {
+        let response = app
+            .post_report_login(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            token
+        );
+    }
+}
+
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+pub async fn report_token_from_email(app: &TestApp) -> String {
+    let requests = app.received_emails(1).await;
+    let body: serde_json::Value =
+        serde_json::from_slice(&requests.last().expect("No email sent").body).unwrap();
+
+    body["TextBody"]
+        .as_str()
+        .and_then(|text_body| text_body.split("/report-login?token=").nth(1))
+        .map(|token| token.chars().take(64).collect())
+        .expect("No report link in email")
+}
\ No newline at end of file
@@ -0,0 +1,187 @@
+use auth_service::{
+    routes::ResetPasswordResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
+};
+use test_helpers::api_test;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+use crate::report_login::report_token_from_email;
+
+#[api_test]
+async fn should_return_200_and_allow_login_with_new_password() {
+    let random_email = get_random_email();
+    let (report_token, _) = report_login(&app, &random_email).await;
+
+    let reset_body = serde_json::json!({
+        "token": report_token,
+        "password": "a brand new password",
+    });
+
+    let response = app.post_reset_password(&reset_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ResetPasswordResponse>()
+            .await
+            .expect("Could not deserialize response body to ResetPasswordResponse")
+            .message,
+        "Password reset successfully!".to_owned()
+    );
+
+    let old_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app
+        .post_login_with_user_agent(&old_login_body, "curl/8.5.0")
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let new_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "a brand new password",
+    });
+
+    let response = app
+        .post_login_with_user_agent(&new_login_body, "curl/8.5.0")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token is used up once the password has been reset
+    let response = app.post_reset_password(&reset_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_revoke_sessions_that_were_not_reported() {
+    let random_email = get_random_email();
+    let (report_token, first_session_token) = report_login(&app, &random_email).await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": first_session_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let reset_body = serde_json::json!({
+        "token": report_token,
+        "password": "a brand new password",
+    });
+
+    let response = app.post_reset_password(&reset_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": first_session_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_login_was_not_reported() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let test_cases = [
+        serde_json::json!({
+            "token": "not-a-token",
+            "password": "a brand new password",
+        }),
+        serde_json::json!({
+            "token": "0".repeat(64),
+            "password": "a brand new password",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_reset_password(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_400_if_password_violates_policy() {
+    let random_email = get_random_email();
+    let (report_token, _) = report_login(&app, &random_email).await;
+
+    let reset_body = serde_json::json!({
+        "token": report_token,
+        "password": "a".repeat(129),
+    });
+
+    let response = app.post_reset_password(&reset_body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+// Signs up, logs in from two devices and reports the second login. Returns
+// the report token and the session token of the first login.
+async fn report_login(app: &TestApp, email: &str) -> (String, String) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let mut session_tokens = vec![];
+    for user_agent in ["curl/8.5.0", "curl/8.6.0"] {
+        let response = app
+            .post_login_with_user_agent(&login_body, user_agent)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let auth_cookie = response
+            .cookies()
+            .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
+            .expect("No auth cookie found");
+        session_tokens.push(auth_cookie.value().to_owned());
+    }
+
+    let report_token = report_token_from_email(app).await;
+
+    let response = app
+        .post_report_login(&serde_json::json!({ "token": report_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    (report_token, session_tokens.swap_remove(0))
+}
\ No newline at end of file
@@ -0,0 +1,48 @@
+use auth_service::utils::constants::CSRF_COOKIE_NAME;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn root_returns_auth_ui_and_sets_csrf_cookie() {
+    let response = app.get_root().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+
+    let csrf_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == *CSRF_COOKIE_NAME)
+        .expect("No CSRF cookie found");
+
+    assert!(!csrf_cookie.value().is_empty());
+    assert!(!csrf_cookie.http_only());
+
+    // an existing CSRF cookie is kept
+    let response = app.get_root().await;
+
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != *CSRF_COOKIE_NAME));
+}
+
+#[api_test]
+async fn root_cannot_be_framed() {
+    let response = app.get_root().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let headers = response.headers();
+    let csp = headers
+        .get("content-security-policy")
+        .expect("No CSP header found")
+        .to_str()
+        .unwrap();
+
+    assert!(csp.contains("frame-ancestors 'none'"));
+    assert!(csp.contains("script-src 'self'"));
+    assert_eq!(headers.get("x-frame-options").unwrap(), "DENY");
+    assert_eq!(headers.get("x-content-type-options").unwrap(), "nosniff");
+    assert_eq!(headers.get("referrer-policy").unwrap(), "same-origin");
+    assert!(headers.contains_key("strict-transport-security"));
+}
\ No newline at end of file
@@ -1,7 +1,9 @@
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::{domain::MAX_PASSWORD_BYTES, routes::SignupResponse, ErrorResponse};
 use test_helpers::api_test;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
 
-use crate::helpers::{get_random_email, TestApp};
+use crate::helpers::{get_random_email, TestApp, TestAppConfig};
 
 #[api_test]
 async fn should_return_201_if_valid_input() {
@@ -40,11 +42,6 @@ async fn should_return_400_if_invalid_input() {
             "password": "password123",
             "requires2FA": true
         }),
-        serde_json::json!({
-            "email": random_email,
-            "password": "",
-            "requires2FA": true
-        }),
         serde_json::json!({
             "email": "",
             "password": "",
@@ -57,7 +54,7 @@ async fn should_return_400_if_invalid_input() {
         }),
         serde_json::json!({
             "email": random_email,
-            "password": "invalid",
+            "password": "a".repeat(MAX_PASSWORD_BYTES + 1),
             "requires2FA": true
         }),
     ];
@@ -77,6 +74,55 @@ async fn should_return_400_if_invalid_input() {
     }
 }
 
+#[api_test]
+async fn should_return_400_if_password_violates_policy() {
+    let random_email = get_random_email();
+    let local_part = random_email.split('@').next().unwrap().to_owned();
+
+    let test_cases = [
+        (
+            "invalid".to_owned(),
+            "Password must be at least 8 characters long",
+        ),
+        (
+            "a".repeat(129),
+            "Password must be at most 128 characters long",
+        ),
+        (
+            local_part,
+            "Password must not be similar to the email address",
+        ),
+    ];
+
+    for (password, reason) in test_cases {
+        let signup_body = serde_json::json!({
+            "email": random_email,
+            "password": password,
+            "requires2FA": true
+        });
+
+        let response = app.post_signup(&signup_body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            signup_body
+        );
+
+        let error_response = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+
+        assert_eq!(error_response.error, "Password does not meet requirements");
+        assert!(
+            error_response.details.contains(&reason.to_owned()),
+            "Failed for input: {:?}",
+            signup_body
+        );
+    }
+}
+
 #[api_test]
 async fn should_return_409_if_email_already_exists() {
     let random_email = get_random_email();
@@ -105,6 +151,84 @@ async fn should_return_409_if_email_already_exists() {
     );
 }
 
+#[api_test]
+async fn should_return_409_if_email_exists_in_other_case() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let signup_body = serde_json::json!({
+        "email": random_email.to_uppercase(),
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn should_return_201_and_notify_owner_if_email_exists_and_existing_users_are_hidden() {
+    let mut app = TestApp::with_config(TestAppConfig {
+        signup_hide_existing_users: true,
+        ..Default::default()
+    })
+    .await;
+    let random_email = get_random_email();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let first = app.post_signup(&signup_body).await;
+    assert_eq!(first.status().as_u16(), 201);
+    let first = first.json::<SignupResponse>().await.unwrap();
+
+    let signup_body = serde_json::json!({
+        "email": random_email.to_uppercase(),
+        "password": "other-password123",
+        "requires2FA": true
+    });
+
+    let second = app.post_signup(&signup_body).await;
+    assert_eq!(second.status().as_u16(), 201);
+    assert_eq!(second.json::<SignupResponse>().await.unwrap(), first);
+
+    let requests = app.received_emails(1).await;
+    assert_eq!(requests.len(), 1);
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["To"].as_str().unwrap().to_lowercase(), random_email);
+    assert_eq!(body["Subject"], "Sign-up attempt");
+
+    // The account is left as it was
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
 #[api_test]
 async fn should_return_422_if_malformed_input() {
     let random_email = get_random_email();
@@ -139,4 +263,22 @@ async fn should_return_422_if_malformed_input() {
             test_case
         );
     }
+}
+
+#[api_test]
+async fn should_send_security_headers() {
+    let response = app
+        .post_signup(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    let headers = response.headers();
+
+    assert_eq!(
+        headers.get("content-security-policy").unwrap(),
+        "default-src 'none'; frame-ancestors 'none'"
+    );
+    assert_eq!(headers.get("x-frame-options").unwrap(), "DENY");
+    assert_eq!(headers.get("x-content-type-options").unwrap(), "nosniff");
+    assert_eq!(headers.get("referrer-policy").unwrap(), "no-referrer");
+    assert!(headers.contains_key("strict-transport-security"));
 }
\ No newline at end of file
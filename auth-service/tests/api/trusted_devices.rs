@@ -0,0 +1,238 @@
+use auth_service::{
+    domain::AuthMethod,
+    routes::{TrustedDevicesResponse, TwoFactorAuthResponse},
+    utils::{
+        auth::{Claims, TOKEN_TTL_SECONDS},
+        constants::{JWT_COOKIE_NAME, JWT_SECRET, TRUSTED_DEVICE_COOKIE_NAME},
+    },
+    ErrorResponse,
+};
+use chrono::Utc;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use test_helpers::api_test;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+pub async fn signup_with_2fa(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+pub async fn login_with_2fa(app: &TestApp, email: &str, trust_device: bool) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = app.get_2fa_code(email).await;
+
+    let request_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+        "trustDevice": trust_device
+    });
+
+    app.post_verify_2fa(&request_body).await
+}
+
+#[api_test]
+async fn should_skip_2fa_on_trusted_device() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    // The 2FA code of the first login and of the login from another browser
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let trusted_device_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == *TRUSTED_DEVICE_COOKIE_NAME)
+        .expect("No trusted device cookie found");
+
+    assert!(trusted_device_cookie.http_only());
+    assert!(trusted_device_cookie.expires().is_some());
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == *JWT_COOKIE_NAME));
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let devices = response
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse")
+        .devices;
+
+    assert_eq!(devices.len(), 1);
+    assert!(devices[0].current);
+
+    // The cookie is bound to the browser it was issued to
+    let response = app
+        .post_login_with_user_agent(&login_body, "curl/8.5.0")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[api_test]
+async fn should_require_2fa_after_revoking_trusted_device() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = login_with_2fa(&app, &random_email, true).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let devices = app
+        .get_trusted_devices()
+        .await
+        .json::<TrustedDevicesResponse>()
+        .await
+        .expect("Could not deserialize response body to TrustedDevicesResponse")
+        .devices;
+
+    let id = devices[0].id.to_string();
+
+    let response = app.delete_trusted_device(&id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let trusted_device_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == *TRUSTED_DEVICE_COOKIE_NAME)
+        .expect("No trusted device cookie found");
+
+    assert!(trusted_device_cookie.value().is_empty());
+
+    let response = app.delete_trusted_device(&id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Trusted device not found".to_owned()
+    );
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with_2fa(&app, &random_email, false).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!response
+        .cookies()
+        .any(|cookie| cookie.name() == *TRUSTED_DEVICE_COOKIE_NAME));
+}
+
+#[api_test]
+async fn should_reject_auth_token_with_email_subject() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Issued before users had ids, and ACCEPT_EMAIL_TOKEN_SUBJECTS is not set
+    let claims = Claims {
+        sub: random_email,
+        exp: (Utc::now().timestamp() + TOKEN_TTL_SECONDS) as usize,
+        jti: Uuid::new_v4().to_string(),
+        auth_time: Utc::now().timestamp() as usize,
+        amr: vec![AuthMethod::Password],
+    };
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+    )
+    .expect("Failed to create token");
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; Path=/", *JWT_COOKIE_NAME, token),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_trusted_devices().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .delete_trusted_device("6f1e5b8e-8f5c-4d4b-9a4b-2d1c6f0e7a3b")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
\ No newline at end of file
@@ -1,17 +1,20 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
+    domain::{LoginAttemptId, TwoFACode, MAX_PENDING_LOGIN_ATTEMPTS},
     routes::TwoFactorAuthResponse,
     utils::constants::JWT_COOKIE_NAME,
     ErrorResponse,
 };
-use secrecy::{ExposeSecret, Secret};
+use secrecy::ExposeSecret;
 use test_helpers::api_test;
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::{get_random_email, TestApp};
+use crate::{
+    helpers::{get_random_email, TestApp},
+    trusted_devices::signup_with_2fa,
+};
 
 #[api_test]
 async fn should_return_200_if_correct_code() {
@@ -53,15 +56,7 @@ async fn should_return_200_if_correct_code() {
 
     let login_attempt_id = response_body.login_attempt_id;
 
-    let code_tuple = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-        .await
-        .unwrap();
-
-    let code = code_tuple.1.as_ref().expose_secret();
+    let code = app.get_2fa_code(&random_email).await;
 
     let request_body = serde_json::json!({
         "email": random_email,
@@ -75,12 +70,66 @@ async fn should_return_200_if_correct_code() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
 }
 
+#[api_test]
+async fn should_return_200_if_email_in_other_case() {
+    let random_email = get_random_email();
+    // The same address with the local part in uppercase
+    let signup_email = random_email
+        .to_uppercase()
+        .replace("@EXAMPLE.COM", "@example.com");
+
+    let signup_body = serde_json::json!({
+        "email": signup_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    // Mail goes to the address the user signed up with
+    let code = app.get_2fa_code(&signup_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+        "trustDevice": true
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
 #[api_test]
 async fn should_return_400_if_invalid_input() {
     let random_email = get_random_email();
@@ -175,15 +224,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let login_attempt_id = response_body.login_attempt_id;
 
-    let code_tuple = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-        .await
-        .unwrap();
-
-    let two_fa_code = code_tuple.1.as_ref();
+    let two_fa_code = app.get_2fa_code(&random_email).await;
 
     // --------------------------
 
@@ -195,12 +236,12 @@ async fn should_return_401_if_incorrect_credentials() {
         (
             incorrect_email.as_str(),
             login_attempt_id.as_str(),
-            two_fa_code.expose_secret().as_str(),
+            two_fa_code.as_str(),
         ),
         (
             random_email.as_str(),
             incorrect_login_attempt_id.expose_secret(),
-            two_fa_code.expose_secret().as_str(),
+            two_fa_code.as_str(),
         ),
         (
             random_email.as_str(),
@@ -237,7 +278,7 @@ async fn should_return_401_if_incorrect_credentials() {
 }
 
 #[api_test]
-async fn should_return_401_if_old_code() {
+async fn should_accept_code_of_earlier_pending_login() {
     let random_email = get_random_email();
 
     let signup_body = serde_json::json!({
@@ -257,7 +298,7 @@ async fn should_return_401_if_old_code() {
         .mount(&app.email_server)
         .await;
 
-    // First login call
+    // First login call, e.g. on a laptop
 
     let login_body = serde_json::json!({
         "email": random_email,
@@ -268,38 +309,83 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 206);
 
-    let response_body = response
+    let login_attempt_id = response
         .json::<TwoFactorAuthResponse>()
         .await
-        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
 
-    assert_eq!(response_body.message, "2FA required".to_owned());
-    assert!(!response_body.login_attempt_id.is_empty());
+    let code = app.get_2fa_code(&random_email).await;
 
-    let login_attempt_id = response_body.login_attempt_id;
+    // Second login call, e.g. on a phone
 
-    let code_tuple = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-        .await
-        .unwrap();
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    // The first login can still be finished
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
 
-    let code = code_tuple.1.as_ref();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_was_dropped() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
 
-    // Second login call
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(MAX_PENDING_LOGIN_ATTEMPTS as u64 + 1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
 
     let response = app.post_login(&login_body).await;
 
     assert_eq!(response.status().as_u16(), 206);
 
-    // 2FA attempt with old login_attempt_id and code
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let code = app.get_2fa_code(&random_email).await;
+
+    // Newer logins push the oldest one out
+
+    for _ in 0..MAX_PENDING_LOGIN_ATTEMPTS {
+        let response = app.post_login(&login_body).await;
+
+        assert_eq!(response.status().as_u16(), 206);
+    }
 
     let request_body = serde_json::json!({
         "email": random_email,
         "loginAttemptId": login_attempt_id,
-        "2FACode": code.expose_secret()
+        "2FACode": code
     });
 
     let response = app.post_verify_2fa(&request_body).await;
@@ -347,20 +433,12 @@ async fn should_return_401_if_same_code_twice() {
 
     let login_attempt_id = response_body.login_attempt_id;
 
-    let code_tuple = app
-        .two_fa_code_store
-        .read()
-        .await
-        .get_code(&Email::parse(Secret::new(random_email.clone())).unwrap())
-        .await
-        .unwrap();
-
-    let code = code_tuple.1.as_ref();
+    let code = app.get_2fa_code(&random_email).await;
 
     let request_body = serde_json::json!({
         "email": random_email,
         "loginAttemptId": login_attempt_id,
-        "2FACode": code.expose_secret()
+        "2FACode": code
     });
 
     let response = app.post_verify_2fa(&request_body).await;
@@ -369,7 +447,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -379,6 +457,51 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(response.status().as_u16(), 401);
 }
 
+#[api_test]
+async fn should_accept_code_once_if_used_concurrently() {
+    let random_email = get_random_email();
+
+    signup_with_2fa(&app, &random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": app.get_2fa_code(&random_email).await
+    });
+
+    let (first, second) = tokio::join!(
+        app.post_verify_2fa(&request_body),
+        app.post_verify_2fa(&request_body)
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+
+    assert_eq!(statuses, [200, 401]);
+}
+
 #[api_test]
 async fn should_return_422_if_malformed_input() {
     let random_email = get_random_email();
@@ -28,7 +28,7 @@ async fn should_return_200_valid_token() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -93,7 +93,7 @@ async fn should_return_401_if_banned_token() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == *JWT_COOKIE_NAME)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -0,0 +1,94 @@
+//! Cookies with `AUTH_COOKIE_HOST_PREFIX=true`. The cookie names are read from
+//! the environment once per process, so this mode gets a test binary of its
+//! own instead of a module of the API tests.
+
+use std::sync::Once;
+
+use auth_service::utils::{
+    constants::{
+        env::{AUTH_COOKIE_HOST_PREFIX_ENV_VAR, AUTH_COOKIE_SECURE_ENV_VAR},
+        CSRF_COOKIE_NAME, CSRF_HEADER_NAME, JWT_COOKIE_NAME, TRUSTED_DEVICE_COOKIE_NAME,
+    },
+    csrf::csrf_protection,
+};
+use axum::{http::StatusCode, middleware, routing::post, Router};
+
+static HOST_PREFIX: Once = Once::new();
+
+fn enable_host_prefix() {
+    HOST_PREFIX.call_once(|| {
+        std::env::set_var(AUTH_COOKIE_HOST_PREFIX_ENV_VAR, "true");
+        std::env::set_var(AUTH_COOKIE_SECURE_ENV_VAR, "true");
+    });
+}
+
+// A route behind the CSRF protection, like `/logout`.
+async fn spawn_app() -> String {
+    let router = Router::new()
+        .route("/logout", post(|| async { StatusCode::OK }))
+        .layer(middleware::from_fn(csrf_protection));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    address
+}
+
+#[test]
+fn every_cookie_gets_the_prefix() {
+    enable_host_prefix();
+
+    assert_eq!(*JWT_COOKIE_NAME, "__Host-jwt");
+    assert_eq!(*TRUSTED_DEVICE_COOKIE_NAME, "__Host-trusted_device");
+    assert_eq!(*CSRF_COOKIE_NAME, "__Host-csrf_token");
+}
+
+#[tokio::test]
+async fn csrf_token_is_checked_against_the_prefixed_cookie() {
+    enable_host_prefix();
+    let address = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/logout", address))
+        .send()
+        .await
+        .unwrap();
+    let csrf_cookie = response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .find(|value| value.starts_with("__Host-csrf_token="))
+        .expect("No __Host-csrf_token cookie set");
+    assert!(csrf_cookie.contains("Secure"));
+    assert!(csrf_cookie.contains("Path=/"));
+    assert!(!csrf_cookie.contains("Domain"));
+
+    let logout = |cookies: &str, csrf_token: Option<&str>| {
+        let mut request = client
+            .post(format!("{}/logout", address))
+            .header("cookie", cookies);
+        if let Some(csrf_token) = csrf_token {
+            request = request.header(CSRF_HEADER_NAME, csrf_token);
+        }
+        request.send()
+    };
+
+    let response = logout("__Host-jwt=token; __Host-csrf_token=abc", Some("abc"))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = logout("__Host-jwt=token; __Host-csrf_token=abc", None)
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+
+    // The unprefixed cookie is not the one the token is checked against.
+    let response = logout("__Host-jwt=token; csrf_token=abc", Some("abc"))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 403);
+}
\ No newline at end of file